@@ -7,17 +7,23 @@ import { LogPanel } from './LogPanel';
 const STORAGE_KEY = 'whisk-tasks';
 const ACCOUNTS_KEY = 'whisk-selected-accounts';
 const ORDER_KEY = 'whisk-order-counter';
+const MAX_TEMPLATE_TASKS = 1000;
+
+export type Ratio = '16:9' | '9:16' | '1:1' | '4:3' | '3:4';
 
 export interface Task {
   id: string;
   order: number;
   selected: boolean;
   prompt: string;
-  ratio: '16:9' | '9:16' | '1:1';
+  videoPrompt?: string;
+  ratio: Ratio;
   count: number;
   status: 'pending' | 'queued' | 'generating' | 'done' | 'error';
   statusText?: string;
   results: string[];
+  // Backend cache ids for results that were not saved to disk; released with the results.
+  imageIds?: string[];
   error?: string;
   accountId?: string;
   projectLink?: string;
@@ -29,6 +35,27 @@ export interface LogEntry {
   type: 'info' | 'success' | 'error' | 'step';
 }
 
+interface SplitTask {
+  prompt: string;
+  videoPrompt?: string;
+  aspectRatio?: Ratio;
+  count?: number;
+}
+
+// One run of the queue: tasks share the batch id/name, and each account resolves its
+// workflow once so parallel threads land in the same project.
+interface StylePresetInfo {
+  name: string;
+  aspectRatio?: string;
+  count?: number;
+}
+
+interface BatchRun {
+  id: string;
+  name: string;
+  workflows: Map<string, Promise<string | undefined>>;
+}
+
 interface RefImage {
   id: string;
   url: string;
@@ -53,10 +80,15 @@ export default function App() {
   const [isRunning, setIsRunning] = useState(false);
   const [showBulkModal, setShowBulkModal] = useState(false);
   const [bulkText, setBulkText] = useState('');
+  const [bulkTemplate, setBulkTemplate] = useState(false);
+  const [bulkCsv, setBulkCsv] = useState('');
   const [runningTaskIds, setRunningTaskIds] = useState<Set<string>>(new Set());
   const [accountEmails, setAccountEmails] = useState<Record<string, string>>({});
   const [refImages, setRefImages] = useState<RefImage[]>([]);
   const [saveFolder, setSaveFolder] = useState('');
+  const [batchName, setBatchName] = useState('');
+  const [stylePresets, setStylePresets] = useState<StylePresetInfo[]>([]);
+  const [stylePreset, setStylePreset] = useState('');
   const [previewImage, setPreviewImage] = useState<{ url: string; taskOrder: number } | null>(null);
   const [showRefModal, setShowRefModal] = useState(false);
   const stopFlag = useRef(false);
@@ -82,6 +114,12 @@ export default function App() {
     } catch (e) { console.error('Failed to save accounts:', e); }
   }, [selectedAccounts]);
 
+  useEffect(() => {
+    invoke<{ stylePresets?: StylePresetInfo[] }>('get_settings')
+      .then(s => setStylePresets(s.stylePresets || []))
+      .catch(e => console.error('Failed to load style presets:', e));
+  }, []);
+
   // Folder picker helper using Tauri
   const pickFolder = async () => {
     try {
@@ -90,7 +128,16 @@ export default function App() {
     } catch { }
   };
 
-  const globalSettingsRef = useRef({ ratio: '9:16' as '16:9' | '9:16' | '1:1', count: 2 });
+  const globalSettingsRef = useRef({ ratio: '9:16' as Ratio, count: 2 });
+
+  // New tasks take the selected preset's ratio and count; the task's own values are what gets sent.
+  const taskDefaults = () => {
+    const preset = stylePresets.find(p => p.name === stylePreset);
+    return {
+      ratio: (preset?.aspectRatio as Ratio | undefined) ?? globalSettingsRef.current.ratio,
+      count: preset?.count ?? globalSettingsRef.current.count,
+    };
+  };
 
   const log = useCallback((message: string, type: LogEntry['type'] = 'info') => {
     const time = new Date().toLocaleTimeString('vi-VN', { hour12: false });
@@ -103,8 +150,7 @@ export default function App() {
       order: orderCounter.current++,
       selected: false,
       prompt: '',
-      ratio: globalSettingsRef.current.ratio,
-      count: globalSettingsRef.current.count,
+      ...taskDefaults(),
       status: 'pending',
       results: [],
     };
@@ -112,25 +158,81 @@ export default function App() {
     setTasks(prev => [...prev, newTask]);
   };
 
-  const addBulkTasks = () => {
+  // Walks expand_template page by page with the seed of the first page, so the prompts match the preview.
+  const expandBulkTemplate = async (): Promise<SplitTask[] | null> => {
+    const options: { template: string; csvText?: string; seed?: number } = {
+      template: bulkText,
+      csvText: bulkCsv.trim() || undefined,
+    };
+    const prompts: string[] = [];
+    let offset: number | null = 0;
+    try {
+      while (offset !== null) {
+        const page: { prompts: string[]; nextOffset: number | null; count: number; seed: number; missingVariables: string[]; missingWildcards: string[]; warnings: string[] } =
+          await invoke('expand_template', { options, offset });
+        if (page.count > MAX_TEMPLATE_TASKS) {
+          log(`Template expands to ${page.count} prompts (max ${MAX_TEMPLATE_TASKS})`, 'error');
+          return null;
+        }
+        if (offset === 0) {
+          options.seed = page.seed;
+          page.warnings.forEach(w => log(w, 'error'));
+          if (page.missingVariables.length) log(`Missing variables: ${page.missingVariables.join(', ')}`, 'error');
+          if (page.missingWildcards.length) log(`Missing wildcards: ${page.missingWildcards.join(', ')}`, 'error');
+        }
+        prompts.push(...page.prompts);
+        offset = page.nextOffset;
+      }
+    } catch (e) {
+      log(`Template failed: ${e}`, 'error');
+      return null;
+    }
+    return prompts.map(prompt => ({ prompt }));
+  };
+
+  const addBulkTasks = async () => {
     const lines = bulkText.split('\n').map(l => l.trim()).filter(l => l);
     if (lines.length === 0) return;
+    const { ratio, count } = taskDefaults();
+    // Structured scripts keep the ratio/count from each prompt's header; plain text stays one prompt per line.
+    let entries: SplitTask[] = lines.map(prompt => ({ prompt }));
+    if (bulkTemplate) {
+      const expanded = await expandBulkTemplate();
+      if (!expanded) return;
+      entries = expanded;
+    } else {
+      try {
+        const split = await invoke<{ structured: boolean; tasks: SplitTask[]; warnings?: string[] }>('split_prompts', {
+          raw: bulkText,
+          options: { defaultAspectRatio: ratio, defaultCount: count },
+        });
+        // Only formats with per-prompt headers replace the line list; "A cat - sleeping" lines stay as they are.
+        if (split.structured && split.tasks.length > 0) {
+          entries = split.tasks;
+          split.warnings?.forEach(w => log(w, 'error'));
+        }
+      } catch (e) {
+        log(`Split failed, adding one task per line: ${e}`, 'error');
+      }
+    }
     const startOrder = orderCounter.current;
-    const newTasks: Task[] = lines.map((prompt, i) => ({
+    const newTasks: Task[] = entries.map((entry, i) => ({
       id: `task-${Date.now()}-${i}`,
       order: startOrder + i,
       selected: false,
-      prompt,
-      ratio: globalSettingsRef.current.ratio,
-      count: globalSettingsRef.current.count,
+      prompt: entry.prompt,
+      videoPrompt: entry.videoPrompt,
+      ratio: entry.aspectRatio ?? ratio,
+      count: entry.count ?? count,
       status: 'pending' as const,
       results: [],
     }));
-    orderCounter.current = startOrder + lines.length;
+    orderCounter.current = startOrder + newTasks.length;
     localStorage.setItem(ORDER_KEY, String(orderCounter.current));
     setTasks(prev => [...prev, ...newTasks]);
     log(`Added ${newTasks.length} tasks`, 'success');
     setBulkText('');
+    setBulkCsv('');
     setShowBulkModal(false);
   };
 
@@ -138,7 +240,15 @@ export default function App() {
     setTasks(prev => prev.map(t => t.id === id ? { ...t, ...updates } : t));
   }, []);
 
+  // Unsaved images live in backend memory until they are released.
+  const releaseImages = (list: Task[]) => {
+    const ids = list.flatMap(t => t.imageIds || []);
+    if (ids.length === 0) return;
+    invoke<number>('release_images', { ids }).catch(e => console.error('Failed to release images:', e));
+  };
+
   const removeTask = (id: string) => {
+    releaseImages(tasks.filter(t => t.id === id));
     setTasks(prev => {
       const remaining = prev.filter(t => t.id !== id);
       const renumbered = remaining.map((t, idx) => ({ ...t, order: idx + 1 }));
@@ -159,6 +269,7 @@ export default function App() {
   const deleteSelected = () => {
     const selectedIds = tasks.filter(t => t.selected).map(t => t.id);
     if (selectedIds.length === 0) return;
+    releaseImages(tasks.filter(t => t.selected));
     const remaining = tasks.filter(t => !t.selected);
     const renumbered = remaining.map((t, idx) => ({ ...t, order: idx + 1 }));
     setTasks(renumbered);
@@ -167,7 +278,24 @@ export default function App() {
     log(`Deleted ${selectedIds.length} tasks`, 'info');
   };
 
-  const processTask = async (task: Task, accountId: string): Promise<void> => {
+  const workflowFor = (batch: BatchRun, accountId: string, cookies: string): Promise<string | undefined> => {
+    let pending = batch.workflows.get(accountId);
+    if (!pending) {
+      pending = invoke<{ id: string; name: string }>('batch_workflow', { cookies, accountId, batchName: batch.name })
+        .then(wf => {
+          log(`[${accountEmails[accountId] || accountId.slice(-8)}] 📂 Workflow: ${wf.name || wf.id}`, 'info');
+          return wf.id;
+        })
+        .catch(err => {
+          log(`[${accountEmails[accountId] || accountId.slice(-8)}] ⚠️ Workflow: ${err}`, 'error');
+          return undefined;
+        });
+      batch.workflows.set(accountId, pending);
+    }
+    return pending;
+  };
+
+  const processTask = async (task: Task, accountId: string, batch: BatchRun): Promise<void> => {
     setRunningTaskIds(prev => new Set(prev).add(task.id));
     updateTask(task.id, { status: 'queued', statusText: 'Waiting...', accountId });
 
@@ -199,28 +327,24 @@ export default function App() {
         setTasks(prev => { resolve(prev.find(t => t.id === task.id) || task); return prev; });
       });
 
-      let existingWorkflowId: string | undefined;
-      try {
-        const raw = localStorage.getItem('autowhisk_accounts');
-        if (raw) {
-          const accs = JSON.parse(raw);
-          const acc = accs.find((a: any) => a.id === accountId);
-          if (acc?.projectLink) {
-            const parts = acc.projectLink.split('/');
-            existingWorkflowId = parts[parts.length - 1];
-          }
-        }
-      } catch { }
+      const existingWorkflowId = accountCookies ? await workflowFor(batch, accountId, accountCookies) : undefined;
 
-      const result = await invoke<{ success: boolean; images?: { savedPath?: string; encodedImage?: string }[]; error?: string; projectLink?: string; diagInfo?: string }>('generate_image', {
+      const result = await invoke<{ success: boolean; images?: { savedPath?: string; encodedImage?: string; imageId?: string }[]; error?: string; projectLink?: string; diagInfo?: string }>('generate_image', {
         cookies: accountCookies || '',
         bearerToken: accountBearerToken || '',
         headers: accountHeaders,
         prompt: latestTask.prompt,
         aspectRatio: latestTask.ratio,
         count: latestTask.count,
+        videoPrompt: latestTask.videoPrompt,
+        stylePreset: stylePreset || undefined,
         saveFolder: saveFolder || undefined,
         existingWorkflowId,
+        accountId,
+        accountEmail: accountEmails[accountId],
+        batchId: batch.id,
+        batchName: batch.name,
+        taskIndex: task.order,
       });
 
 
@@ -229,6 +353,7 @@ export default function App() {
         const paths = result.images
           .filter(img => img.savedPath || img.encodedImage)
           .map(img => img.savedPath || img.encodedImage || '');
+        const imageIds = result.images.flatMap(img => img.imageId ? [img.imageId] : []);
         log(`[Task #${task.order}] ✅ Done ${paths.length}/${task.count} images`, 'success');
 
         if (result.projectLink) {
@@ -251,6 +376,7 @@ export default function App() {
           status: 'done',
           statusText: `${paths.length} images`,
           results: paths,
+          imageIds,
           projectLink: result.projectLink || '',
           selected: false,
         });
@@ -276,13 +402,19 @@ export default function App() {
     const validTasks = tasksToRun.filter(t => runnableStatuses.includes(t.status) && t.prompt.trim());
     if (validTasks.length === 0) { log('Không có task hợp lệ!', 'error'); return; }
 
+    releaseImages(validTasks);
     validTasks.forEach(t => {
-      updateTask(t.id, { status: 'pending', statusText: undefined, results: [], error: undefined });
+      updateTask(t.id, { status: 'pending', statusText: undefined, results: [], imageIds: [], error: undefined });
     });
 
     setIsRunning(true);
     stopFlag.current = false;
     const totalThreads = selectedAccounts.reduce((sum, a) => sum + a.threads, 0);
+    const batch: BatchRun = {
+      id: `batch-${Date.now()}`,
+      name: batchName.trim() || `Batch ${new Date().toLocaleString()}`,
+      workflows: new Map(),
+    };
     log(`🚀 Chạy ${validTasks.length} tasks với ${selectedAccounts.length} accounts (${totalThreads} total threads)`, 'info');
 
     // Build thread pool
@@ -314,15 +446,13 @@ export default function App() {
               const accounts = JSON.parse(raw);
               const acc = accounts.find((a: any) => a.id === accountId);
               if (acc?.cookies) {
-                let workflowId: string | undefined;
-                if (acc.projectLink) {
-                  const parts = acc.projectLink.split('/');
-                  workflowId = parts[parts.length - 1];
-                }
+                const workflowId = await workflowFor(batch, accountId, acc.cookies);
                 const uploadResult = await invoke<{ success: boolean; uploadedCount?: number; workflowId?: string }>('upload_ref_images', {
                   cookies: acc.cookies,
                   refImages: refImages.map(r => r.url),
                   existingWorkflowId: workflowId,
+                  accountId,
+                  workflowName: batch.name,
                 });
                 if (uploadResult.success) {
                   log(`[${acc.email || accountId.slice(-8)}] 🖼️ Fill ${uploadResult.uploadedCount} ảnh tham chiếu thành công`, 'success');
@@ -342,7 +472,7 @@ export default function App() {
         }
 
         log(`[Thread ${accountId.slice(-8)}-${threadIdx}] Processing task #${task.order}`, 'step');
-        await processTask(task, accountId);
+        await processTask(task, accountId, batch);
 
         // Small delay between tasks on same thread
         await new Promise(r => setTimeout(r, 300));
@@ -441,6 +571,24 @@ export default function App() {
                 {saveFolder || 'Chọn thư mục...'}
               </button>
             </div>
+            <input
+              value={batchName}
+              onChange={e => setBatchName(e.target.value)}
+              disabled={isRunning}
+              placeholder="Tên batch / workflow..."
+              className="px-3 py-1.5 bg-[#1a1a2a] border border-gray-800 focus:border-gray-600 rounded-lg text-xs text-gray-300 placeholder-gray-600 outline-none w-48"
+            />
+            {stylePresets.length > 0 && (
+              <select
+                value={stylePreset}
+                onChange={e => setStylePreset(e.target.value)}
+                disabled={isRunning}
+                className="px-2 py-1.5 bg-[#1a1a2a] border border-gray-800 focus:border-gray-600 rounded-lg text-xs text-gray-300 outline-none"
+              >
+                <option value="">Không dùng style</option>
+                {stylePresets.map(p => <option key={p.name} value={p.name}>{p.name}</option>)}
+              </select>
+            )}
           </div>
         </div>
       </header>
@@ -535,14 +683,27 @@ export default function App() {
               <textarea
                 value={bulkText}
                 onChange={e => setBulkText(e.target.value)}
-                placeholder="One prompt per line..."
+                placeholder={bulkTemplate ? 'A {character} in {location}, {sunny|2::rainy} day, __style__' : 'One prompt per line...'}
                 rows={12}
                 className="w-full px-4 py-3 bg-[#1a1a2a] border border-gray-700 rounded-lg text-sm resize-none focus:outline-none focus:border-cyan-500 focus:ring-1 focus:ring-cyan-500/50 placeholder-gray-600"
                 autoFocus
               />
+              <label className="flex items-center gap-2 mt-3 text-sm text-gray-400">
+                <input type="checkbox" checked={bulkTemplate} onChange={e => setBulkTemplate(e.target.checked)} />
+                Template ({'{biến}'}, {'{a|b}'}, __wildcard__)
+              </label>
+              {bulkTemplate && (
+                <textarea
+                  value={bulkCsv}
+                  onChange={e => setBulkCsv(e.target.value)}
+                  placeholder={'CSV (tùy chọn)\ncharacter,location\nknight,castle'}
+                  rows={4}
+                  className="w-full mt-2 px-4 py-3 bg-[#1a1a2a] border border-gray-700 rounded-lg text-sm resize-none focus:outline-none focus:border-cyan-500 placeholder-gray-600 font-mono"
+                />
+              )}
               <div className="flex items-center justify-between mt-4">
                 <span className="text-sm text-gray-500">
-                  {bulkText.split('\n').filter(l => l.trim()).length} prompts
+                  {bulkTemplate ? 'Template' : `${bulkText.split('\n').filter(l => l.trim()).length} prompts`}
                 </span>
                 <div className="flex gap-3">
                   <button
@@ -556,7 +717,7 @@ export default function App() {
                     disabled={bulkText.split('\n').filter(l => l.trim()).length === 0}
                     className="px-4 py-2 bg-cyan-600 hover:bg-cyan-700 disabled:opacity-50 disabled:cursor-not-allowed rounded-lg text-sm font-medium transition-colors"
                   >
-                    Add {bulkText.split('\n').filter(l => l.trim()).length} tasks
+                    {bulkTemplate ? 'Expand & add' : `Add ${bulkText.split('\n').filter(l => l.trim()).length} tasks`}
                   </button>
                 </div>
               </div>
@@ -1,5 +1,5 @@
 import { useState } from 'react';
-import { Task } from './AutoWhiskTab';
+import { Ratio, Task } from './AutoWhiskTab';
 import { TaskRow } from './TaskRow';
 
 interface Props {
@@ -20,7 +20,7 @@ interface Props {
   onStop: () => void;
   isRunning: boolean;
   onPreviewImage: (url: string, taskOrder: number) => void;
-  onGlobalSettingsChange?: (ratio: '16:9' | '9:16' | '1:1', count: number) => void;
+  onGlobalSettingsChange?: (ratio: Ratio, count: number) => void;
   onDownloadSelected?: () => void;
   onShowRefModal?: () => void;
   refImageCount?: number;
@@ -50,7 +50,7 @@ export function TaskTable({
   refImageCount = 0,
 }: Props) {
   const [globalCount, setGlobalCount] = useState<number>(2);
-  const [globalRatio, setGlobalRatio] = useState<'16:9' | '9:16' | '1:1'>('9:16');
+  const [globalRatio, setGlobalRatio] = useState<Ratio>('9:16');
   const [filterStatus, setFilterStatus] = useState<string>('all');
 
   const allSelected = tasks.length > 0 && tasks.every(t => t.selected);
@@ -66,7 +66,7 @@ export function TaskTable({
     onGlobalSettingsChange?.(globalRatio, count);
   };
 
-  const handleGlobalRatioChange = (ratio: '16:9' | '9:16' | '1:1') => {
+  const handleGlobalRatioChange = (ratio: Ratio) => {
     setGlobalRatio(ratio);
     tasks.filter(t => !['queued', 'generating'].includes(t.status)).forEach(t => {
       onUpdateTask(t.id, { ratio });
@@ -104,10 +104,12 @@ export function TaskTable({
 
           <div className="flex items-center gap-2 pl-2 border-l border-gray-700">
             <span className="text-xs text-gray-500">Ratio:</span>
-            <select value={globalRatio} onChange={e => handleGlobalRatioChange(e.target.value as '16:9' | '9:16' | '1:1')} className="px-2 py-1 bg-[#1a1a2a] border border-gray-700 rounded text-xs cursor-pointer focus:outline-none focus:border-cyan-500">
+            <select value={globalRatio} onChange={e => handleGlobalRatioChange(e.target.value as Ratio)} className="px-2 py-1 bg-[#1a1a2a] border border-gray-700 rounded text-xs cursor-pointer focus:outline-none focus:border-cyan-500">
               <option value="9:16">9:16</option>
               <option value="16:9">16:9</option>
               <option value="1:1">1:1</option>
+              <option value="4:3">4:3</option>
+              <option value="3:4">3:4</option>
             </select>
           </div>
 
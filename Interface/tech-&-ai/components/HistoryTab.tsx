@@ -1,6 +1,6 @@
 import React from 'react';
 import { History as HistoryIcon, Search, Trash2, RefreshCw, Check, FileText, ChevronRight, Clock, Tag, Hash } from 'lucide-react';
-import { useHistory, HistoryEntry } from './useHistory';
+import { useHistory, HistoryEntry, formatHistoryTime } from './useHistory';
 
 const HistoryTab: React.FC = () => {
   const {
@@ -16,7 +16,7 @@ const HistoryTab: React.FC = () => {
       <div className="flex items-center gap-3 flex-wrap">
         <div className="flex items-center gap-1.5 bg-zinc-800 px-3 py-1.5 rounded-lg border border-zinc-700/50">
           <Clock className="w-3 h-3 text-zinc-500" />
-          <span className="text-[11px] text-zinc-400">{entry.time}</span>
+          <span className="text-[11px] text-zinc-400">{formatHistoryTime(entry)}</span>
         </div>
         <div className="flex items-center gap-1.5 bg-zinc-800 px-3 py-1.5 rounded-lg border border-zinc-700/50">
           <Tag className="w-3 h-3 text-amber-500" />
@@ -24,7 +24,7 @@ const HistoryTab: React.FC = () => {
         </div>
         <div className="flex items-center gap-1.5 bg-zinc-800 px-3 py-1.5 rounded-lg border border-zinc-700/50">
           <Hash className="w-3 h-3 text-emerald-500" />
-          <span className="text-[11px] text-emerald-400">{entry.output_count} kết quả</span>
+          <span className="text-[11px] text-emerald-400">{entry.outputCount} kết quả</span>
         </div>
       </div>
 
@@ -45,7 +45,7 @@ const HistoryTab: React.FC = () => {
 
       <div className="flex-1 min-h-0">
         <div className="flex items-center gap-2 mb-2">
-          <span className="text-[10px] font-bold text-zinc-600 uppercase tracking-wider">Output ({entry.output_count})</span>
+          <span className="text-[10px] font-bold text-zinc-600 uppercase tracking-wider">Output ({entry.outputCount})</span>
           <button
             onClick={() => handleCopy(entry.output.join('\n\n'), 'detail-output')}
             className="text-[10px] text-zinc-600 hover:text-zinc-300 transition-colors"
@@ -108,11 +108,11 @@ const HistoryTab: React.FC = () => {
             {entries.length === 0 ? (
               <div className="h-full flex items-center justify-center text-zinc-700 text-sm">Chưa có lịch sử</div>
             ) : (
-              entries.map((entry, idx) => (
+              entries.map(entry => (
                 <div
-                  key={idx}
+                  key={entry.id}
                   onClick={() => setSelectedEntry(entry)}
-                  className={`flex items-center gap-3 p-3 rounded-xl border cursor-pointer transition-all ${selectedEntry === entry
+                  className={`flex items-center gap-3 p-3 rounded-xl border cursor-pointer transition-all ${selectedEntry?.id === entry.id
                     ? 'bg-amber-600/10 border-amber-500/40'
                     : 'bg-zinc-900/60 border-zinc-800/60 hover:border-zinc-700'
                     }`}
@@ -120,13 +120,13 @@ const HistoryTab: React.FC = () => {
                   <div className="flex-1 min-w-0">
                     <div className="flex items-center gap-2 mb-1">
                       <span className="text-[10px] text-amber-400 font-bold bg-amber-600/15 px-1.5 py-0.5 rounded">{entry.tab}</span>
-                      <span className="text-[10px] text-zinc-600">{entry.time}</span>
+                      <span className="text-[10px] text-zinc-600">{formatHistoryTime(entry)}</span>
                     </div>
                     <p className="text-[11px] text-zinc-500 truncate">{entry.input}</p>
-                    <span className="text-[10px] text-zinc-600 mt-0.5">{entry.output_count} kết quả</span>
+                    <span className="text-[10px] text-zinc-600 mt-0.5">{entry.outputCount} kết quả</span>
                   </div>
                   <button
-                    onClick={e => { e.stopPropagation(); deleteEntry(idx); }}
+                    onClick={e => { e.stopPropagation(); deleteEntry(entry.id); }}
                     className="flex-shrink-0 p-1 rounded hover:bg-red-600/20 text-zinc-700 hover:text-red-400 transition-colors"
                   >
                     <Trash2 className="w-3 h-3" />
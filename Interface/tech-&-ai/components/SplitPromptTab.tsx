@@ -9,6 +9,7 @@ const SplitPromptTab: React.FC = () => {
       searchText, setSearchText,
       result,
       isProcessing,
+      error,
       copyFeedback,
       textareaRef,
       handleProcess,
@@ -185,6 +186,7 @@ const SplitPromptTab: React.FC = () => {
                      Format: {result.format}
                   </span>
                )}
+               {error && <span className="text-red-400 font-medium">{error}</span>}
             </div>
          </div>
       </div>
@@ -0,0 +1,28 @@
+import { test } from 'node:test';
+import assert from 'node:assert/strict';
+import { readFileSync } from 'node:fs';
+import { processChain } from './chainLogic.ts';
+
+// Shared with chain.rs in src-tauri. `tsSingles` lists the cases where this splitter
+// differs from the Rust port (JavaScript reads `\Z` as a literal Z).
+interface ChainCase {
+    input: string;
+    prefix: string;
+    suffix: string;
+    singles: string[];
+    tsSingles?: string[];
+    note?: string;
+}
+
+const corpus: ChainCase[] = JSON.parse(
+    readFileSync(new URL('../../../fixtures/chain_corpus.json', import.meta.url), 'utf8'),
+);
+
+test('matches the shared chain corpus', () => {
+    for (const c of corpus) {
+        const expected = c.tsSingles ?? c.singles;
+        const { singles, chains } = processChain(c.input, c.prefix, c.suffix);
+        assert.deepEqual(singles, expected, JSON.stringify(c.input));
+        assert.deepEqual(chains, expected.slice(1).map((s, i) => `${expected[i]} ${s}`), JSON.stringify(c.input));
+    }
+});
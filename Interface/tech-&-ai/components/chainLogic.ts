@@ -274,6 +274,7 @@ function parseSingleLine(raw: string): string[] {
 }
 
 export function parseInput(raw: string): string[] {
+    if (!raw.trim()) return [];
     if (raw.toUpperCase().includes('FLOW VIDEO PROMPT:')) {
         return parseFlowVideoFormat(raw).images;
     }
@@ -1,63 +1,85 @@
-import { useState, useCallback } from 'react';
+import { useState, useCallback, useEffect } from 'react';
+import { invoke } from '@tauri-apps/api/core';
 
 export interface HistoryEntry {
-    time: string;
+    id: number;
+    createdAt: number;
     tab: string;
     input: string;
     output: string[];
-    output_count: number;
+    outputCount: number;
+    pinned: boolean;
 }
 
-const STORAGE_KEY = 'lozy_history';
-
-function loadHistory(): HistoryEntry[] {
-    try {
-        const raw = localStorage.getItem(STORAGE_KEY);
-        return raw ? JSON.parse(raw) : [];
-    } catch { return []; }
+interface HistoryPage {
+    total: number;
+    entries: HistoryEntry[];
 }
 
-function saveHistory(entries: HistoryEntry[]) {
-    localStorage.setItem(STORAGE_KEY, JSON.stringify(entries));
+const LEGACY_KEY = 'lozy_history';
+const PAGE_SIZE = 200;
+
+export function formatHistoryTime(entry: HistoryEntry): string {
+    return new Date(entry.createdAt * 1000).toLocaleString('vi-VN');
 }
 
 export function addHistory(tab: string, input: string, output: string[]) {
-    const entries = loadHistory();
-    const entry: HistoryEntry = {
-        time: new Date().toLocaleString('vi-VN'),
-        tab,
-        input: input.length > 500 ? input.substring(0, 500) + '...' : input,
-        output: output.slice(0, 50),
-        output_count: output.length,
-    };
-    entries.unshift(entry);
-    if (entries.length > 200) entries.length = 200;
-    saveHistory(entries);
+    invoke('add_history', { tab, input, output })
+        .catch(e => console.error('Failed to save history:', e));
+}
+
+// Moves the old localStorage history into the database once, then drops the key. Started
+// at app startup; history reads await the same promise so they never see a partial import.
+let legacyImport: Promise<void> | null = null;
+
+export function importLegacyHistory(): Promise<void> {
+    legacyImport ??= (async () => {
+        const raw = localStorage.getItem(LEGACY_KEY);
+        if (!raw) return;
+        try {
+            const entries = JSON.parse(raw);
+            if (Array.isArray(entries) && entries.length > 0) {
+                await invoke<number>('import_legacy_history', { entries });
+            }
+            localStorage.removeItem(LEGACY_KEY);
+        } catch (e) {
+            console.error('Failed to import legacy history:', e);
+        }
+    })();
+    return legacyImport;
 }
 
 export function useHistory() {
-    const [entries, setEntries] = useState<HistoryEntry[]>(loadHistory);
+    const [entries, setEntries] = useState<HistoryEntry[]>([]);
     const [selectedEntry, setSelectedEntry] = useState<HistoryEntry | null>(null);
     const [searchText, setSearchText] = useState('');
     const [copyFeedback, setCopyFeedback] = useState<string | null>(null);
 
-    const refresh = useCallback(() => {
-        setEntries(loadHistory());
-    }, []);
+    const refresh = useCallback(async () => {
+        try {
+            await importLegacyHistory();
+            const page = await invoke<HistoryPage>('search_history', {
+                query: { text: searchText.trim() || undefined, limit: PAGE_SIZE },
+            });
+            setEntries(page.entries);
+        } catch (e) {
+            console.error('Failed to load history:', e);
+        }
+    }, [searchText]);
 
-    const clearAll = useCallback(() => {
-        saveHistory([]);
-        setEntries([]);
-        setSelectedEntry(null);
-    }, []);
+    useEffect(() => { refresh(); }, [refresh]);
 
-    const deleteEntry = useCallback((idx: number) => {
-        const updated = [...loadHistory()];
-        updated.splice(idx, 1);
-        saveHistory(updated);
-        setEntries(updated);
+    const clearAll = useCallback(async () => {
+        await invoke('clear_history', { keepPinned: false });
         setSelectedEntry(null);
-    }, []);
+        refresh();
+    }, [refresh]);
+
+    const deleteEntry = useCallback(async (id: number) => {
+        await invoke('delete_history', { id });
+        setSelectedEntry(prev => (prev?.id === id ? null : prev));
+        refresh();
+    }, [refresh]);
 
     const handleCopy = useCallback((text: string, id: string) => {
         navigator.clipboard.writeText(text);
@@ -65,16 +87,8 @@ export function useHistory() {
         setTimeout(() => setCopyFeedback(null), 2000);
     }, []);
 
-    const filtered = searchText.trim()
-        ? entries.filter(e =>
-            e.tab.toLowerCase().includes(searchText.toLowerCase()) ||
-            e.input.toLowerCase().includes(searchText.toLowerCase()) ||
-            e.output.some(o => o.toLowerCase().includes(searchText.toLowerCase()))
-        )
-        : entries;
-
     return {
-        entries: filtered,
+        entries,
         selectedEntry, setSelectedEntry,
         searchText, setSearchText,
         copyFeedback,
@@ -1,5 +1,5 @@
 import { useState, useRef, useCallback } from 'react';
-import { processSplit } from './promptSplitterLogic';
+import { invoke } from '@tauri-apps/api/core';
 import { addHistory } from './useHistory';
 
 export interface SplitResult {
@@ -14,16 +14,27 @@ export function useSplitPrompt() {
     const [searchText, setSearchText] = useState('');
     const [result, setResult] = useState<SplitResult>({ images: [], videos: [], format: '' });
     const [isProcessing, setIsProcessing] = useState(false);
+    const [error, setError] = useState('');
     const [copyFeedback, setCopyFeedback] = useState<string | null>(null);
     const textareaRef = useRef<HTMLTextAreaElement>(null);
 
-    const handleProcess = useCallback(() => {
+    // Same backend splitter as the AutoWhisk bulk add, so both tabs agree on the formats.
+    const handleProcess = useCallback(async () => {
         if (!input.trim()) return;
         setIsProcessing(true);
-        const { images, videos, format } = processSplit(input, filterText);
-        setResult({ images, videos, format });
-        addHistory('Tách Prompt AI', input, [...images, ...videos]);
-        setIsProcessing(false);
+        setError('');
+        try {
+            const { images, videos, format } = await invoke<SplitResult>('split_prompts', {
+                raw: input,
+                options: { filter: filterText },
+            });
+            setResult({ images, videos, format });
+            addHistory('Tách Prompt AI', input, [...images, ...videos]);
+        } catch (e) {
+            setError(String(e));
+        } finally {
+            setIsProcessing(false);
+        }
     }, [input, filterText]);
 
     const handleCopy = useCallback((text: string, id: string) => {
@@ -58,6 +69,7 @@ export function useSplitPrompt() {
         searchText, setSearchText,
         result,
         isProcessing,
+        error,
         copyFeedback,
         textareaRef,
         handleProcess,
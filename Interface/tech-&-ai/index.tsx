@@ -2,12 +2,15 @@ import React from 'react';
 import ReactDOM from 'react-dom/client';
 import './index.css';
 import App from './App';
+import { importLegacyHistory } from './components/useHistory';
 
 const rootElement = document.getElementById('root');
 if (!rootElement) {
   throw new Error("Could not find root element to mount to");
 }
 
+importLegacyHistory();
+
 const root = ReactDOM.createRoot(rootElement);
 root.render(
   <React.StrictMode>
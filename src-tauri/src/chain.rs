@@ -0,0 +1,464 @@
+use crate::prompt_split::{
+    collapse_whitespace, is_flow_video_format, is_image_inline_format, is_image_ratio_format,
+    is_motion_prompt_format, is_prompt_format, is_script_prompt_line, is_title_content_format,
+    is_title_dash_format, parse_flow_video_format, parse_num, parse_paragraph_format,
+    parse_prompt_format, parse_title_content_format, sections, DOODLE_PROMPT_KEYWORDS,
+};
+use crate::util::re;
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainOptions {
+    #[serde(default)]
+    pub prefix: Option<String>,
+    #[serde(default)]
+    pub suffix: Option<String>,
+    #[serde(default)]
+    pub singles_from: Option<String>,
+    #[serde(default)]
+    pub singles_to: Option<String>,
+    #[serde(default)]
+    pub chains_from: Option<String>,
+    #[serde(default)]
+    pub chains_to: Option<String>,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ChainResult {
+    pub singles: Vec<String>,
+    pub chains: Vec<String>,
+    pub single_count: usize,
+    pub pair_count: usize,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selected_singles: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub selected_chains: Option<Vec<String>>,
+}
+
+// The keep-header parsers below differ from the splitter's: the chain output must still
+// say which image or motion prompt each entry came from.
+pub fn parse_image_ratio_format_keep_header(raw: &str) -> Vec<String> {
+    let header = re!(r"(?is)(Image\s+(\d+)[^\n]*ratio[^\n]*)\n");
+    let terminator = re!(r"(?i)Image\s+\d+[^\n]*ratio");
+    let mut items: Vec<(u32, String)> = sections(raw, header, terminator, None, 0)
+        .into_iter()
+        .map(|s| {
+            let title = s.caps.get(1).map(|m| m.as_str().trim()).unwrap_or("");
+            (
+                parse_num(&s.caps, 2),
+                format!("{}\n{}", title, collapse_whitespace(s.body)),
+            )
+        })
+        .collect();
+    items.sort_by_key(|(n, _)| *n);
+    items
+        .into_iter()
+        .map(|(_, text)| text)
+        .filter(|text| !text.trim().is_empty())
+        .collect()
+}
+
+pub fn parse_title_dash_format_keep_header(raw: &str) -> Vec<String> {
+    let title_re = re!(r"(?m)(?:^|\n)([A-Z][A-Za-z\s]+?)\s*[—–-]\s*");
+    let matches: Vec<_> = title_re.captures_iter(raw).collect();
+    let mut results = Vec::new();
+    for (i, caps) in matches.iter().enumerate() {
+        let (Some(whole), Some(title)) = (caps.get(0), caps.get(1)) else {
+            continue;
+        };
+        let end = matches
+            .get(i + 1)
+            .and_then(|next| next.get(0))
+            .map(|m| m.start())
+            .unwrap_or(raw.len());
+        let content = collapse_whitespace(&raw[whole.end()..end]);
+        if !content.is_empty() {
+            results.push(format!("{}\n{}", title.as_str().trim(), content));
+        }
+    }
+    results
+}
+
+pub fn parse_motion_prompt_format_keep_header(raw: &str) -> Vec<String> {
+    let header = re!(r"(?is)(Motion\s+Prompt\s*(\d+)\s*(?:\([^)]*\))?)\s*\n?");
+    let terminator = re!(r"(?i)Motion\s+Prompt\s*\d+");
+    let mut items: Vec<(u32, String, String)> = sections(raw, header, terminator, None, 0)
+        .into_iter()
+        .map(|s| {
+            (
+                parse_num(&s.caps, 2),
+                collapse_whitespace(s.caps.get(1).map(|m| m.as_str()).unwrap_or("")),
+                collapse_whitespace(s.body),
+            )
+        })
+        .collect();
+    items.sort_by_key(|(n, _, _)| *n);
+    items
+        .into_iter()
+        .filter(|(_, _, content)| !content.is_empty())
+        .map(|(_, title, content)| format!("{}\n{}", title, content))
+        .collect()
+}
+
+pub fn parse_image_inline_format_keep_header(raw: &str) -> Vec<String> {
+    let header = re!(r"(?is)Image\s*(\d+)\s*\n?([A-Z])");
+    let terminator = re!(r"(?i)Image\s*\d+\s*\n?[A-Z]");
+    let mut items: Vec<(u32, String)> = sections(raw, header, terminator, Some(2), 1)
+        .into_iter()
+        .map(|s| (parse_num(&s.caps, 1), collapse_whitespace(s.body)))
+        .collect();
+    items.sort_by_key(|(n, _)| *n);
+    items
+        .into_iter()
+        .filter(|(_, content)| !content.is_empty())
+        .map(|(n, content)| format!("Image {}\n{}", n, content))
+        .collect()
+}
+
+// Looser than the splitter's is_script_placeholder_format, which needs the placeholder to
+// follow an "Image N:" header, but input with a SCENE ADD-ON is left to the FRAME format.
+pub fn is_image_script_placeholder_format(raw: &str) -> bool {
+    re!(r"(?i)Image\s+\d+:").is_match(raw)
+        && re!(r"(?i)SCRIPT PLACEHOLDER:").is_match(raw)
+        && !re!(r"(?i)SCENE ADD-ON \(English\):").is_match(raw)
+}
+
+// Skips the narration after SCRIPT PLACEHOLDER and keeps everything from the first line
+// that reads like an image description. Unlike the splitter, only the doodle keywords
+// count, and an image with no such line is dropped instead of falling back to its last
+// paragraph.
+pub fn parse_image_script_placeholder_format(raw: &str) -> Vec<String> {
+    let placeholder = re!(r"(?i)SCRIPT PLACEHOLDER:\s*");
+    let mut results = Vec::new();
+    for part in re!(r"(?i)Image\s+\d+:\s*").split(raw) {
+        if part.trim().is_empty() {
+            continue;
+        }
+        let Some(m) = placeholder.find(part) else {
+            continue;
+        };
+        let mut prompt_lines: Vec<&str> = Vec::new();
+        let mut script_ended = false;
+        for line in part[m.end()..].trim().split('\n').map(str::trim) {
+            if line.is_empty() {
+                continue;
+            }
+            if script_ended || is_script_prompt_line(line, DOODLE_PROMPT_KEYWORDS) {
+                script_ended = true;
+                prompt_lines.push(line);
+            }
+        }
+        if !prompt_lines.is_empty() {
+            results.push(collapse_whitespace(&prompt_lines.join(" ")));
+        }
+    }
+    results
+}
+
+pub fn is_frame_script_scene_format(raw: &str) -> bool {
+    re!(r"(?i)FRAME\s+\d+").is_match(raw)
+        && re!(r"(?i)SCRIPT PLACEHOLDER:").is_match(raw)
+        && re!(r"(?i)SCENE ADD-ON \(English\):").is_match(raw)
+}
+
+// Whole FRAME blocks, headers included, with blank lines dropped. The splitter's
+// scene_addon format extracts only the SCENE ADD-ON text instead.
+pub fn parse_frame_script_scene_format(raw: &str) -> Vec<String> {
+    let starts: Vec<usize> = re!(r"(?i)FRAME\s+\d+")
+        .find_iter(raw)
+        .map(|m| m.start())
+        .collect();
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, &start)| {
+            let end = starts.get(i + 1).copied().unwrap_or(raw.len());
+            raw[start..end]
+                .trim()
+                .split('\n')
+                .map(str::trim)
+                .filter(|l| !l.is_empty())
+                .collect::<Vec<_>>()
+                .join("\n")
+        })
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+pub fn is_long_sentence_format(raw: &str) -> bool {
+    !raw.trim().contains('\n') && re!(r"\.[A-Z]").is_match(raw)
+}
+
+// Splits after every period that is directly followed by a capital letter.
+pub fn parse_long_sentence_format(raw: &str) -> Vec<String> {
+    let mut results = Vec::new();
+    let mut start = 0;
+    for m in re!(r"\.[A-Z]").find_iter(raw) {
+        results.push(raw[start..m.start() + 1].trim());
+        start = m.start() + 1;
+    }
+    results.push(raw[start..].trim());
+    results
+        .into_iter()
+        .filter(|s| !s.is_empty())
+        .map(str::to_string)
+        .collect()
+}
+
+pub fn is_period_newline_format(raw: &str) -> bool {
+    raw.contains('\n') && re!(r"\.\s*\n\s*\n").is_match(raw)
+}
+
+pub fn parse_period_newline_format(raw: &str) -> Vec<String> {
+    re!(r"\n\s*\n+")
+        .split(raw)
+        .map(collapse_whitespace)
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+// One long line: split after "no text." style closers first, then on ".Capital".
+fn parse_single_line(raw: &str) -> Vec<String> {
+    let closer = re!(r"(?i)(?:no\s+watermark|no\s+text|no\s+dialogue|no\s+speech)\.");
+    // Equivalent of splitting on `(CLOSER)\s+(?=[A-Z])` with the closer kept as its own part.
+    let mut parts: Vec<&str> = Vec::new();
+    let mut last = 0;
+    for m in re!(r"(?i)(?:no\s+watermark|no\s+text|no\s+dialogue|no\s+speech)\.\s+").find_iter(raw)
+    {
+        if !raw[m.end()..].starts_with(|c: char| c.is_ascii_alphabetic()) {
+            continue;
+        }
+        let closer_end = m.start() + m.as_str().trim_end().len();
+        parts.push(&raw[last..m.start()]);
+        parts.push(&raw[m.start()..closer_end]);
+        last = m.end();
+    }
+    parts.push(&raw[last..]);
+
+    if parts.len() > 1 {
+        let mut results = Vec::new();
+        let mut current = String::new();
+        for part in parts {
+            current.push_str(part);
+            if closer.is_match(part) {
+                if !current.trim().is_empty() {
+                    results.push(current.trim().to_string());
+                }
+                current.clear();
+            }
+        }
+        if !current.trim().is_empty() {
+            results.push(current.trim().to_string());
+        }
+        results
+    } else if re!(r"\.[A-Z]").is_match(raw) {
+        parse_long_sentence_format(raw)
+    } else {
+        vec![raw.trim().to_string()]
+    }
+}
+
+pub fn parse_input(raw: &str) -> Vec<String> {
+    if raw.trim().is_empty() {
+        return Vec::new();
+    }
+    if is_flow_video_format(raw) {
+        parse_flow_video_format(raw).0
+    } else if is_frame_script_scene_format(raw) {
+        parse_frame_script_scene_format(raw)
+    } else if is_image_script_placeholder_format(raw) {
+        parse_image_script_placeholder_format(raw)
+    } else if is_period_newline_format(raw) {
+        parse_period_newline_format(raw)
+    } else if is_long_sentence_format(raw) {
+        parse_long_sentence_format(raw)
+    } else if is_image_ratio_format(raw) {
+        parse_image_ratio_format_keep_header(raw)
+    } else if is_title_dash_format(raw) {
+        parse_title_dash_format_keep_header(raw)
+    } else if is_motion_prompt_format(raw) {
+        parse_motion_prompt_format_keep_header(raw)
+    } else if is_image_inline_format(raw) {
+        parse_image_inline_format_keep_header(raw)
+    } else if is_prompt_format(raw) {
+        parse_prompt_format(raw)
+    } else if re!(r"\n\s*\n").is_match(raw) {
+        parse_paragraph_format(raw)
+    } else if is_title_content_format(raw) {
+        parse_title_content_format(raw)
+    } else if raw.contains('\n') {
+        raw.trim()
+            .split('\n')
+            .map(str::trim)
+            .filter(|l| !l.is_empty())
+            .map(str::to_string)
+            .collect()
+    } else {
+        parse_single_line(raw)
+    }
+}
+
+// process_chain trims both, so a single space always separates them from the prompt.
+pub fn add_prefix(prompts: Vec<String>, prefix: &str) -> Vec<String> {
+    if prefix.is_empty() {
+        return prompts;
+    }
+    prompts
+        .into_iter()
+        .map(|p| format!("{} {}", prefix, p))
+        .collect()
+}
+
+pub fn add_suffix(prompts: Vec<String>, suffix: &str) -> Vec<String> {
+    if suffix.is_empty() {
+        return prompts;
+    }
+    prompts
+        .into_iter()
+        .map(|p| format!("{} {}", p, suffix))
+        .collect()
+}
+
+pub fn create_chain_pairs(prompts: &[String]) -> Vec<String> {
+    prompts
+        .windows(2)
+        .map(|pair| format!("{} {}", pair[0], pair[1]))
+        .collect()
+}
+
+// Leading integer of a text box, like parseInt; 0 and garbage count as "not set".
+fn range_bound(text: &str) -> Option<i64> {
+    let text = text.trim_start();
+    let (sign, digits) = match text.strip_prefix('-') {
+        Some(rest) => (-1, rest),
+        None => (1, text.strip_prefix('+').unwrap_or(text)),
+    };
+    let end = digits
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(digits.len());
+    digits[..end]
+        .parse::<i64>()
+        .ok()
+        .map(|n| sign * n)
+        .filter(|&n| n != 0)
+}
+
+// Same from/to semantics as the Merge Overlap tab: 1-based and inclusive, empty means the
+// whole list, and a negative "to" counts back from the end.
+pub fn items_in_range(list: &[String], from: &str, to: &str) -> Vec<String> {
+    let len = list.len() as i64;
+    let start = (range_bound(from).unwrap_or(1) - 1).max(0);
+    let mut end = range_bound(to).unwrap_or(len).min(len);
+    if end < 0 {
+        end = (len + end).max(0);
+    }
+    if start >= end {
+        return Vec::new();
+    }
+    list[start as usize..end as usize].to_vec()
+}
+
+fn selection(list: &[String], from: &Option<String>, to: &Option<String>) -> Option<Vec<String>> {
+    if from.is_none() && to.is_none() {
+        return None;
+    }
+    Some(items_in_range(
+        list,
+        from.as_deref().unwrap_or(""),
+        to.as_deref().unwrap_or(""),
+    ))
+}
+
+pub fn process_chain(raw: &str, options: &ChainOptions) -> ChainResult {
+    let prompts = parse_input(raw);
+    let singles = add_prefix(prompts, options.prefix.as_deref().unwrap_or("").trim());
+    let singles = add_suffix(singles, options.suffix.as_deref().unwrap_or("").trim());
+    let chains = create_chain_pairs(&singles);
+    ChainResult {
+        single_count: singles.len(),
+        pair_count: chains.len(),
+        selected_singles: selection(&singles, &options.singles_from, &options.singles_to),
+        selected_chains: selection(&chains, &options.chains_from, &options.chains_to),
+        singles,
+        chains,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Shared with components/chainLogic.test.ts. `tsSingles` records where chainLogic.ts
+    // differs from this port.
+    #[derive(serde::Deserialize)]
+    struct Case {
+        input: String,
+        prefix: String,
+        suffix: String,
+        singles: Vec<String>,
+    }
+
+    fn corpus() -> Vec<Case> {
+        serde_json::from_str(include_str!("../../fixtures/chain_corpus.json")).unwrap()
+    }
+
+    fn run(raw: &str, prefix: &str, suffix: &str) -> ChainResult {
+        let options = ChainOptions {
+            prefix: Some(prefix.to_string()),
+            suffix: Some(suffix.to_string()),
+            ..Default::default()
+        };
+        process_chain(raw, &options)
+    }
+
+    #[test]
+    fn matches_chain_logic_corpus() {
+        for case in corpus() {
+            let expected = &case.singles;
+            let result = run(&case.input, &case.prefix, &case.suffix);
+            assert_eq!(result.singles, *expected, "input: {:?}", case.input);
+            let pairs: Vec<String> = expected
+                .windows(2)
+                .map(|p| format!("{} {}", p[0], p[1]))
+                .collect();
+            assert_eq!(result.chains, pairs, "input: {:?}", case.input);
+            assert_eq!(result.pair_count, expected.len().saturating_sub(1));
+        }
+    }
+
+    #[test]
+    fn parse_input_keeps_headers_in_number_order() {
+        assert_eq!(
+            parse_input("Motion Prompt 2 (5s)\npan left\nMotion Prompt 1\nzoom in"),
+            vec!["Motion Prompt 1\nzoom in", "Motion Prompt 2 (5s)\npan left"]
+        );
+    }
+
+    #[test]
+    fn prefix_and_suffix_are_trimmed_and_spaced_once() {
+        let result = run("a\nb", "  pre  ", " post ");
+        assert_eq!(result.singles, vec!["pre a post", "pre b post"]);
+        assert_eq!(add_prefix(vec!["x".into()], ""), vec!["x"]);
+        assert_eq!(add_suffix(vec!["x".into()], ""), vec!["x"]);
+    }
+
+    #[test]
+    fn selections_follow_the_range_boxes() {
+        let list: Vec<String> = (1..=5).map(|i| i.to_string()).collect();
+        assert_eq!(items_in_range(&list, "", ""), list);
+        assert_eq!(items_in_range(&list, "2", "3"), vec!["2", "3"]);
+        assert_eq!(items_in_range(&list, "0", "-2"), vec!["1", "2", "3"]);
+        assert_eq!(items_in_range(&list, "4", "2"), Vec::<String>::new());
+        assert_eq!(items_in_range(&list, "abc", "9"), list);
+
+        let options = ChainOptions {
+            chains_from: Some("2".to_string()),
+            ..Default::default()
+        };
+        let result = process_chain("a\nb\nc\nd", &options);
+        assert!(result.selected_singles.is_none());
+        assert_eq!(result.selected_chains.unwrap(), vec!["b c", "c d"]);
+    }
+}
@@ -0,0 +1,344 @@
+use crate::filename;
+use crate::library;
+use crate::metadata;
+use ab_glyph::{point, Font, FontArc, PxScale, ScaleFont};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::{Rgb, RgbImage};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+const DEFAULT_COLUMNS: u32 = 6;
+const DEFAULT_THUMB_SIZE: u32 = 256;
+const MAX_SHEET_HEIGHT: u32 = 12_000;
+const PADDING: u32 = 8;
+const FONT_PX: f32 = 14.0;
+const BACKGROUND: Rgb<u8> = Rgb([24, 24, 27]);
+const TEXT_COLOR: [f32; 3] = [230.0, 230.0, 230.0];
+
+const FONT_CANDIDATES: [&str; 6] = [
+    "C:\\Windows\\Fonts\\segoeui.ttf",
+    "C:\\Windows\\Fonts\\arial.ttf",
+    "/System/Library/Fonts/Supplemental/Arial.ttf",
+    "/Library/Fonts/Arial.ttf",
+    "/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf",
+    "/usr/share/fonts/TTF/DejaVuSans.ttf",
+];
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum LabelWith {
+    None,
+    Index,
+    Prompt,
+    #[default]
+    Both,
+    Filename,
+}
+
+struct SheetItem {
+    path: String,
+    index: u32,
+    prompt: String,
+}
+
+fn load_font() -> Option<FontArc> {
+    FONT_CANDIDATES
+        .iter()
+        .filter_map(|p| std::fs::read(p).ok())
+        .find_map(|bytes| FontArc::try_from_vec(bytes).ok())
+}
+
+fn text_width(font: &FontArc, text: &str) -> f32 {
+    let scaled = font.as_scaled(PxScale::from(FONT_PX));
+    text.chars()
+        .map(|c| scaled.h_advance(scaled.glyph_id(c)))
+        .sum()
+}
+
+fn fit_text(font: &FontArc, text: &str, max_width: f32) -> String {
+    let single_line: String = text.split_whitespace().collect::<Vec<_>>().join(" ");
+    if text_width(font, &single_line) <= max_width {
+        return single_line;
+    }
+    let mut out = String::new();
+    for c in single_line.chars() {
+        out.push(c);
+        if text_width(font, &format!("{}…", out)) > max_width {
+            out.pop();
+            break;
+        }
+    }
+    format!("{}…", out.trim_end())
+}
+
+fn draw_text(canvas: &mut RgbImage, font: &FontArc, text: &str, x: f32, y: f32) {
+    let scaled = font.as_scaled(PxScale::from(FONT_PX));
+    let mut caret = x;
+    for c in text.chars() {
+        let id = scaled.glyph_id(c);
+        let glyph = id.with_scale_and_position(FONT_PX, point(caret, y + scaled.ascent()));
+        caret += scaled.h_advance(id);
+        let Some(outlined) = font.outline_glyph(glyph) else {
+            continue;
+        };
+        let bounds = outlined.px_bounds();
+        outlined.draw(|gx, gy, coverage| {
+            let px = bounds.min.x as i64 + gx as i64;
+            let py = bounds.min.y as i64 + gy as i64;
+            if px < 0 || py < 0 || px >= canvas.width() as i64 || py >= canvas.height() as i64 {
+                return;
+            }
+            let pixel = canvas.get_pixel_mut(px as u32, py as u32);
+            for (ch, target) in pixel.0.iter_mut().zip(TEXT_COLOR) {
+                *ch = (*ch as f32 * (1.0 - coverage) + target * coverage) as u8;
+            }
+        });
+    }
+}
+
+fn label_for(item: &SheetItem, mode: LabelWith) -> String {
+    let file_name = Path::new(&item.path)
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    match mode {
+        LabelWith::None => String::new(),
+        LabelWith::Index => format!("#{}", item.index),
+        LabelWith::Prompt => item.prompt.clone(),
+        LabelWith::Both => format!("#{} {}", item.index, item.prompt),
+        LabelWith::Filename => file_name,
+    }
+}
+
+fn collect_items(
+    batch_id: Option<&str>,
+    paths: Option<Vec<String>>,
+) -> Result<Vec<SheetItem>, String> {
+    if let Some(batch) = batch_id {
+        let images = library::batch_images(batch)?;
+        return Ok(images
+            .into_iter()
+            .enumerate()
+            .map(|(i, img)| SheetItem {
+                path: img.file_path,
+                index: img.task_index.unwrap_or(i as u32 + 1),
+                prompt: img.prompt,
+            })
+            .collect());
+    }
+
+    Ok(paths
+        .unwrap_or_default()
+        .into_iter()
+        .enumerate()
+        .map(|(i, path)| {
+            let prompt = metadata::read_image_metadata(&path)
+                .ok()
+                .and_then(|v| v["metadata"]["prompt"].as_str().map(|s| s.to_string()))
+                .unwrap_or_default();
+            SheetItem {
+                path,
+                index: i as u32 + 1,
+                prompt,
+            }
+        })
+        .collect())
+}
+
+fn save_sheet(sheet: &RgbImage, path: &Path, jpeg: bool) -> Result<(), String> {
+    if jpeg {
+        let mut buf = Vec::new();
+        JpegEncoder::new_with_quality(&mut buf, 88)
+            .encode_image(sheet)
+            .map_err(|e| e.to_string())?;
+        std::fs::write(path, buf).map_err(|e| e.to_string())
+    } else {
+        sheet
+            .save_with_format(path, image::ImageFormat::Png)
+            .map_err(|e| e.to_string())
+    }
+}
+
+pub fn build_contact_sheet(
+    batch_id: Option<&str>,
+    paths: Option<Vec<String>>,
+    columns: Option<u32>,
+    thumb_size: Option<u32>,
+    label_with: Option<LabelWith>,
+    output_folder: Option<&str>,
+    format: Option<&str>,
+) -> Result<Value, String> {
+    let items = collect_items(batch_id, paths)?;
+    if items.is_empty() {
+        return Err("No images for contact sheet".to_string());
+    }
+
+    let columns = columns.unwrap_or(DEFAULT_COLUMNS).clamp(1, 32);
+    let thumb = thumb_size.unwrap_or(DEFAULT_THUMB_SIZE).clamp(32, 1024);
+    let label_mode = label_with.unwrap_or_default();
+    let jpeg = matches!(format, Some("jpg") | Some("jpeg"));
+    let ext = if jpeg { "jpg" } else { "png" };
+
+    let font = if label_mode == LabelWith::None {
+        None
+    } else {
+        load_font()
+    };
+    let label_height = if font.is_some() {
+        FONT_PX as u32 + PADDING
+    } else {
+        0
+    };
+    let cell_w = thumb + PADDING;
+    let cell_h = thumb + label_height + PADDING;
+    let rows_per_sheet = ((MAX_SHEET_HEIGHT - PADDING) / cell_h).max(1);
+    let per_sheet = (rows_per_sheet * columns) as usize;
+
+    let out_dir = match output_folder {
+        Some(f) => PathBuf::from(f),
+        None => Path::new(&items[0].path)
+            .parent()
+            .map(|p| p.to_path_buf())
+            .unwrap_or_else(|| PathBuf::from(".")),
+    };
+    std::fs::create_dir_all(&out_dir).map_err(|e| e.to_string())?;
+    let base_name = match batch_id {
+        Some(b) => format!("contact_sheet_{}", filename::slugify(b, 40)),
+        None => "contact_sheet".to_string(),
+    };
+
+    let mut warnings = Vec::new();
+    if label_mode != LabelWith::None && font.is_none() {
+        warnings.push("No system font found, sheets were built without labels".to_string());
+    }
+
+    let mut sheets = Vec::new();
+    let mut skipped = Vec::new();
+    let sheet_count = items.len().div_ceil(per_sheet);
+
+    for (sheet_idx, chunk) in items.chunks(per_sheet).enumerate() {
+        let rows = (chunk.len() as u32).div_ceil(columns);
+        let cols = columns.min(chunk.len() as u32);
+        let mut canvas =
+            RgbImage::from_pixel(cols * cell_w + PADDING, rows * cell_h + PADDING, BACKGROUND);
+
+        for (i, item) in chunk.iter().enumerate() {
+            let x = PADDING + (i as u32 % columns) * cell_w;
+            let y = PADDING + (i as u32 / columns) * cell_h;
+
+            match image::open(&item.path) {
+                Ok(img) => {
+                    let small = img.resize(thumb, thumb, FilterType::Triangle).to_rgb8();
+                    let ox = x + (thumb - small.width()) / 2;
+                    let oy = y + (thumb - small.height()) / 2;
+                    image::imageops::replace(&mut canvas, &small, ox as i64, oy as i64);
+                }
+                Err(e) => {
+                    skipped.push(json!({ "path": item.path, "error": e.to_string() }));
+                    continue;
+                }
+            }
+
+            if let Some(font) = &font {
+                let label = fit_text(font, &label_for(item, label_mode), thumb as f32);
+                draw_text(&mut canvas, font, &label, x as f32, (y + thumb + 4) as f32);
+            }
+        }
+
+        let name = if sheet_count > 1 {
+            format!("{}_{:02}.{}", base_name, sheet_idx + 1, ext)
+        } else {
+            format!("{}.{}", base_name, ext)
+        };
+        let path = filename::reserve_unique_path(&out_dir, &name)?;
+        if let Err(e) = save_sheet(&canvas, &path, jpeg) {
+            let _ = std::fs::remove_file(&path);
+            return Err(e);
+        }
+        sheets.push(json!({
+            "path": path.to_string_lossy(),
+            "count": chunk.len(),
+            "width": canvas.width(),
+            "height": canvas.height()
+        }));
+    }
+
+    Ok(json!({
+        "sheets": sheets,
+        "imageCount": items.len(),
+        "labelsRendered": font.is_some(),
+        "warnings": warnings,
+        "skipped": skipped
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write_pngs(dir: &Path, count: usize) -> Vec<String> {
+        (0..count)
+            .map(|i| {
+                let path = dir.join(format!("img_{:03}.png", i));
+                RgbImage::from_pixel(16, 12, Rgb([i as u8, 80, 160]))
+                    .save(&path)
+                    .unwrap();
+                path.to_string_lossy().to_string()
+            })
+            .collect()
+    }
+
+    fn build(paths: Vec<String>, out: &Path, columns: u32) -> Value {
+        build_contact_sheet(
+            None,
+            Some(paths),
+            Some(columns),
+            Some(32),
+            Some(LabelWith::None),
+            Some(&out.to_string_lossy()),
+            None,
+        )
+        .unwrap()
+    }
+
+    #[test]
+    fn grid_and_pagination() {
+        let dir = std::env::temp_dir().join(format!("autowhisk_sheet_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let cell = 32 + PADDING;
+
+        let result = build(write_pngs(&dir, 5), &dir.join("small"), 2);
+        assert_eq!(result["imageCount"], 5);
+        assert_eq!(result["warnings"], json!([]));
+        let sheet = &result["sheets"][0];
+        assert_eq!(result["sheets"].as_array().unwrap().len(), 1);
+        assert_eq!(sheet["width"], 2 * cell + PADDING);
+        assert_eq!(sheet["height"], 3 * cell + PADDING);
+        let img = image::open(sheet["path"].as_str().unwrap()).unwrap();
+        assert_eq!(
+            (img.width(), img.height()),
+            (2 * cell + PADDING, 3 * cell + PADDING)
+        );
+
+        // One column of unlabelled cells fits this many rows before a new sheet starts.
+        let per_sheet = ((MAX_SHEET_HEIGHT - PADDING) / cell) as usize;
+        let result = build(write_pngs(&dir, per_sheet + 1), &dir.join("paged"), 1);
+        let sheets = result["sheets"].as_array().unwrap();
+        assert_eq!(sheets.len(), 2);
+        assert_eq!(sheets[0]["count"], per_sheet);
+        assert_eq!(sheets[1]["count"], 1);
+        assert!(sheets[0]["path"]
+            .as_str()
+            .unwrap()
+            .ends_with("contact_sheet_01.png"));
+        assert!(sheets[1]["path"]
+            .as_str()
+            .unwrap()
+            .ends_with("contact_sheet_02.png"));
+        assert_eq!(sheets[1]["height"], cell + PADDING);
+
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,353 @@
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+
+pub const DEFAULT_TEMPLATE: &str = "whisk_{timestamp}_{n}.{ext}";
+
+// Wide enough for a long prompt slug; anything bigger is a typo, not a file name.
+const MAX_WIDTH: usize = 64;
+
+const RESERVED_NAMES: [&str; 22] = [
+    "CON", "PRN", "AUX", "NUL", "COM1", "COM2", "COM3", "COM4", "COM5", "COM6", "COM7", "COM8",
+    "COM9", "LPT1", "LPT2", "LPT3", "LPT4", "LPT5", "LPT6", "LPT7", "LPT8", "LPT9",
+];
+
+const PLACEHOLDERS: [&str; 9] = [
+    "task_index",
+    "prompt_slug",
+    "seed",
+    "n",
+    "ext",
+    "timestamp",
+    "ratio",
+    "model",
+    "workflow",
+];
+
+#[derive(Debug, Clone, Default)]
+pub struct FilenameContext {
+    pub task_index: Option<u32>,
+    pub prompt: String,
+    pub seed: u32,
+    pub n: u32,
+    pub ext: String,
+    pub timestamp: u64,
+    pub ratio: String,
+    pub model: String,
+    pub workflow_id: String,
+}
+
+fn fold_ascii(c: char) -> Option<char> {
+    const GROUPS: [(&str, char); 7] = [
+        ("àáạảãâầấậẩẫăằắặẳẵäå", 'a'),
+        ("èéẹẻẽêềếệểễë", 'e'),
+        ("ìíịỉĩïî", 'i'),
+        ("òóọỏõôồốộổỗơờớợởỡöø", 'o'),
+        ("ùúụủũưừứựửữüû", 'u'),
+        ("ỳýỵỷỹÿ", 'y'),
+        ("đ", 'd'),
+    ];
+    if c.is_ascii_alphanumeric() {
+        return Some(c.to_ascii_lowercase());
+    }
+    let lower = c.to_lowercase().next().unwrap_or(c);
+    GROUPS
+        .iter()
+        .find(|(chars, _)| chars.contains(lower))
+        .map(|(_, ascii)| *ascii)
+}
+
+pub fn slugify(text: &str, max_len: usize) -> String {
+    let mut slug = String::new();
+    let mut pending_sep = false;
+    for c in text.chars() {
+        match fold_ascii(c) {
+            Some(a) => {
+                if pending_sep && !slug.is_empty() {
+                    slug.push('_');
+                }
+                pending_sep = false;
+                slug.push(a);
+            }
+            None => pending_sep = true,
+        }
+        if slug.len() >= max_len {
+            break;
+        }
+    }
+    slug.truncate(max_len);
+    slug.trim_end_matches('_').to_string()
+}
+
+fn sanitize_file_name(name: &str) -> String {
+    let cleaned: String = name
+        .chars()
+        .map(|c| match c {
+            '<' | '>' | ':' | '"' | '/' | '\\' | '|' | '?' | '*' => '_',
+            c if c.is_control() => '_',
+            c => c,
+        })
+        .collect();
+    let name = cleaned.trim().trim_end_matches(['.', ' ']);
+    // Windows refuses device names even with an extension ("nul.png").
+    let stem = name.split('.').next().unwrap_or("").trim_end();
+    if RESERVED_NAMES.iter().any(|r| r.eq_ignore_ascii_case(stem)) {
+        format!("_{}", name)
+    } else {
+        name.to_string()
+    }
+}
+
+fn render_placeholder(name: &str, spec: Option<&str>, ctx: &FilenameContext) -> String {
+    // Numeric values treat the spec as a zero-padded width ("03"), text values as a max length.
+    let width: Option<usize> = spec.and_then(|s| s.parse().ok());
+    let number = |v: u64| match width {
+        Some(w) => format!("{:0w$}", v, w = w),
+        None => v.to_string(),
+    };
+    let text = |v: &str| match width {
+        Some(w) => v.chars().take(w).collect(),
+        None => v.to_string(),
+    };
+
+    match name {
+        "task_index" => number(ctx.task_index.unwrap_or(0) as u64),
+        "seed" => number(ctx.seed as u64),
+        "n" => number(ctx.n as u64),
+        "timestamp" => number(ctx.timestamp),
+        "prompt_slug" => slugify(&ctx.prompt, width.unwrap_or(40)),
+        "ext" => text(&ctx.ext),
+        "ratio" => text(&ctx.ratio.replace(':', "x")),
+        "model" => text(&ctx.model.to_lowercase()),
+        "workflow" => text(&ctx.workflow_id.chars().take(8).collect::<String>()),
+        _ => String::new(),
+    }
+}
+
+pub fn render(template: &str, ctx: &FilenameContext) -> Result<String, String> {
+    let mut out = String::new();
+    let mut chars = template.chars().peekable();
+
+    while let Some(c) = chars.next() {
+        match c {
+            '{' if chars.peek() == Some(&'{') => {
+                chars.next();
+                out.push('{');
+            }
+            '}' if chars.peek() == Some(&'}') => {
+                chars.next();
+                out.push('}');
+            }
+            '{' => {
+                let mut inner = String::new();
+                let mut closed = false;
+                for c in chars.by_ref() {
+                    if c == '}' {
+                        closed = true;
+                        break;
+                    }
+                    inner.push(c);
+                }
+                if !closed {
+                    return Err(format!("Unclosed placeholder in template '{}'", template));
+                }
+                let (name, spec) = match inner.split_once(':') {
+                    Some((n, s)) => (n.trim(), Some(s.trim())),
+                    None => (inner.trim(), None),
+                };
+                if !PLACEHOLDERS.contains(&name) {
+                    return Err(format!(
+                        "Unknown placeholder '{{{}}}' (expected one of {})",
+                        name,
+                        PLACEHOLDERS.join(", ")
+                    ));
+                }
+                if let Some(s) = spec {
+                    match s.parse::<usize>() {
+                        Ok(w) if w <= MAX_WIDTH => {}
+                        Ok(_) => {
+                            return Err(format!(
+                                "Width '{}' for '{{{}}}' is larger than {}",
+                                s, name, MAX_WIDTH
+                            ))
+                        }
+                        Err(_) => return Err(format!("Invalid width '{}' for '{{{}}}'", s, name)),
+                    }
+                }
+                out.push_str(&render_placeholder(name, spec, ctx));
+            }
+            c => out.push(c),
+        }
+    }
+    let mut name = sanitize_file_name(&out);
+    if name.is_empty() || name.starts_with('.') {
+        return Err(format!(
+            "Template '{}' produced an empty file name",
+            template
+        ));
+    }
+    // A template that does not end in .{ext} would save files the OS cannot open by type.
+    let suffix = format!(".{}", ctx.ext.to_lowercase());
+    if !ctx.ext.is_empty() && !name.to_lowercase().ends_with(&suffix) {
+        name.push_str(&suffix);
+    }
+    Ok(name)
+}
+
+pub fn validate_template(template: &str) -> Result<(), String> {
+    let ctx = FilenameContext {
+        prompt: "prompt".to_string(),
+        n: 1,
+        ext: "png".to_string(),
+        ..Default::default()
+    };
+    render(template, &ctx).map(|_| ())
+}
+
+pub fn reserve_unique_path(folder: &Path, file_name: &str) -> Result<PathBuf, String> {
+    let (stem, ext) = match file_name.rsplit_once('.') {
+        Some((s, e)) if !s.is_empty() => (s.to_string(), format!(".{}", e)),
+        _ => (file_name.to_string(), String::new()),
+    };
+
+    for attempt in 0..10_000u32 {
+        let candidate = if attempt == 0 {
+            folder.join(file_name)
+        } else {
+            folder.join(format!("{}_{}{}", stem, attempt, ext))
+        };
+        // create_new is atomic, so concurrent saves can never pick the same name.
+        match OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(&candidate)
+        {
+            Ok(_) => return Ok(candidate),
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => continue,
+            Err(e) => return Err(format!("Cannot create {}: {}", candidate.display(), e)),
+        }
+    }
+    Err(format!("No free file name for {}", file_name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ctx() -> FilenameContext {
+        FilenameContext {
+            task_index: Some(7),
+            prompt: "Một con mèo: đang ngủ / trên ghế!".to_string(),
+            seed: 42,
+            n: 3,
+            ext: "png".to_string(),
+            timestamp: 1_700_000_000,
+            ratio: "16:9".to_string(),
+            model: "IMAGEN_3_5".to_string(),
+            workflow_id: "0123456789abcdef".to_string(),
+        }
+    }
+
+    fn temp_dir() -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("autowhisk_fn_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn renders_every_placeholder() {
+        let name = render(
+            "{task_index:03}_{seed:6}_{n}_{timestamp}_{ratio}_{model}_{workflow}_{prompt_slug}.{ext}",
+            &ctx(),
+        )
+        .unwrap();
+        assert_eq!(
+            name,
+            "007_000042_3_1700000000_16x9_imagen_3_5_01234567_mot_con_meo_dang_ngu_tren_ghe.png"
+        );
+    }
+
+    #[test]
+    fn text_width_is_a_max_length() {
+        assert_eq!(
+            render("{prompt_slug:8}.{ext}", &ctx()).unwrap(),
+            "mot_con.png"
+        );
+        assert_eq!(render("{model:6}.{ext}", &ctx()).unwrap(), "imagen.png");
+    }
+
+    #[test]
+    fn escaped_braces_are_literal() {
+        assert_eq!(render("{{x}}_{n}.{ext}", &ctx()).unwrap(), "{x}_3.png");
+    }
+
+    #[test]
+    fn extension_is_appended_when_missing() {
+        assert_eq!(render("{n}", &ctx()).unwrap(), "3.png");
+        assert_eq!(render("{{ext}}_{n}", &ctx()).unwrap(), "{ext}_3.png");
+        assert_eq!(render("{n}_{ext}", &ctx()).unwrap(), "3_png.png");
+        assert_eq!(render("{n}.PNG", &ctx()).unwrap(), "3.PNG");
+    }
+
+    #[test]
+    fn rejects_bad_templates() {
+        assert!(render("{nope}.{ext}", &ctx()).is_err());
+        assert!(render("{seed.{ext}", &ctx()).is_err());
+        assert!(render("{seed:abc}.{ext}", &ctx()).is_err());
+        assert!(render("...", &ctx()).is_err());
+    }
+
+    #[test]
+    fn width_is_capped() {
+        assert!(render("{seed:99999999}.{ext}", &ctx()).is_err());
+        assert!(render(&format!("{{seed:{}}}.{{ext}}", MAX_WIDTH + 1), &ctx()).is_err());
+        let name = render(&format!("{{seed:{}}}.{{ext}}", MAX_WIDTH), &ctx()).unwrap();
+        assert_eq!(name.len(), MAX_WIDTH + ".png".len());
+    }
+
+    #[test]
+    fn strips_characters_windows_rejects() {
+        let ctx = FilenameContext {
+            ratio: "a<b>c|d?e*f\"g".to_string(),
+            ..ctx()
+        };
+        assert_eq!(render("{ratio}. ", &ctx).unwrap(), "a_b_c_d_e_f_g.png");
+    }
+
+    #[test]
+    fn prefixes_reserved_device_names() {
+        assert_eq!(sanitize_file_name("CON"), "_CON");
+        assert_eq!(sanitize_file_name("nul.png"), "_nul.png");
+        assert_eq!(sanitize_file_name("Com1.tar.gz"), "_Com1.tar.gz");
+        assert_eq!(sanitize_file_name("console.png"), "console.png");
+        assert_eq!(sanitize_file_name("lpt10.png"), "lpt10.png");
+        let ctx = FilenameContext {
+            model: "AUX".to_string(),
+            ..ctx()
+        };
+        assert_eq!(render("{model}.{ext}", &ctx).unwrap(), "_aux.png");
+    }
+
+    #[test]
+    fn slugify_folds_vietnamese_and_collapses_separators() {
+        assert_eq!(slugify("  Đường phố -- Hà Nội  ", 40), "duong_pho_ha_noi");
+        assert_eq!(slugify("abc def", 5), "abc_d");
+        assert_eq!(slugify("abc def", 4), "abc");
+        assert_eq!(slugify("!!!", 10), "");
+    }
+
+    #[test]
+    fn reserve_unique_path_adds_suffixes() {
+        let dir = temp_dir();
+        let first = reserve_unique_path(&dir, "img.png").unwrap();
+        let second = reserve_unique_path(&dir, "img.png").unwrap();
+        let third = reserve_unique_path(&dir, "img.png").unwrap();
+        let bare = reserve_unique_path(&dir, "noext").unwrap();
+        let bare2 = reserve_unique_path(&dir, "noext").unwrap();
+        assert_eq!(first, dir.join("img.png"));
+        assert_eq!(second, dir.join("img_1.png"));
+        assert_eq!(third, dir.join("img_2.png"));
+        assert_eq!(bare, dir.join("noext"));
+        assert_eq!(bare2, dir.join("noext_1"));
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,524 @@
+use crate::settings::app_data_dir;
+use crate::util::re;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+
+const DEFAULT_PAGE_SIZE: u32 = 50;
+const MAX_PAGE_SIZE: u32 = 500;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryEntry {
+    pub id: i64,
+    pub created_at: u64,
+    pub tab: String,
+    pub input: String,
+    pub output: Vec<String>,
+    pub output_count: u32,
+    pub pinned: bool,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct HistoryQuery {
+    #[serde(default)]
+    pub text: Option<String>,
+    #[serde(default)]
+    pub tab: Option<String>,
+    #[serde(default)]
+    pub pinned_only: bool,
+    #[serde(default)]
+    pub limit: Option<u32>,
+    #[serde(default)]
+    pub offset: Option<u32>,
+}
+
+// Shape written by the old localStorage hook and the C# tool; `time` is a vi-VN locale
+// string such as "14:30:05 18/10/2026".
+#[derive(Debug, Deserialize, Clone, Default)]
+pub struct LegacyHistoryEntry {
+    #[serde(default)]
+    pub time: String,
+    #[serde(default)]
+    pub tab: String,
+    #[serde(default)]
+    pub input: String,
+    #[serde(default)]
+    pub output: Vec<String>,
+    #[serde(default)]
+    pub output_count: Option<u32>,
+}
+
+fn open_db() -> Result<Connection, String> {
+    let dir = app_data_dir();
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let conn = Connection::open(dir.join("history.db")).map_err(|e| e.to_string())?;
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS history (
+            id INTEGER PRIMARY KEY AUTOINCREMENT,
+            created_at INTEGER NOT NULL,
+            tab TEXT NOT NULL DEFAULT '',
+            input TEXT NOT NULL DEFAULT '',
+            output TEXT NOT NULL DEFAULT '[]',
+            output_count INTEGER NOT NULL DEFAULT 0,
+            pinned INTEGER NOT NULL DEFAULT 0
+        );
+        CREATE INDEX IF NOT EXISTS idx_history_created ON history(created_at);
+        CREATE INDEX IF NOT EXISTS idx_history_tab ON history(tab);
+        CREATE VIRTUAL TABLE IF NOT EXISTS history_fts USING fts5(tab, input, output);",
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(conn)
+}
+
+fn row_to_entry(row: &Row) -> rusqlite::Result<HistoryEntry> {
+    let output: String = row.get("output")?;
+    Ok(HistoryEntry {
+        id: row.get("id")?,
+        created_at: row.get::<_, i64>("created_at")? as u64,
+        tab: row.get("tab")?,
+        input: row.get("input")?,
+        output: serde_json::from_str(&output).unwrap_or_default(),
+        output_count: row.get("output_count")?,
+        pinned: row.get("pinned")?,
+    })
+}
+
+fn find_entry(conn: &Connection, id: i64) -> Result<Option<HistoryEntry>, String> {
+    conn.query_row("SELECT * FROM history WHERE id = ?1", [id], row_to_entry)
+        .optional()
+        .map_err(|e| e.to_string())
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+// Writes the row and its FTS entry; callers run it inside a transaction.
+fn insert_entry(
+    conn: &Connection,
+    created_at: u64,
+    tab: &str,
+    input: &str,
+    output: &[String],
+    output_count: u32,
+) -> Result<i64, String> {
+    conn.execute(
+        "INSERT INTO history (created_at, tab, input, output, output_count)
+         VALUES (?1, ?2, ?3, ?4, ?5)",
+        params![
+            created_at as i64,
+            tab,
+            input,
+            serde_json::to_string(output).unwrap_or_else(|_| "[]".to_string()),
+            output_count,
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    let id = conn.last_insert_rowid();
+    conn.execute(
+        "INSERT INTO history_fts (rowid, tab, input, output) VALUES (?1, ?2, ?3, ?4)",
+        params![id, tab, input, output.join("\n")],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+// Unlike the old localStorage store, input and output are kept whole and nothing is
+// evicted; pruning is left to clear_history.
+pub fn add_history(tab: &str, input: &str, output: &[String]) -> Result<HistoryEntry, String> {
+    let conn = open_db()?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let id = insert_entry(
+        &tx,
+        unix_now(),
+        tab.trim(),
+        input,
+        output,
+        output.len() as u32,
+    )?;
+    tx.commit().map_err(|e| e.to_string())?;
+    find_entry(&conn, id)?.ok_or_else(|| format!("History entry not found: {}", id))
+}
+
+// Every word must match, as a prefix, somewhere in the tab, input or output. Quoting
+// each word keeps FTS5 operators typed by the user from being interpreted.
+fn fts_query(text: &str) -> Option<String> {
+    let terms: Vec<String> = text
+        .split_whitespace()
+        .map(|w| w.replace('"', ""))
+        .filter(|w| !w.is_empty())
+        .map(|w| format!("\"{}\"*", w))
+        .collect();
+    if terms.is_empty() {
+        None
+    } else {
+        Some(terms.join(" "))
+    }
+}
+
+pub fn search_history(query: &HistoryQuery) -> Result<Value, String> {
+    let conn = open_db()?;
+    let mut clauses: Vec<&str> = Vec::new();
+    let mut args: Vec<String> = Vec::new();
+
+    if let Some(fts) = query.text.as_deref().and_then(fts_query) {
+        clauses.push("id IN (SELECT rowid FROM history_fts WHERE history_fts MATCH ?)");
+        args.push(fts);
+    }
+    if let Some(tab) = query.tab.as_deref().filter(|t| !t.trim().is_empty()) {
+        clauses.push("tab = ?");
+        args.push(tab.trim().to_string());
+    }
+    if query.pinned_only {
+        clauses.push("pinned = 1");
+    }
+
+    let where_sql = if clauses.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", clauses.join(" AND "))
+    };
+
+    let total: i64 = conn
+        .query_row(
+            &format!("SELECT COUNT(*) FROM history {}", where_sql),
+            params_from_iter(args.iter()),
+            |r| r.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let offset = query.offset.unwrap_or(0);
+    // Pinned entries stay on the first page regardless of age.
+    let sql = format!(
+        "SELECT * FROM history {} ORDER BY pinned DESC, created_at DESC, id DESC LIMIT {} OFFSET {}",
+        where_sql, limit, offset
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let entries: Vec<HistoryEntry> = stmt
+        .query_map(params_from_iter(args.iter()), row_to_entry)
+        .map_err(|e| e.to_string())?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(json!({
+        "total": total,
+        "limit": limit,
+        "offset": offset,
+        "entries": entries
+    }))
+}
+
+pub fn get_history(id: i64) -> Result<HistoryEntry, String> {
+    let conn = open_db()?;
+    find_entry(&conn, id)?.ok_or_else(|| format!("History entry not found: {}", id))
+}
+
+pub fn set_pinned(id: i64, pinned: bool) -> Result<HistoryEntry, String> {
+    let conn = open_db()?;
+    conn.execute(
+        "UPDATE history SET pinned = ?1 WHERE id = ?2",
+        params![pinned, id],
+    )
+    .map_err(|e| e.to_string())?;
+    find_entry(&conn, id)?.ok_or_else(|| format!("History entry not found: {}", id))
+}
+
+pub fn delete_history(id: i64) -> Result<bool, String> {
+    let conn = open_db()?;
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    let removed = tx
+        .execute("DELETE FROM history WHERE id = ?1", [id])
+        .map_err(|e| e.to_string())?;
+    tx.execute("DELETE FROM history_fts WHERE rowid = ?1", [id])
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(removed > 0)
+}
+
+pub fn clear_history(keep_pinned: bool) -> Result<usize, String> {
+    let conn = open_db()?;
+    let filter = if keep_pinned { "WHERE pinned = 0" } else { "" };
+    let tx = conn.unchecked_transaction().map_err(|e| e.to_string())?;
+    tx.execute(
+        &format!(
+            "DELETE FROM history_fts WHERE rowid IN (SELECT id FROM history {})",
+            filter
+        ),
+        [],
+    )
+    .map_err(|e| e.to_string())?;
+    let removed = tx
+        .execute(&format!("DELETE FROM history {}", filter), [])
+        .map_err(|e| e.to_string())?;
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(removed)
+}
+
+// "json" writes the entries as stored; "txt" writes just the outputs, one per line,
+// for pasting back into a batch.
+pub fn export_history(ids: Option<&[i64]>, path: &str, format: &str) -> Result<Value, String> {
+    let conn = open_db()?;
+    let entries: Vec<HistoryEntry> = match ids {
+        Some(ids) => ids
+            .iter()
+            .filter_map(|id| find_entry(&conn, *id).transpose())
+            .collect::<Result<_, _>>()?,
+        None => {
+            let mut stmt = conn
+                .prepare("SELECT * FROM history ORDER BY created_at DESC, id DESC")
+                .map_err(|e| e.to_string())?;
+            let rows: Vec<HistoryEntry> = stmt
+                .query_map([], row_to_entry)
+                .map_err(|e| e.to_string())?
+                .filter_map(|r| r.ok())
+                .collect();
+            rows
+        }
+    };
+
+    let body = match format.trim().to_lowercase().as_str() {
+        "" | "json" => serde_json::to_string_pretty(&entries).map_err(|e| e.to_string())?,
+        "txt" => entries
+            .iter()
+            .flat_map(|e| e.output.iter().map(String::as_str))
+            .collect::<Vec<_>>()
+            .join("\n"),
+        other => return Err(format!("Unknown history export format: {}", other)),
+    };
+    if let Some(parent) = std::path::Path::new(path).parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    std::fs::write(path, body).map_err(|e| e.to_string())?;
+
+    Ok(json!({
+        "path": path,
+        "count": entries.len()
+    }))
+}
+
+fn days_from_civil(year: i64, month: i64, day: i64) -> i64 {
+    let y = if month <= 2 { year - 1 } else { year };
+    let era = y.div_euclid(400);
+    let yoe = y - era * 400;
+    let mp = (month + 9) % 12;
+    let doy = (153 * mp + 2) / 5 + day - 1;
+    let doe = yoe * 365 + yoe / 4 - yoe / 100 + doy;
+    era * 146097 + doe - 719468
+}
+
+// Locale strings carry no offset, so they are read as UTC; that keeps imported entries
+// in their original order, which is all the old field was good for.
+fn parse_legacy_time(time: &str) -> Option<u64> {
+    let date = re!(r"(\d{1,2})/(\d{1,2})/(\d{4})").captures(time)?;
+    let clock = re!(r"(\d{1,2}):(\d{2})(?::(\d{2}))?").captures(time);
+    let num = |caps: &regex::Captures, i: usize| -> i64 {
+        caps.get(i)
+            .and_then(|m| m.as_str().parse().ok())
+            .unwrap_or(0)
+    };
+    let (day, month, year) = (num(&date, 1), num(&date, 2), num(&date, 3));
+    if !(1..=12).contains(&month) || !(1..=31).contains(&day) {
+        return None;
+    }
+    let seconds = clock
+        .map(|c| num(&c, 1) * 3600 + num(&c, 2) * 60 + num(&c, 3))
+        .unwrap_or(0);
+    let ts = days_from_civil(year, month, day) * 86400 + seconds;
+    u64::try_from(ts).ok()
+}
+
+// One-off migration of the localStorage history; entries arrive newest first. All or
+// nothing: the frontend only drops its copy after a success, so a partial import would be
+// imported again on the next start.
+pub fn import_legacy(entries: &[LegacyHistoryEntry]) -> Result<usize, String> {
+    let mut conn = open_db()?;
+    let tx = conn.transaction().map_err(|e| e.to_string())?;
+    let now = unix_now();
+    let mut imported = 0;
+    for entry in entries.iter().rev() {
+        if entry.input.trim().is_empty() && entry.output.is_empty() {
+            continue;
+        }
+        let created_at = parse_legacy_time(&entry.time).unwrap_or(now);
+        // The old store cut output to 50 items but kept the real count.
+        let count = entry.output_count.unwrap_or(entry.output.len() as u32);
+        insert_entry(
+            &tx,
+            created_at,
+            entry.tab.trim(),
+            &entry.input,
+            &entry.output,
+            count,
+        )?;
+        imported += 1;
+    }
+    tx.commit().map_err(|e| e.to_string())?;
+    Ok(imported)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Tests share one database, so each works in its own tab.
+    fn tab() -> String {
+        format!("test-{}", uuid::Uuid::new_v4().simple())
+    }
+
+    fn query(tab: &str) -> HistoryQuery {
+        HistoryQuery {
+            tab: Some(tab.to_string()),
+            ..Default::default()
+        }
+    }
+
+    fn ids(result: &Value) -> Vec<i64> {
+        result["entries"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|e| e["id"].as_i64().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn search_matches_every_word_as_a_prefix() {
+        let tab = tab();
+        let cat = add_history(&tab, "a sleeping cat", &["cat on a mat".into()]).unwrap();
+        let dog = add_history(&tab, "a running dog", &["dog in the \"park\"".into()]).unwrap();
+
+        let search = |text: &str| {
+            ids(&search_history(&HistoryQuery {
+                text: Some(text.to_string()),
+                ..query(&tab)
+            })
+            .unwrap())
+        };
+        assert_eq!(search("sleep"), vec![cat.id]);
+        assert_eq!(search("dog par"), vec![dog.id]);
+        assert_eq!(search("cat dog"), Vec::<i64>::new());
+        assert_eq!(search("\"park\" OR"), Vec::<i64>::new());
+        assert_eq!(search("   ").len(), 2);
+    }
+
+    #[test]
+    fn pinned_entries_lead_and_filter() {
+        let tab = tab();
+        let old = add_history(&tab, "old", &[]).unwrap();
+        let new = add_history(&tab, "new", &[]).unwrap();
+        assert!(set_pinned(old.id, true).unwrap().pinned);
+
+        assert_eq!(
+            ids(&search_history(&query(&tab)).unwrap()),
+            vec![old.id, new.id]
+        );
+        let pinned = search_history(&HistoryQuery {
+            pinned_only: true,
+            ..query(&tab)
+        })
+        .unwrap();
+        assert_eq!(ids(&pinned), vec![old.id]);
+
+        assert!(delete_history(new.id).unwrap());
+        assert!(!delete_history(new.id).unwrap());
+        assert!(get_history(new.id).is_err());
+    }
+
+    #[test]
+    fn pages_cover_every_entry_once() {
+        let tab = tab();
+        let added: Vec<i64> = (0..5)
+            .map(|i| add_history(&tab, &format!("prompt {}", i), &[]).unwrap().id)
+            .collect();
+        let page = |offset| {
+            search_history(&HistoryQuery {
+                limit: Some(2),
+                offset: Some(offset),
+                ..query(&tab)
+            })
+            .unwrap()
+        };
+        let first = page(0);
+        assert_eq!(first["total"], 5);
+        let mut seen: Vec<i64> = [0, 2, 4].iter().flat_map(|&o| ids(&page(o))).collect();
+        seen.sort_unstable();
+        assert_eq!(seen, added);
+
+        let capped = search_history(&HistoryQuery {
+            limit: Some(100_000),
+            ..query(&tab)
+        })
+        .unwrap();
+        assert_eq!(capped["limit"], MAX_PAGE_SIZE);
+    }
+
+    #[test]
+    fn export_writes_json_and_txt() {
+        let tab = tab();
+        let a = add_history(&tab, "a", &["one".into(), "two".into()]).unwrap();
+        let b = add_history(&tab, "b", &["three".into()]).unwrap();
+        let dir = std::env::temp_dir().join(format!("autowhisk_hist_{}", uuid::Uuid::new_v4()));
+
+        let txt = dir.join("out.txt");
+        let result = export_history(Some(&[a.id, b.id]), &txt.to_string_lossy(), "txt").unwrap();
+        assert_eq!(result["count"], 2);
+        assert_eq!(std::fs::read_to_string(&txt).unwrap(), "one\ntwo\nthree");
+
+        let json = dir.join("out.json");
+        export_history(Some(&[b.id]), &json.to_string_lossy(), "json").unwrap();
+        let written: Vec<HistoryEntry> =
+            serde_json::from_str(&std::fs::read_to_string(&json).unwrap()).unwrap();
+        assert_eq!(written.len(), 1);
+        assert_eq!(written[0].output, vec!["three"]);
+
+        assert!(export_history(None, &json.to_string_lossy(), "csv").is_err());
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+
+    #[test]
+    fn legacy_times_parse_as_utc() {
+        assert_eq!(parse_legacy_time("00:00:00 01/01/1970"), Some(0));
+        assert_eq!(parse_legacy_time("14:30:05 18/10/2026"), Some(1792333805));
+        assert_eq!(parse_legacy_time("18/10/2026, 14:30"), Some(1792333800));
+        assert_eq!(parse_legacy_time("29/02/2024"), Some(1709164800));
+        assert_eq!(parse_legacy_time("10/13/2026"), None);
+        assert_eq!(parse_legacy_time("yesterday"), None);
+    }
+
+    #[test]
+    fn legacy_import_counts_what_it_inserts() {
+        let tab = tab();
+        let entry = |time: &str, input: &str| LegacyHistoryEntry {
+            time: time.to_string(),
+            tab: tab.clone(),
+            input: input.to_string(),
+            output: vec![format!("{} out", input)],
+            output_count: Some(60),
+        };
+        let entries = vec![
+            entry("10:00:00 02/01/2024", "newer"),
+            LegacyHistoryEntry {
+                tab: tab.clone(),
+                ..Default::default()
+            },
+            entry("10:00:00 01/01/2024", "older"),
+        ];
+        assert_eq!(import_legacy(&entries).unwrap(), 2);
+
+        let result = search_history(&query(&tab)).unwrap();
+        let stored: Vec<HistoryEntry> = serde_json::from_value(result["entries"].clone()).unwrap();
+        let inputs: Vec<&str> = stored.iter().map(|e| e.input.as_str()).collect();
+        assert_eq!(inputs, vec!["newer", "older"]);
+        assert_eq!(stored[0].output_count, 60);
+        assert_eq!(stored[1].created_at, 1704103200);
+    }
+}
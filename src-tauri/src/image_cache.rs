@@ -0,0 +1,118 @@
+use std::collections::{HashMap, VecDeque};
+use std::sync::{Mutex, OnceLock};
+
+pub const SCHEME: &str = "whisk-img";
+
+const MAX_ENTRIES: usize = 256;
+const MAX_BYTES: usize = 512 * 1024 * 1024;
+
+struct CachedImage {
+    bytes: Vec<u8>,
+    mime: &'static str,
+}
+
+#[derive(Default)]
+struct ImageCache {
+    entries: HashMap<String, CachedImage>,
+    order: VecDeque<String>,
+    total_bytes: usize,
+}
+
+impl ImageCache {
+    fn evict(&mut self) {
+        while self.entries.len() > MAX_ENTRIES || self.total_bytes > MAX_BYTES {
+            let Some(oldest) = self.order.pop_front() else {
+                break;
+            };
+            if let Some(img) = self.entries.remove(&oldest) {
+                self.total_bytes -= img.bytes.len();
+            }
+        }
+    }
+}
+
+fn cache() -> &'static Mutex<ImageCache> {
+    static CACHE: OnceLock<Mutex<ImageCache>> = OnceLock::new();
+    CACHE.get_or_init(|| Mutex::new(ImageCache::default()))
+}
+
+pub fn mime_for(bytes: &[u8]) -> &'static str {
+    match crate::output::detect_ext(bytes) {
+        Some("png") => "image/png",
+        Some("webp") => "image/webp",
+        _ => "image/jpeg",
+    }
+}
+
+pub fn insert(bytes: Vec<u8>) -> String {
+    let id = uuid::Uuid::new_v4().simple().to_string();
+    let mime = mime_for(&bytes);
+
+    let mut cache = cache().lock().unwrap_or_else(|e| e.into_inner());
+    cache.total_bytes += bytes.len();
+    cache
+        .entries
+        .insert(id.clone(), CachedImage { bytes, mime });
+    cache.order.push_back(id.clone());
+    cache.evict();
+    id
+}
+
+pub fn get(id: &str) -> Option<(Vec<u8>, &'static str)> {
+    let cache = cache().lock().unwrap_or_else(|e| e.into_inner());
+    cache
+        .entries
+        .get(id)
+        .map(|img| (img.bytes.clone(), img.mime))
+}
+
+pub fn release(ids: &[String]) -> usize {
+    let mut cache = cache().lock().unwrap_or_else(|e| e.into_inner());
+    let mut released = 0;
+    for id in ids {
+        if let Some(img) = cache.entries.remove(id) {
+            cache.total_bytes -= img.bytes.len();
+            released += 1;
+        }
+    }
+    cache.order.retain(|id| !ids.contains(id));
+    released
+}
+
+// Webviews on Windows reach custom schemes through http://<scheme>.localhost.
+pub fn url_for(id: &str) -> String {
+    if cfg!(windows) {
+        format!("http://{}.localhost/{}", SCHEME, id)
+    } else {
+        format!("{}://localhost/{}", SCHEME, id)
+    }
+}
+
+pub fn handle_request(path: &str) -> (u16, &'static str, Vec<u8>) {
+    let id = path
+        .trim_start_matches('/')
+        .split(['?', '#'])
+        .next()
+        .unwrap_or("");
+    match get(id) {
+        Some((bytes, mime)) => (200, mime, bytes),
+        None => (404, "text/plain", b"image not found".to_vec()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn released_images_are_no_longer_served() {
+        let kept = insert(b"\x89PNG\r\n\x1a\nkept".to_vec());
+        let dropped = insert(vec![0xFF, 0xD8, 0xFF, 0xE0]);
+        assert_eq!(get(&kept).map(|(_, mime)| mime), Some("image/png"));
+
+        assert_eq!(release(&[dropped.clone(), "unknown".to_string()]), 1);
+        assert_eq!(handle_request(&format!("/{}", dropped)).0, 404);
+        assert_eq!(handle_request(&format!("/{}?t=1", kept)).0, 200);
+        assert_eq!(release(&[kept]), 1);
+    }
+}
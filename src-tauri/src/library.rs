@@ -0,0 +1,452 @@
+use crate::filename;
+use crate::manifest::sha256_hex;
+use crate::settings::app_data_dir;
+use rusqlite::{params, params_from_iter, Connection, OptionalExtension, Row};
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::path::{Path, PathBuf};
+
+const DEFAULT_PAGE_SIZE: u32 = 100;
+const MAX_PAGE_SIZE: u32 = 500;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LibraryImage {
+    pub id: String,
+    #[serde(default)]
+    pub file_path: String,
+    #[serde(default)]
+    pub ext: String,
+    #[serde(default)]
+    pub size: u64,
+    #[serde(default)]
+    pub prompt: String,
+    #[serde(default)]
+    pub seed: Option<u32>,
+    #[serde(default)]
+    pub aspect_ratio: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub account_id: Option<String>,
+    #[serde(default)]
+    pub account_email: Option<String>,
+    #[serde(default)]
+    pub workflow_id: Option<String>,
+    #[serde(default)]
+    pub batch_id: Option<String>,
+    #[serde(default)]
+    pub task_index: Option<u32>,
+    #[serde(default)]
+    pub source_path: Option<String>,
+    #[serde(default)]
+    pub created_at: u64,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default)]
+    pub rating: u8,
+    #[serde(default)]
+    pub phash: Option<String>,
+    #[serde(default)]
+    pub dhash: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SearchQuery {
+    #[serde(default)]
+    pub text: Option<String>,
+    #[serde(default)]
+    pub account_id: Option<String>,
+    #[serde(default)]
+    pub batch_id: Option<String>,
+    #[serde(default)]
+    pub workflow_id: Option<String>,
+    #[serde(default)]
+    pub tag: Option<String>,
+    #[serde(default)]
+    pub min_rating: Option<u8>,
+    #[serde(default)]
+    pub limit: Option<u32>,
+    #[serde(default)]
+    pub offset: Option<u32>,
+}
+
+pub fn library_dir() -> PathBuf {
+    app_data_dir().join("library")
+}
+
+fn open_db() -> Result<Connection, String> {
+    let dir = library_dir();
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let conn = Connection::open(dir.join("library.db")).map_err(|e| e.to_string())?;
+    conn.execute_batch(
+        "CREATE TABLE IF NOT EXISTS images (
+            id TEXT PRIMARY KEY,
+            file_path TEXT NOT NULL,
+            ext TEXT NOT NULL,
+            size INTEGER NOT NULL DEFAULT 0,
+            prompt TEXT NOT NULL DEFAULT '',
+            seed INTEGER,
+            aspect_ratio TEXT,
+            model TEXT,
+            account_id TEXT,
+            account_email TEXT,
+            workflow_id TEXT,
+            batch_id TEXT,
+            task_index INTEGER,
+            source_path TEXT,
+            created_at INTEGER NOT NULL,
+            tags TEXT NOT NULL DEFAULT '[]',
+            rating INTEGER NOT NULL DEFAULT 0
+        );
+        CREATE INDEX IF NOT EXISTS idx_images_created ON images(created_at);
+        CREATE INDEX IF NOT EXISTS idx_images_batch ON images(batch_id);",
+    )
+    .map_err(|e| e.to_string())?;
+    ensure_column(&conn, "phash", "TEXT")?;
+    ensure_column(&conn, "dhash", "TEXT")?;
+    Ok(conn)
+}
+
+fn ensure_column(conn: &Connection, name: &str, decl: &str) -> Result<(), String> {
+    let mut stmt = conn
+        .prepare("SELECT name FROM pragma_table_info('images')")
+        .map_err(|e| e.to_string())?;
+    let exists = stmt
+        .query_map([], |r| r.get::<_, String>(0))
+        .map_err(|e| e.to_string())?
+        .filter_map(|r| r.ok())
+        .any(|c| c == name);
+    if !exists {
+        conn.execute(
+            &format!("ALTER TABLE images ADD COLUMN {} {}", name, decl),
+            [],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    Ok(())
+}
+
+fn row_to_image(row: &Row) -> rusqlite::Result<LibraryImage> {
+    let tags: String = row.get("tags")?;
+    Ok(LibraryImage {
+        id: row.get("id")?,
+        file_path: row.get("file_path")?,
+        ext: row.get("ext")?,
+        size: row.get::<_, i64>("size")? as u64,
+        prompt: row.get("prompt")?,
+        seed: row.get("seed")?,
+        aspect_ratio: row.get("aspect_ratio")?,
+        model: row.get("model")?,
+        account_id: row.get("account_id")?,
+        account_email: row.get("account_email")?,
+        workflow_id: row.get("workflow_id")?,
+        batch_id: row.get("batch_id")?,
+        task_index: row.get("task_index")?,
+        source_path: row.get("source_path")?,
+        created_at: row.get::<_, i64>("created_at")? as u64,
+        tags: serde_json::from_str(&tags).unwrap_or_default(),
+        rating: row.get("rating")?,
+        phash: row.get("phash")?,
+        dhash: row.get("dhash")?,
+    })
+}
+
+fn find_image(conn: &Connection, id: &str) -> Result<Option<LibraryImage>, String> {
+    conn.query_row("SELECT * FROM images WHERE id = ?1", [id], row_to_image)
+        .optional()
+        .map_err(|e| e.to_string())
+}
+
+// Entries are keyed by the hash of the image as generated. Hashing the stored bytes
+// would never dedupe, since the embedded metadata carries a timestamp.
+pub fn add_image(
+    source: &[u8],
+    bytes: &[u8],
+    ext: &str,
+    record: LibraryImage,
+) -> Result<String, String> {
+    let id = sha256_hex(source);
+    let dir = library_dir().join("images").join(&id[..2]);
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    let path = dir.join(format!("{}.{}", id, ext));
+    if !path.exists() {
+        std::fs::write(&path, bytes).map_err(|e| e.to_string())?;
+    }
+
+    let conn = open_db()?;
+    conn.execute(
+        "INSERT INTO images (id, file_path, ext, size, prompt, seed, aspect_ratio, model,
+            account_id, account_email, workflow_id, batch_id, task_index, source_path,
+            created_at, tags, rating, phash, dhash)
+         VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8, ?9, ?10, ?11, ?12, ?13, ?14, ?15, ?16, ?17,
+            ?18, ?19)
+         ON CONFLICT(id) DO NOTHING",
+        params![
+            id,
+            path.to_string_lossy().to_string(),
+            ext,
+            bytes.len() as i64,
+            record.prompt,
+            record.seed,
+            record.aspect_ratio,
+            record.model,
+            record.account_id,
+            record.account_email,
+            record.workflow_id,
+            record.batch_id,
+            record.task_index,
+            record.source_path,
+            record.created_at as i64,
+            serde_json::to_string(&record.tags).unwrap_or_else(|_| "[]".to_string()),
+            record.rating,
+            record.phash,
+            record.dhash,
+        ],
+    )
+    .map_err(|e| e.to_string())?;
+    Ok(id)
+}
+
+// Makes user text match literally inside a LIKE pattern that uses ESCAPE '\'.
+fn escape_like(text: &str) -> String {
+    text.replace('\\', "\\\\")
+        .replace('%', "\\%")
+        .replace('_', "\\_")
+}
+
+pub fn search_images(query: &SearchQuery) -> Result<Value, String> {
+    let conn = open_db()?;
+    let mut clauses: Vec<&str> = Vec::new();
+    let mut args: Vec<String> = Vec::new();
+
+    if let Some(text) = query.text.as_deref().filter(|t| !t.trim().is_empty()) {
+        clauses.push("(prompt LIKE ? ESCAPE '\\' OR tags LIKE ? ESCAPE '\\')");
+        let pattern = format!("%{}%", escape_like(text.trim()));
+        args.push(pattern.clone());
+        args.push(pattern);
+    }
+    if let Some(account) = &query.account_id {
+        clauses.push("account_id = ?");
+        args.push(account.clone());
+    }
+    if let Some(batch) = &query.batch_id {
+        clauses.push("batch_id = ?");
+        args.push(batch.clone());
+    }
+    if let Some(wf) = &query.workflow_id {
+        clauses.push("workflow_id = ?");
+        args.push(wf.clone());
+    }
+    if let Some(tag) = &query.tag {
+        clauses.push("tags LIKE ? ESCAPE '\\'");
+        args.push(format!(
+            "%{}%",
+            escape_like(&serde_json::to_string(tag).unwrap_or_default())
+        ));
+    }
+    if let Some(min) = query.min_rating {
+        clauses.push("rating >= ?");
+        args.push(min.to_string());
+    }
+
+    let where_sql = if clauses.is_empty() {
+        String::new()
+    } else {
+        format!("WHERE {}", clauses.join(" AND "))
+    };
+
+    let total: i64 = conn
+        .query_row(
+            &format!("SELECT COUNT(*) FROM images {}", where_sql),
+            params_from_iter(args.iter()),
+            |r| r.get(0),
+        )
+        .map_err(|e| e.to_string())?;
+
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_PAGE_SIZE)
+        .clamp(1, MAX_PAGE_SIZE);
+    let offset = query.offset.unwrap_or(0);
+    let sql = format!(
+        "SELECT * FROM images {} ORDER BY created_at DESC LIMIT {} OFFSET {}",
+        where_sql, limit, offset
+    );
+    let mut stmt = conn.prepare(&sql).map_err(|e| e.to_string())?;
+    let images: Vec<LibraryImage> = stmt
+        .query_map(params_from_iter(args.iter()), row_to_image)
+        .map_err(|e| e.to_string())?
+        .filter_map(|r| r.ok())
+        .collect();
+
+    Ok(json!({
+        "total": total,
+        "images": images
+    }))
+}
+
+// Prefers the user's saved copy when it still exists, falling back to the library file.
+pub fn batch_images(batch_id: &str) -> Result<Vec<LibraryImage>, String> {
+    let conn = open_db()?;
+    let mut stmt = conn
+        .prepare("SELECT * FROM images WHERE batch_id = ?1 ORDER BY task_index, created_at")
+        .map_err(|e| e.to_string())?;
+    let images = stmt
+        .query_map([batch_id], row_to_image)
+        .map_err(|e| e.to_string())?
+        .filter_map(|r| r.ok())
+        .map(|mut img| {
+            if let Some(source) = img.source_path.as_ref().filter(|p| Path::new(p).exists()) {
+                img.file_path = source.clone();
+            }
+            img
+        })
+        .collect();
+    Ok(images)
+}
+
+pub fn batch_paths(batch_id: &str) -> Result<Vec<String>, String> {
+    Ok(batch_images(batch_id)?
+        .into_iter()
+        .map(|img| img.file_path)
+        .collect())
+}
+
+pub fn get_image(id: &str) -> Result<Value, String> {
+    let conn = open_db()?;
+    match find_image(&conn, id)? {
+        Some(img) => serde_json::to_value(img).map_err(|e| e.to_string()),
+        None => Err(format!("Image not found: {}", id)),
+    }
+}
+
+pub fn update_image(
+    id: &str,
+    tags: Option<Vec<String>>,
+    rating: Option<u8>,
+) -> Result<Value, String> {
+    let conn = open_db()?;
+    if let Some(tags) = tags {
+        let tags: Vec<String> = tags
+            .into_iter()
+            .map(|t| t.trim().to_string())
+            .filter(|t| !t.is_empty())
+            .collect();
+        conn.execute(
+            "UPDATE images SET tags = ?1 WHERE id = ?2",
+            params![serde_json::to_string(&tags).unwrap_or_default(), id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    if let Some(rating) = rating {
+        conn.execute(
+            "UPDATE images SET rating = ?1 WHERE id = ?2",
+            params![rating.min(5), id],
+        )
+        .map_err(|e| e.to_string())?;
+    }
+    get_image(id)
+}
+
+pub fn export_images(ids: &[String], folder: &str) -> Result<Value, String> {
+    let conn = open_db()?;
+    let target = Path::new(folder);
+    std::fs::create_dir_all(target).map_err(|e| e.to_string())?;
+
+    let mut exported = Vec::new();
+    let mut failed = Vec::new();
+    for id in ids {
+        let result = find_image(&conn, id).and_then(|img| {
+            let img = img.ok_or_else(|| "Image not found".to_string())?;
+            let slug = filename::slugify(&img.prompt, 40);
+            let name = if slug.is_empty() {
+                format!("{}.{}", &img.id[..12], img.ext)
+            } else {
+                format!("{}_{}.{}", slug, &img.id[..12], img.ext)
+            };
+            let dest = filename::reserve_unique_path(target, &name)?;
+            std::fs::copy(&img.file_path, &dest).map_err(|e| {
+                let _ = std::fs::remove_file(&dest);
+                e.to_string()
+            })?;
+            Ok(dest.to_string_lossy().to_string())
+        });
+        match result {
+            Ok(path) => exported.push(json!({ "id": id, "path": path })),
+            Err(e) => failed.push(json!({ "id": id, "error": e })),
+        }
+    }
+
+    Ok(json!({
+        "exported": exported,
+        "failed": failed
+    }))
+}
+
+pub fn delete_image(id: &str) -> Result<bool, String> {
+    let conn = open_db()?;
+    let Some(img) = find_image(&conn, id)? else {
+        return Ok(false);
+    };
+    conn.execute("DELETE FROM images WHERE id = ?1", [id])
+        .map_err(|e| e.to_string())?;
+    let _ = std::fs::remove_file(&img.file_path);
+    Ok(true)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn image(prompt: &str, batch: &str) -> LibraryImage {
+        LibraryImage {
+            prompt: prompt.to_string(),
+            batch_id: Some(batch.to_string()),
+            created_at: 1,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn same_source_is_stored_once() {
+        let batch = uuid::Uuid::new_v4().to_string();
+        let source = batch.as_bytes();
+        let first = add_image(source, b"stored-1", "jpg", image("fox", &batch)).unwrap();
+        let second = add_image(source, b"stored-2", "jpg", image("fox", &batch)).unwrap();
+        assert_eq!(first, second);
+        assert_eq!(batch_images(&batch).unwrap().len(), 1);
+        assert!(delete_image(&first).unwrap());
+    }
+
+    #[test]
+    fn like_wildcards_match_literally() {
+        let tag = uuid::Uuid::new_v4().simple().to_string();
+        let literal = format!("{} 100% done", tag);
+        let other = format!("{} 100 done", tag);
+        let a = add_image(literal.as_bytes(), b"a", "png", image(&literal, &tag)).unwrap();
+        let b = add_image(other.as_bytes(), b"b", "png", image(&other, &tag)).unwrap();
+
+        let search = |text: String| {
+            search_images(&SearchQuery {
+                text: Some(text),
+                ..Default::default()
+            })
+            .unwrap()["total"]
+                .as_i64()
+                .unwrap()
+        };
+        assert_eq!(search(format!("{} 100%", tag)), 1);
+        assert_eq!(search(format!("{}_100", tag)), 0);
+        assert_eq!(search(tag.clone()), 2);
+
+        delete_image(&a).unwrap();
+        delete_image(&b).unwrap();
+    }
+
+    #[test]
+    fn escape_like_escapes_wildcards_and_backslash() {
+        assert_eq!(escape_like(r"50%_a\b"), r"50\%\_a\\b");
+    }
+}
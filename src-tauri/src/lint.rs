@@ -0,0 +1,402 @@
+use crate::prompt_split::collapse_whitespace;
+use crate::util::re;
+use regex::Regex;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+const DEFAULT_MAX_WORDS: usize = 200;
+const DEFAULT_MAX_CHARS: usize = 2000;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LintOptions {
+    #[serde(default)]
+    pub max_words: Option<usize>,
+    #[serde(default)]
+    pub max_chars: Option<usize>,
+    #[serde(default)]
+    pub markers: Option<bool>,
+    #[serde(default)]
+    pub duplicates: Option<bool>,
+    #[serde(default)]
+    pub non_latin: Option<bool>,
+    #[serde(default)]
+    pub banned_words: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    Warning,
+    Error,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct LintIssue {
+    pub rule: &'static str,
+    pub severity: Severity,
+    pub message: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PromptLint {
+    pub index: usize,
+    pub issues: Vec<LintIssue>,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct LintReport {
+    pub ok: bool,
+    pub prompt_count: usize,
+    pub error_count: usize,
+    pub warning_count: usize,
+    pub prompts: Vec<PromptLint>,
+}
+
+fn issue(rule: &'static str, severity: Severity, message: String) -> LintIssue {
+    LintIssue {
+        rule,
+        severity,
+        message,
+    }
+}
+
+// Headers the splitter normally strips; seeing one means the prompt was pasted raw or
+// split with the wrong format.
+fn leftover_marker(prompt: &str) -> Option<&'static str> {
+    let markers: [(&str, &Regex); 9] = [
+        (
+            "IMAGE PROMPT:",
+            re!(r"(?i)\bIMAGE PROMPT\s*(?:\([^)]*\))?\s*:"),
+        ),
+        (
+            "VIDEO PROMPT:",
+            re!(r"(?i)\bVIDEO PROMPT\s*(?:\([^)]*\))?\s*:"),
+        ),
+        (
+            "SCRIPT PLACEHOLDER:",
+            re!(r"(?i)\bSCRIPT\s+PLACEHOLDER\s*:"),
+        ),
+        ("SCENE ADD-ON", re!(r"(?i)\bSCENE ADD-ON\b")),
+        ("TEXT VERSION", re!(r"(?i)\bTEXT VERSION\s*\(English\)")),
+        ("[IMAGE n]", re!(r"(?i)\[\s*IMAGE\s*\d+\s*\]")),
+        ("Motion Prompt n", re!(r"(?i)\bMotion\s+Prompt\s*\d+")),
+        (
+            "Image/Frame/Scene n:",
+            re!(r"(?im)^\s*(?:Image|Frame|Scene|Prompt)\s*\d+\s*:"),
+        ),
+        ("Prompt:", re!(r"(?im)^\s*(?:Image\s+)?Prompt\s*:")),
+    ];
+    markers
+        .into_iter()
+        .find(|(_, re)| re.is_match(prompt))
+        .map(|(label, _)| label)
+}
+
+fn script_name(c: char) -> Option<&'static str> {
+    let name = match c as u32 {
+        0x0370..=0x03FF => "Greek",
+        0x0400..=0x052F => "Cyrillic",
+        0x0590..=0x05FF => "Hebrew",
+        0x0600..=0x06FF | 0x0750..=0x077F => "Arabic",
+        0x0900..=0x097F => "Devanagari",
+        0x0E00..=0x0E7F => "Thai",
+        0x3040..=0x30FF => "Japanese",
+        0x4E00..=0x9FFF | 0x3400..=0x4DBF => "Chinese",
+        0xAC00..=0xD7AF | 0x1100..=0x11FF => "Korean",
+        _ => return None,
+    };
+    Some(name)
+}
+
+// Vietnamese and other accented Latin text is fine; only letters from other scripts count.
+fn non_latin_scripts(prompt: &str) -> Vec<&'static str> {
+    let mut scripts = Vec::new();
+    for c in prompt.chars().filter(|c| c.is_alphabetic()) {
+        let latin =
+            c.is_ascii() || matches!(c as u32, 0x00C0..=0x024F | 0x1E00..=0x1EFF | 0x0300..=0x036F);
+        if latin {
+            continue;
+        }
+        let name = script_name(c).unwrap_or("non-Latin");
+        if !scripts.contains(&name) {
+            scripts.push(name);
+        }
+    }
+    scripts
+}
+
+fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric() || c == '_'
+}
+
+// `\b` only holds next to a word character, so entries like "18+" or "@handle" get a
+// boundary on their word side only.
+fn banned_patterns(words: &[String]) -> Result<Vec<(String, Regex)>, String> {
+    let mut patterns = Vec::new();
+    for (i, word) in words.iter().enumerate() {
+        let w = word.trim();
+        if w.is_empty() {
+            return Err(format!("Banned word #{} is empty", i + 1));
+        }
+        let start = if w.starts_with(is_word_char) {
+            r"\b"
+        } else {
+            ""
+        };
+        let end = if w.ends_with(is_word_char) { r"\b" } else { "" };
+        let pattern = format!("(?i){}{}{}", start, regex::escape(w), end);
+        let re = Regex::new(&pattern)
+            .map_err(|e| format!("Banned word #{} ({}) is invalid: {}", i + 1, w, e))?;
+        patterns.push((w.to_string(), re));
+    }
+    Ok(patterns)
+}
+
+impl LintOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        banned_patterns(&self.banned_words).map(|_| ())
+    }
+}
+
+pub fn lint_prompts(prompts: &[String], options: &LintOptions) -> Result<LintReport, String> {
+    let max_words = options.max_words.unwrap_or(DEFAULT_MAX_WORDS);
+    let max_chars = options.max_chars.unwrap_or(DEFAULT_MAX_CHARS);
+    let banned = banned_patterns(&options.banned_words)?;
+    let mut first_seen: HashMap<String, usize> = HashMap::new();
+
+    let mut report = LintReport {
+        prompt_count: prompts.len(),
+        ..Default::default()
+    };
+    for (index, prompt) in prompts.iter().enumerate() {
+        let mut issues = Vec::new();
+        let text = prompt.trim();
+
+        if text.is_empty() {
+            issues.push(issue(
+                "empty",
+                Severity::Error,
+                "Prompt is empty".to_string(),
+            ));
+        } else {
+            let chars = text.chars().count();
+            let words = text.split_whitespace().count();
+            if max_chars > 0 && chars > max_chars {
+                issues.push(issue(
+                    "max_chars",
+                    Severity::Error,
+                    format!("{} characters (limit {})", chars, max_chars),
+                ));
+            }
+            if max_words > 0 && words > max_words {
+                issues.push(issue(
+                    "max_words",
+                    Severity::Warning,
+                    format!("{} words (limit {})", words, max_words),
+                ));
+            }
+
+            if options.markers.unwrap_or(true) {
+                if let Some(marker) = leftover_marker(text) {
+                    issues.push(issue(
+                        "leftover_marker",
+                        Severity::Warning,
+                        format!("Contains a leftover \"{}\" header", marker),
+                    ));
+                }
+            }
+
+            if options.duplicates.unwrap_or(true) {
+                let key = collapse_whitespace(&text.to_lowercase());
+                match first_seen.get(&key) {
+                    Some(&first) => issues.push(issue(
+                        "duplicate",
+                        Severity::Warning,
+                        format!("Same as prompt #{}", first + 1),
+                    )),
+                    None => {
+                        first_seen.insert(key, index);
+                    }
+                }
+            }
+
+            if options.non_latin.unwrap_or(true) {
+                let scripts = non_latin_scripts(text);
+                if !scripts.is_empty() {
+                    issues.push(issue(
+                        "non_latin",
+                        Severity::Warning,
+                        format!("Contains {} text", scripts.join(", ")),
+                    ));
+                }
+            }
+
+            let hits: Vec<&str> = banned
+                .iter()
+                .filter(|(_, re)| re.is_match(text))
+                .map(|(word, _)| word.as_str())
+                .collect();
+            if !hits.is_empty() {
+                issues.push(issue(
+                    "banned_word",
+                    Severity::Error,
+                    format!("Contains banned words: {}", hits.join(", ")),
+                ));
+            }
+        }
+
+        if issues.is_empty() {
+            continue;
+        }
+        for i in &issues {
+            match i.severity {
+                Severity::Error => report.error_count += 1,
+                Severity::Warning => report.warning_count += 1,
+            }
+        }
+        report.prompts.push(PromptLint { index, issues });
+    }
+    report.ok = report.error_count == 0;
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lint(prompts: &[&str], options: &LintOptions) -> LintReport {
+        let prompts: Vec<String> = prompts.iter().map(|p| p.to_string()).collect();
+        lint_prompts(&prompts, options).unwrap()
+    }
+
+    fn rules(report: &LintReport, index: usize) -> Vec<&'static str> {
+        report
+            .prompts
+            .iter()
+            .find(|p| p.index == index)
+            .map(|p| p.issues.iter().map(|i| i.rule).collect())
+            .unwrap_or_default()
+    }
+
+    #[test]
+    fn empty_prompt_is_an_error() {
+        let report = lint(&["  \n", "a red fox"], &LintOptions::default());
+        assert_eq!(rules(&report, 0), ["empty"]);
+        assert!(rules(&report, 1).is_empty());
+        assert_eq!(report.error_count, 1);
+        assert!(!report.ok);
+    }
+
+    #[test]
+    fn length_limits() {
+        let options = LintOptions {
+            max_chars: Some(10),
+            max_words: Some(2),
+            ..Default::default()
+        };
+        let report = lint(&["one two three", "one two", "abcdefghijk"], &options);
+        assert_eq!(rules(&report, 0), ["max_chars", "max_words"]);
+        assert!(rules(&report, 1).is_empty());
+        assert_eq!(rules(&report, 2), ["max_chars"]);
+        assert_eq!((report.error_count, report.warning_count), (2, 1));
+
+        let unlimited = LintOptions {
+            max_chars: Some(0),
+            max_words: Some(0),
+            ..Default::default()
+        };
+        assert!(lint(&["one two three"], &unlimited).prompts.is_empty());
+    }
+
+    #[test]
+    fn each_leftover_marker() {
+        let cases = [
+            ("IMAGE PROMPT (16:9): a fox", "IMAGE PROMPT:"),
+            ("a fox. VIDEO PROMPT: pan left", "VIDEO PROMPT:"),
+            ("Script placeholder: tbd", "SCRIPT PLACEHOLDER:"),
+            ("a fox SCENE ADD-ON more", "SCENE ADD-ON"),
+            ("TEXT VERSION (English) a fox", "TEXT VERSION"),
+            ("[IMAGE 3] a fox", "[IMAGE n]"),
+            ("a fox Motion Prompt 2", "Motion Prompt n"),
+            ("a fox\nScene 4: a den", "Image/Frame/Scene n:"),
+            ("Prompt: a fox", "Prompt:"),
+        ];
+        for (prompt, marker) in cases {
+            assert_eq!(leftover_marker(prompt), Some(marker), "{}", prompt);
+        }
+        assert_eq!(leftover_marker("a fox in the scene at 5pm"), None);
+
+        let report = lint(&["Prompt: a fox"], &LintOptions::default());
+        assert_eq!(rules(&report, 0), ["leftover_marker"]);
+        let off = LintOptions {
+            markers: Some(false),
+            ..Default::default()
+        };
+        assert!(lint(&["Prompt: a fox"], &off).prompts.is_empty());
+    }
+
+    #[test]
+    fn duplicates_ignore_case_and_spacing() {
+        let report = lint(
+            &["A red  fox", "a dog", "a RED fox "],
+            &LintOptions::default(),
+        );
+        assert!(rules(&report, 0).is_empty());
+        assert_eq!(rules(&report, 2), ["duplicate"]);
+        assert_eq!(report.prompts[0].issues[0].message, "Same as prompt #1");
+    }
+
+    #[test]
+    fn non_latin_but_not_vietnamese() {
+        let report = lint(
+            &["cô gái đẹp đứng dưới mưa", "a fox, собака", "一只狐狸 fox"],
+            &LintOptions::default(),
+        );
+        assert!(rules(&report, 0).is_empty());
+        assert_eq!(rules(&report, 1), ["non_latin"]);
+        assert_eq!(
+            report.prompts[0].issues[0].message,
+            "Contains Cyrillic text"
+        );
+        assert_eq!(report.prompts[1].issues[0].message, "Contains Chinese text");
+    }
+
+    #[test]
+    fn banned_words_with_punctuation() {
+        let options = LintOptions {
+            banned_words: vec!["nsfw".into(), "18+".into(), "@handle".into()],
+            ..Default::default()
+        };
+        let report = lint(
+            &[
+                "NSFW art",
+                "nsfwish art",
+                "rated 18+ only",
+                "ask @handle, please",
+            ],
+            &options,
+        );
+        assert_eq!(rules(&report, 0), ["banned_word"]);
+        assert!(rules(&report, 1).is_empty());
+        assert_eq!(rules(&report, 2), ["banned_word"]);
+        assert_eq!(rules(&report, 3), ["banned_word"]);
+        assert_eq!(
+            report.prompts[1].issues[0].message,
+            "Contains banned words: 18+"
+        );
+    }
+
+    #[test]
+    fn empty_banned_word_is_rejected() {
+        let options = LintOptions {
+            banned_words: vec!["nsfw".into(), "  ".into()],
+            ..Default::default()
+        };
+        assert_eq!(options.validate().unwrap_err(), "Banned word #2 is empty");
+        assert!(lint_prompts(&["a fox".to_string()], &options).is_err());
+    }
+}
@@ -2,56 +2,330 @@
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
 mod accounts;
+mod chain;
+mod contact_sheet;
+mod filename;
+mod history;
+mod image_cache;
+mod library;
+mod lint;
+mod manifest;
+mod metadata;
+mod output;
+mod phash;
+mod presets;
+mod prompt_split;
+mod reference;
+mod settings;
+mod template;
+mod thumbnails;
+mod upload_cache;
+mod util;
 mod whisk;
+mod workflows;
 
 use tauri_plugin_dialog::DialogExt;
 
+// Flat arguments: the prebuilt frontend bundle calls generate_image with these keys.
 #[tauri::command]
 async fn generate_image(
     cookies: Option<String>,
     bearer_token: Option<String>,
     prompt: String,
     aspect_ratio: Option<String>,
+    image_model: Option<String>,
     count: Option<u32>,
     save_folder: Option<String>,
     headers: Option<std::collections::HashMap<String, String>>,
     existing_workflow_id: Option<String>,
+    account_id: Option<String>,
+    account_email: Option<String>,
+    batch_id: Option<String>,
+    batch_name: Option<String>,
+    filename_template: Option<String>,
+    task_index: Option<u32>,
+    save_options: Option<output::SaveOptions>,
+    duplicate_threshold: Option<u32>,
+    references: Option<Vec<reference::RecipeReference>>,
+    style_preset: Option<String>,
+    video_prompt: Option<String>,
 ) -> Result<serde_json::Value, String> {
     println!(
-        "[generate_image] aspect_ratio={:?}, count={:?}",
-        aspect_ratio, count
+        "[generate_image] aspect_ratio={:?}, image_model={:?}, count={:?}, preset={:?}",
+        aspect_ratio, image_model, count, style_preset
     );
-    let ratio = aspect_ratio.unwrap_or_else(|| "16:9".to_string());
-    let cnt = count.unwrap_or(1);
+    let preset = match style_preset.as_deref().filter(|s| !s.trim().is_empty()) {
+        Some(name) => Some(presets::find_preset(name)?),
+        None => None,
+    };
+    let (final_prompt, ratio, cnt) =
+        presets::resolve_task(preset.as_ref(), &prompt, aspect_ratio, count);
     let c = cookies.unwrap_or_default();
     let t = bearer_token.unwrap_or_default();
-    println!("[generate_image] resolved ratio={}, count={}", ratio, cnt);
-
-    let result = whisk::generate_image_async(
-        &c,
-        &t,
-        &prompt,
-        &ratio,
-        cnt,
-        save_folder.as_deref(),
-        headers.as_ref(),
+    let app_settings = settings::load_settings();
+    let template = filename_template
+        .filter(|s| !s.trim().is_empty())
+        .or(app_settings.filename_template)
+        .unwrap_or_else(|| filename::DEFAULT_TEMPLATE.to_string());
+    let task = whisk::TaskContext {
+        prompt: final_prompt,
+        aspect_ratio: ratio,
+        image_model,
+        count: cnt,
+        save_folder,
         existing_workflow_id,
-    )
-    .await;
-
-    match result {
-        Ok(val) => Ok(val),
-        Err(e) => Err(e.to_string()),
+        batch_id,
+        batch_name,
+        task_index,
+        account_id,
+        account_email,
+        file_template: template,
+        save_options: save_options
+            .or(app_settings.save_options)
+            .unwrap_or_default(),
+        // On unless disabled in settings: batch duplicate checks and contact sheets read
+        // their images from the library.
+        library: app_settings.library_enabled.unwrap_or(true),
+        duplicate_threshold,
+        references: references.unwrap_or_default(),
+        style_preset: preset.as_ref().map(|p| p.name.clone()),
+        base_prompt: preset.as_ref().map(|_| prompt.clone()),
+        video_prompt: video_prompt
+            .map(|v| v.trim().to_string())
+            .filter(|v| !v.is_empty()),
+    };
+    println!(
+        "[generate_image] resolved ratio={}, count={}",
+        task.aspect_ratio, task.count
+    );
+    // Only feeds the thumbnail allow-list; a settings write failure must not fail the batch.
+    if let Some(folder) = task.save_folder.as_deref() {
+        if let Err(e) = settings::remember_output_folder(folder) {
+            println!("[generate_image] failed to remember output folder: {}", e);
+        }
     }
+
+    whisk::generate_image_async(&c, &t, headers.as_ref(), &task).await
 }
 
 #[tauri::command]
 async fn upload_ref_images(
     cookies: String,
-    ref_images: Vec<String>,
+    ref_images: Vec<reference::ReferenceInput>,
     existing_workflow_id: Option<String>,
+    account_id: Option<String>,
+    workflow_name: Option<String>,
+    max_edge: Option<u32>,
+) -> Result<serde_json::Value, String> {
+    whisk::upload_ref_images_async(
+        &cookies,
+        ref_images,
+        existing_workflow_id,
+        account_id.as_deref(),
+        workflow_name.as_deref(),
+        max_edge,
+    )
+    .await
+}
+
+#[tauri::command]
+fn list_capabilities() -> serde_json::Value {
+    whisk::list_capabilities()
+}
+
+#[tauri::command]
+fn read_image_metadata(path: String) -> Result<serde_json::Value, String> {
+    metadata::read_image_metadata(&path)
+}
+
+#[tauri::command]
+fn release_images(ids: Vec<String>) -> usize {
+    image_cache::release(&ids)
+}
+
+#[tauri::command]
+fn read_manifest(folder: String, batch_id: Option<String>) -> Result<serde_json::Value, String> {
+    manifest::read_manifest(&folder, batch_id.as_deref())
+}
+
+#[tauri::command]
+fn search_images(query: Option<library::SearchQuery>) -> Result<serde_json::Value, String> {
+    library::search_images(&query.unwrap_or_default())
+}
+
+#[tauri::command]
+fn get_image(id: String) -> Result<serde_json::Value, String> {
+    library::get_image(&id)
+}
+
+#[tauri::command]
+fn update_image(
+    id: String,
+    tags: Option<Vec<String>>,
+    rating: Option<u8>,
+) -> Result<serde_json::Value, String> {
+    library::update_image(&id, tags, rating)
+}
+
+#[tauri::command]
+fn export_images(ids: Vec<String>, folder: String) -> Result<serde_json::Value, String> {
+    library::export_images(&ids, &folder)
+}
+
+#[tauri::command]
+fn delete_image(id: String) -> Result<bool, String> {
+    library::delete_image(&id)
+}
+
+#[tauri::command]
+fn add_history(
+    tab: String,
+    input: String,
+    output: Vec<String>,
+) -> Result<history::HistoryEntry, String> {
+    history::add_history(&tab, &input, &output)
+}
+
+#[tauri::command]
+fn search_history(query: Option<history::HistoryQuery>) -> Result<serde_json::Value, String> {
+    history::search_history(&query.unwrap_or_default())
+}
+
+#[tauri::command]
+fn get_history_entry(id: i64) -> Result<history::HistoryEntry, String> {
+    history::get_history(id)
+}
+
+#[tauri::command]
+fn pin_history(id: i64, pinned: bool) -> Result<history::HistoryEntry, String> {
+    history::set_pinned(id, pinned)
+}
+
+#[tauri::command]
+fn delete_history(id: i64) -> Result<bool, String> {
+    history::delete_history(id)
+}
+
+#[tauri::command]
+fn clear_history(keep_pinned: Option<bool>) -> Result<usize, String> {
+    history::clear_history(keep_pinned.unwrap_or(true))
+}
+
+#[tauri::command]
+fn export_history(
+    ids: Option<Vec<i64>>,
+    path: String,
+    format: Option<String>,
+) -> Result<serde_json::Value, String> {
+    history::export_history(ids.as_deref(), &path, format.as_deref().unwrap_or("json"))
+}
+
+#[tauri::command]
+fn import_legacy_history(entries: Vec<history::LegacyHistoryEntry>) -> Result<usize, String> {
+    history::import_legacy(&entries)
+}
+
+#[tauri::command]
+async fn find_duplicates(
+    folder: Option<String>,
+    paths: Option<Vec<String>>,
+    batch_id: Option<String>,
+    threshold: Option<u32>,
+) -> Result<serde_json::Value, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        phash::find_duplicates(folder.as_deref(), paths, batch_id.as_deref(), threshold)
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn build_contact_sheet(
+    batch_id: Option<String>,
+    paths: Option<Vec<String>>,
+    columns: Option<u32>,
+    thumb_size: Option<u32>,
+    label_with: Option<contact_sheet::LabelWith>,
+    output_folder: Option<String>,
+    format: Option<String>,
 ) -> Result<serde_json::Value, String> {
-    whisk::upload_ref_images_async(&cookies, ref_images, existing_workflow_id).await
+    tauri::async_runtime::spawn_blocking(move || {
+        contact_sheet::build_contact_sheet(
+            batch_id.as_deref(),
+            paths,
+            columns,
+            thumb_size,
+            label_with,
+            output_folder.as_deref(),
+            format.as_deref(),
+        )
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+async fn get_thumbnail(path: String, size: Option<u32>) -> Result<serde_json::Value, String> {
+    tauri::async_runtime::spawn_blocking(move || {
+        thumbnails::get_thumbnail(&path, size.unwrap_or(256))
+    })
+    .await
+    .map_err(|e| e.to_string())?
+}
+
+#[tauri::command]
+fn split_prompts(
+    raw: String,
+    options: Option<prompt_split::SplitOptions>,
+) -> Result<prompt_split::SplitResult, String> {
+    prompt_split::split_prompts(&raw, &options.unwrap_or_default())
+}
+
+#[tauri::command]
+fn process_chain(raw: String, options: Option<chain::ChainOptions>) -> chain::ChainResult {
+    chain::process_chain(&raw, &options.unwrap_or_default())
+}
+
+#[tauri::command]
+fn preview_template(
+    options: template::TemplateOptions,
+) -> Result<template::TemplatePreview, String> {
+    template::preview_template(&options)
+}
+
+#[tauri::command]
+fn expand_template(
+    options: template::TemplateOptions,
+    offset: Option<usize>,
+    page_size: Option<usize>,
+) -> Result<template::TemplatePage, String> {
+    template::expand_template(&options, offset.unwrap_or(0), page_size)
+}
+
+#[tauri::command]
+fn lint_prompts(
+    prompts: Vec<String>,
+    options: Option<lint::LintOptions>,
+) -> Result<lint::LintReport, String> {
+    let options = options
+        .or_else(|| settings::load_settings().lint)
+        .unwrap_or_default();
+    lint::lint_prompts(&prompts, &options)
+}
+
+#[tauri::command]
+fn detect_format(raw: String, format: Option<String>) -> Result<prompt_split::Detection, String> {
+    prompt_split::detect_format(&raw, format.as_deref())
+}
+
+#[tauri::command]
+fn get_settings() -> Result<serde_json::Value, String> {
+    settings::get_settings()
+}
+
+#[tauri::command]
+fn update_settings(patch: serde_json::Value) -> Result<serde_json::Value, String> {
+    settings::update_settings(patch)
 }
 
 #[tauri::command]
@@ -84,7 +358,13 @@ async fn choose_folder(app: tauri::AppHandle) -> Result<Option<String>, String>
         .blocking_pick_folder();
 
     match folder {
-        Some(path) => Ok(Some(path.to_string())),
+        Some(path) => {
+            let path = path.to_string();
+            if let Err(e) = settings::remember_output_folder(&path) {
+                println!("[choose_folder] failed to remember output folder: {}", e);
+            }
+            Ok(Some(path))
+        }
         None => Ok(None),
     }
 }
@@ -134,6 +414,51 @@ async fn download_update(url: String, _app: tauri::AppHandle) -> Result<String,
     Ok(save_path.to_string_lossy().to_string())
 }
 
+#[tauri::command]
+fn list_workflows(account_id: Option<String>) -> Result<serde_json::Value, String> {
+    workflows::list_workflows(account_id.as_deref())
+}
+
+#[tauri::command]
+async fn create_workflow(
+    cookies: String,
+    account_id: Option<String>,
+    name: String,
+) -> Result<serde_json::Value, String> {
+    whisk::create_named_workflow(&cookies, account_id.as_deref(), &name).await
+}
+
+#[tauri::command]
+async fn batch_workflow(
+    cookies: String,
+    account_id: Option<String>,
+    batch_name: Option<String>,
+) -> Result<serde_json::Value, String> {
+    whisk::batch_workflow(&cookies, account_id.as_deref(), batch_name.as_deref()).await
+}
+
+#[tauri::command]
+async fn rename_workflow(
+    cookies: Option<String>,
+    workflow_id: String,
+    name: String,
+) -> Result<serde_json::Value, String> {
+    whisk::rename_workflow(&cookies.unwrap_or_default(), &workflow_id, &name).await
+}
+
+#[tauri::command]
+fn set_default_workflow(
+    account_id: String,
+    workflow_id: Option<String>,
+) -> Result<serde_json::Value, String> {
+    workflows::set_default_workflow(&account_id, workflow_id.as_deref())
+}
+
+#[tauri::command]
+fn forget_workflow(workflow_id: String) -> Result<bool, String> {
+    workflows::forget_workflow(&workflow_id)
+}
+
 #[tauri::command]
 async fn delete_ref_image(cookies: String, media_names: Vec<String>) -> Result<bool, String> {
     whisk::delete_reference_image(&cookies, media_names).await
@@ -143,9 +468,69 @@ fn main() {
     tauri::Builder::default()
         .plugin(tauri_plugin_dialog::init())
         .plugin(tauri_plugin_shell::init())
+        .register_uri_scheme_protocol(image_cache::SCHEME, |_ctx, request| {
+            let (status, mime, body) = image_cache::handle_request(request.uri().path());
+            tauri::http::Response::builder()
+                .status(status)
+                .header("Content-Type", mime)
+                .header("Access-Control-Allow-Origin", "*")
+                .body(body)
+                .unwrap_or_default()
+        })
+        .register_asynchronous_uri_scheme_protocol(
+            thumbnails::SCHEME,
+            |_ctx, request, responder| {
+                let uri = request.uri().clone();
+                tauri::async_runtime::spawn(async move {
+                    let (status, mime, body) =
+                        thumbnails::serve(uri.path().to_string(), uri.query().map(str::to_string))
+                            .await;
+                    responder.respond(
+                        tauri::http::Response::builder()
+                            .status(status)
+                            .header("Content-Type", mime)
+                            .header("Access-Control-Allow-Origin", "*")
+                            .body(body)
+                            .unwrap_or_default(),
+                    );
+                });
+            },
+        )
+        .setup(|_app| {
+            thumbnails::spawn_cleanup_worker();
+            Ok(())
+        })
         .invoke_handler(tauri::generate_handler![
             generate_image,
             upload_ref_images,
+            list_capabilities,
+            read_image_metadata,
+            read_manifest,
+            release_images,
+            search_images,
+            get_image,
+            update_image,
+            export_images,
+            delete_image,
+            add_history,
+            search_history,
+            get_history_entry,
+            pin_history,
+            delete_history,
+            clear_history,
+            export_history,
+            import_legacy_history,
+            find_duplicates,
+            build_contact_sheet,
+            get_thumbnail,
+            split_prompts,
+            detect_format,
+            process_chain,
+            preview_template,
+            expand_template,
+            lint_prompts,
+            get_settings,
+            update_settings,
             list_accounts,
             add_account,
             delete_account,
@@ -153,6 +538,12 @@ fn main() {
             check_update,
             download_update,
             delete_ref_image,
+            list_workflows,
+            create_workflow,
+            batch_workflow,
+            rename_workflow,
+            set_default_workflow,
+            forget_workflow,
         ])
         .run(tauri::generate_context!())
         .expect("error while running tauri application");
@@ -0,0 +1,109 @@
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::path::Path;
+use std::sync::Mutex;
+
+pub const MANIFEST_FILE: &str = "manifest.jsonl";
+
+static WRITE_LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestImage {
+    pub n: u32,
+    pub seed: u32,
+    #[serde(default)]
+    pub path: Option<String>,
+    #[serde(default)]
+    pub sha256: Option<String>,
+    pub status: String,
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub similar_to: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub similar_distance: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub video_prompt_path: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub encode_error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ManifestEntry {
+    #[serde(default)]
+    pub batch_id: Option<String>,
+    #[serde(default)]
+    pub task_index: Option<u32>,
+    pub prompt: String,
+    #[serde(default)]
+    pub base_prompt: Option<String>,
+    #[serde(default)]
+    pub style_preset: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub video_prompt: Option<String>,
+    #[serde(default)]
+    pub account_id: Option<String>,
+    #[serde(default)]
+    pub workflow_id: Option<String>,
+    #[serde(default)]
+    pub project_link: Option<String>,
+    #[serde(default)]
+    pub aspect_ratio: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    pub status: String,
+    #[serde(default)]
+    pub error: Option<String>,
+    pub created_at: u64,
+    #[serde(default)]
+    pub images: Vec<ManifestImage>,
+}
+
+pub fn sha256_hex(bytes: &[u8]) -> String {
+    Sha256::digest(bytes)
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+pub fn append_entry(folder: &Path, entry: &ManifestEntry) -> Result<(), String> {
+    let mut line = serde_json::to_string(entry).map_err(|e| e.to_string())?;
+    line.push('\n');
+
+    // Tasks of the same batch finish concurrently; keep each line whole.
+    let _guard = WRITE_LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    std::fs::create_dir_all(folder).map_err(|e| e.to_string())?;
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(folder.join(MANIFEST_FILE))
+        .map_err(|e| format!("Failed to open manifest: {}", e))?;
+    file.write_all(line.as_bytes())
+        .map_err(|e| format!("Failed to write manifest: {}", e))
+}
+
+pub fn load_entries(folder: &str) -> Result<Vec<ManifestEntry>, String> {
+    let path = Path::new(folder).join(MANIFEST_FILE);
+    if !path.exists() {
+        return Ok(Vec::new());
+    }
+    let content = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+    Ok(content
+        .lines()
+        .filter(|l| !l.trim().is_empty())
+        .filter_map(|l| serde_json::from_str(l).ok())
+        .collect())
+}
+
+pub fn read_manifest(folder: &str, batch_id: Option<&str>) -> Result<Value, String> {
+    let entries: Vec<ManifestEntry> = load_entries(folder)?
+        .into_iter()
+        .filter(|e| batch_id.is_none() || e.batch_id.as_deref() == batch_id)
+        .collect();
+    serde_json::to_value(&entries).map_err(|e| e.to_string())
+}
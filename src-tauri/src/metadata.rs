@@ -0,0 +1,510 @@
+use crate::reference::{self, SourceFormat};
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::io::Cursor;
+use std::path::Path;
+
+const XMP_HEADER: &[u8] = b"http://ns.adobe.com/xap/1.0/\0";
+const EXIF_HEADER: &[u8] = b"Exif\0\0";
+const XMP_NAMESPACE: &str = "https://labs.google/fx/tools/whisk/ns/1.0/";
+const SOFTWARE: &str = "AutoWhisk";
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageMetadata {
+    #[serde(default)]
+    pub prompt: String,
+    #[serde(default)]
+    pub seed: Option<u32>,
+    #[serde(default)]
+    pub aspect_ratio: Option<String>,
+    #[serde(default)]
+    pub model: Option<String>,
+    #[serde(default)]
+    pub workflow_id: Option<String>,
+    #[serde(default)]
+    pub account_email: Option<String>,
+    #[serde(default)]
+    pub timestamp: Option<u64>,
+}
+
+impl ImageMetadata {
+    fn fields(&self) -> Vec<(&'static str, String)> {
+        let mut fields = vec![("Prompt", self.prompt.clone())];
+        if let Some(seed) = self.seed {
+            fields.push(("Seed", seed.to_string()));
+        }
+        if let Some(ratio) = &self.aspect_ratio {
+            fields.push(("AspectRatio", ratio.clone()));
+        }
+        if let Some(model) = &self.model {
+            fields.push(("Model", model.clone()));
+        }
+        if let Some(wf) = &self.workflow_id {
+            fields.push(("WorkflowId", wf.clone()));
+        }
+        if let Some(email) = &self.account_email {
+            fields.push(("AccountEmail", email.clone()));
+        }
+        if let Some(ts) = self.timestamp {
+            fields.push(("Timestamp", ts.to_string()));
+        }
+        fields
+    }
+
+    fn set_field(&mut self, key: &str, value: String) {
+        match key {
+            "Prompt" => self.prompt = value,
+            "Seed" => self.seed = value.trim().parse().ok(),
+            "AspectRatio" => self.aspect_ratio = Some(value),
+            "Model" => self.model = Some(value),
+            "WorkflowId" => self.workflow_id = Some(value),
+            "AccountEmail" => self.account_email = Some(value),
+            "Timestamp" => self.timestamp = value.trim().parse().ok(),
+            _ => {}
+        }
+    }
+}
+
+pub fn encode_png(img: &DynamicImage, meta: &ImageMetadata) -> Result<Vec<u8>, String> {
+    let (color, data, width, height) = if img.color().has_alpha() {
+        let rgba = img.to_rgba8();
+        let (w, h) = rgba.dimensions();
+        (png::ColorType::Rgba, rgba.into_raw(), w, h)
+    } else {
+        let rgb = img.to_rgb8();
+        let (w, h) = rgb.dimensions();
+        (png::ColorType::Rgb, rgb.into_raw(), w, h)
+    };
+
+    let mut buf = Vec::new();
+    {
+        let mut encoder = png::Encoder::new(&mut buf, width, height);
+        encoder.set_color(color);
+        encoder.set_depth(png::BitDepth::Eight);
+        encoder
+            .add_text_chunk("Software".to_string(), SOFTWARE.to_string())
+            .map_err(|e| e.to_string())?;
+        for (key, value) in meta.fields() {
+            encoder
+                .add_itxt_chunk(key.to_string(), value)
+                .map_err(|e| e.to_string())?;
+        }
+        let mut writer = encoder.write_header().map_err(|e| e.to_string())?;
+        writer.write_image_data(&data).map_err(|e| e.to_string())?;
+        writer.finish().map_err(|e| e.to_string())?;
+    }
+    Ok(buf)
+}
+
+// XML 1.0 has no way to carry other control characters, so they are dropped. Whitespace
+// is written as character references because parsers normalize it to spaces in attributes.
+fn xml_escape(s: &str) -> String {
+    let mut out = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => out.push_str("&amp;"),
+            '<' => out.push_str("&lt;"),
+            '>' => out.push_str("&gt;"),
+            '"' => out.push_str("&quot;"),
+            '\n' => out.push_str("&#10;"),
+            '\r' => out.push_str("&#13;"),
+            '\t' => out.push_str("&#9;"),
+            c if c.is_control() => {}
+            c => out.push(c),
+        }
+    }
+    out
+}
+
+fn xml_unescape(s: &str) -> String {
+    s.replace("&quot;", "\"")
+        .replace("&lt;", "<")
+        .replace("&gt;", ">")
+        .replace("&#10;", "\n")
+        .replace("&#13;", "\r")
+        .replace("&#9;", "\t")
+        .replace("&amp;", "&")
+}
+
+fn build_xmp_packet(meta: &ImageMetadata) -> String {
+    let attrs: String = meta
+        .fields()
+        .into_iter()
+        .map(|(k, v)| format!("\n    whisk:{}=\"{}\"", k, xml_escape(&v)))
+        .collect();
+    format!(
+        "<?xpacket begin=\"\u{feff}\" id=\"W5M0MpCehiHzreSzNTczkc9d\"?>\n\
+<x:xmpmeta xmlns:x=\"adobe:ns:meta/\">\n\
+ <rdf:RDF xmlns:rdf=\"http://www.w3.org/1999/02/22-rdf-syntax-ns#\">\n\
+  <rdf:Description rdf:about=\"\"\n    xmlns:xmp=\"http://ns.adobe.com/xap/1.0/\"\n    xmlns:whisk=\"{}\"\n    xmp:CreatorTool=\"{}\"{}/>\n\
+ </rdf:RDF>\n\
+</x:xmpmeta>\n\
+<?xpacket end=\"w\"?>",
+        XMP_NAMESPACE, SOFTWARE, attrs
+    )
+}
+
+fn parse_xmp_packet(xmp: &str) -> ImageMetadata {
+    let mut meta = ImageMetadata::default();
+    let mut rest = xmp;
+    while let Some(pos) = rest.find("whisk:") {
+        rest = &rest[pos + "whisk:".len()..];
+        let Some(eq) = rest.find("=\"") else {
+            break;
+        };
+        let key = &rest[..eq];
+        if key.is_empty() || !key.chars().all(|c| c.is_ascii_alphanumeric()) {
+            continue;
+        }
+        let value_start = &rest[eq + 2..];
+        let Some(end) = value_start.find('"') else {
+            break;
+        };
+        meta.set_field(key, xml_unescape(&value_start[..end]));
+        rest = &value_start[end..];
+    }
+    meta
+}
+
+// Marker segments between SOI and start of scan as (marker, whole segment), plus the
+// offset where the scan data starts.
+fn jpeg_segments(bytes: &[u8]) -> (Vec<(u8, &[u8])>, usize) {
+    let mut segments = Vec::new();
+    let mut pos = 2;
+    while pos + 4 <= bytes.len() && bytes[pos] == 0xFF {
+        let marker = bytes[pos + 1];
+        if marker == 0xDA || marker == 0xD9 {
+            break;
+        }
+        let len = u16::from_be_bytes([bytes[pos + 2], bytes[pos + 3]]) as usize;
+        if len < 2 {
+            break;
+        }
+        let end = (pos + 2 + len).min(bytes.len());
+        segments.push((marker, &bytes[pos..end]));
+        pos = end;
+    }
+    (segments, pos)
+}
+
+fn is_app1(marker: u8, segment: &[u8], header: &[u8]) -> bool {
+    marker == 0xE1 && segment[4..].starts_with(header)
+}
+
+pub fn embed_xmp_jpeg(bytes: &[u8], meta: &ImageMetadata) -> Result<Vec<u8>, String> {
+    if reference::sniff_format(bytes) != Some(SourceFormat::Jpeg) {
+        return Err("Not a JPEG stream".to_string());
+    }
+
+    let packet = build_xmp_packet(meta);
+    let payload_len = XMP_HEADER.len() + packet.len() + 2;
+    if payload_len > u16::MAX as usize {
+        return Err("XMP packet too large for a JPEG APP1 segment".to_string());
+    }
+    let mut xmp = vec![0xFF, 0xE1];
+    xmp.extend_from_slice(&(payload_len as u16).to_be_bytes());
+    xmp.extend_from_slice(XMP_HEADER);
+    xmp.extend_from_slice(packet.as_bytes());
+
+    // JFIF APP0 and EXIF APP1 stay first, as some readers expect them there. A packet
+    // from an earlier embed is dropped so the file only ever carries one.
+    let (segments, scan_start) = jpeg_segments(bytes);
+    let mut out = Vec::with_capacity(bytes.len() + xmp.len());
+    out.extend_from_slice(&bytes[..2]);
+    let mut inserted = false;
+    for (marker, segment) in segments {
+        if is_app1(marker, segment, XMP_HEADER) {
+            continue;
+        }
+        if !inserted && marker != 0xE0 && !is_app1(marker, segment, EXIF_HEADER) {
+            out.extend_from_slice(&xmp);
+            inserted = true;
+        }
+        out.extend_from_slice(segment);
+    }
+    if !inserted {
+        out.extend_from_slice(&xmp);
+    }
+    out.extend_from_slice(&bytes[scan_start..]);
+    Ok(out)
+}
+
+fn read_jpeg_xmp(bytes: &[u8]) -> Option<String> {
+    jpeg_segments(bytes)
+        .0
+        .into_iter()
+        .find(|(marker, segment)| is_app1(*marker, segment, XMP_HEADER))
+        .map(|(_, segment)| String::from_utf8_lossy(&segment[4 + XMP_HEADER.len()..]).to_string())
+}
+
+fn riff_chunk(tag: &[u8; 4], payload: &[u8]) -> Vec<u8> {
+    let mut chunk = Vec::with_capacity(payload.len() + 9);
+    chunk.extend_from_slice(tag);
+    chunk.extend_from_slice(&(payload.len() as u32).to_le_bytes());
+    chunk.extend_from_slice(payload);
+    if payload.len() % 2 == 1 {
+        chunk.push(0);
+    }
+    chunk
+}
+
+fn webp_chunks(bytes: &[u8]) -> Vec<([u8; 4], &[u8])> {
+    let mut chunks = Vec::new();
+    let mut pos = 12;
+    while pos + 8 <= bytes.len() {
+        let tag = [bytes[pos], bytes[pos + 1], bytes[pos + 2], bytes[pos + 3]];
+        let len = u32::from_le_bytes([
+            bytes[pos + 4],
+            bytes[pos + 5],
+            bytes[pos + 6],
+            bytes[pos + 7],
+        ]) as usize;
+        let end = (pos + 8 + len).min(bytes.len());
+        chunks.push((tag, &bytes[pos + 8..end]));
+        pos = end + (len % 2);
+    }
+    chunks
+}
+
+pub fn embed_xmp_webp(
+    bytes: &[u8],
+    width: u32,
+    height: u32,
+    has_alpha: bool,
+    meta: &ImageMetadata,
+) -> Result<Vec<u8>, String> {
+    if reference::sniff_format(bytes) != Some(SourceFormat::Webp) {
+        return Err("Not a WebP stream".to_string());
+    }
+
+    // XMP requires the extended (VP8X) layout; simple files only carry one image chunk.
+    let mut flags = 0x04u8;
+    if has_alpha {
+        flags |= 0x10;
+    }
+    let mut vp8x = vec![flags, 0, 0, 0];
+    vp8x.extend_from_slice(&(width.saturating_sub(1)).to_le_bytes()[..3]);
+    vp8x.extend_from_slice(&(height.saturating_sub(1)).to_le_bytes()[..3]);
+
+    let mut body = b"WEBP".to_vec();
+    body.extend(riff_chunk(b"VP8X", &vp8x));
+    for (tag, payload) in webp_chunks(bytes) {
+        if &tag == b"VP8X" || &tag == b"XMP " {
+            continue;
+        }
+        body.extend(riff_chunk(&tag, payload));
+    }
+    body.extend(riff_chunk(b"XMP ", build_xmp_packet(meta).as_bytes()));
+
+    let mut out = b"RIFF".to_vec();
+    out.extend_from_slice(&(body.len() as u32).to_le_bytes());
+    out.extend(body);
+    Ok(out)
+}
+
+fn read_webp_xmp(bytes: &[u8]) -> Option<String> {
+    webp_chunks(bytes)
+        .into_iter()
+        .find(|(tag, _)| tag == b"XMP ")
+        .map(|(_, payload)| String::from_utf8_lossy(payload).to_string())
+}
+
+fn read_png_metadata(bytes: &[u8]) -> Result<ImageMetadata, String> {
+    let decoder = png::Decoder::new(Cursor::new(bytes));
+    let reader = decoder.read_info().map_err(|e| e.to_string())?;
+    let info = reader.info();
+
+    let mut meta = ImageMetadata::default();
+    for chunk in &info.uncompressed_latin1_text {
+        meta.set_field(&chunk.keyword, chunk.text.clone());
+    }
+    for chunk in &info.utf8_text {
+        if let Ok(text) = chunk.get_text() {
+            meta.set_field(&chunk.keyword, text);
+        }
+    }
+    Ok(meta)
+}
+
+pub fn read_image_metadata(path: &str) -> Result<Value, String> {
+    let p = Path::new(path);
+    if !p.exists() {
+        return Err(format!("File not found: {}", path));
+    }
+    let bytes = std::fs::read(p).map_err(|e| e.to_string())?;
+
+    let (format, meta) = match reference::sniff_format(&bytes) {
+        Some(SourceFormat::Png) => ("png", Some(read_png_metadata(&bytes)?)),
+        Some(SourceFormat::Jpeg) => ("jpeg", read_jpeg_xmp(&bytes).map(|x| parse_xmp_packet(&x))),
+        Some(SourceFormat::Webp) => ("webp", read_webp_xmp(&bytes).map(|x| parse_xmp_packet(&x))),
+        _ => return Err("Unsupported image format (expected PNG, JPEG or WebP)".to_string()),
+    };
+
+    let found = meta
+        .as_ref()
+        .map(|m| !m.prompt.is_empty() || m.seed.is_some())
+        .unwrap_or(false);
+
+    Ok(serde_json::json!({
+        "path": path,
+        "format": format,
+        "found": found,
+        "metadata": meta
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::output::{self, OutputFormat, SaveOptions};
+    use image::ImageFormat;
+
+    fn sample_meta() -> ImageMetadata {
+        ImageMetadata {
+            prompt: "a \"red\" fox <on> a hill & sky\nsecond line".to_string(),
+            seed: Some(123456),
+            aspect_ratio: Some("16:9".to_string()),
+            model: Some("IMAGEN_3_5".to_string()),
+            workflow_id: Some("wf-1".to_string()),
+            account_email: Some("a@example.com".to_string()),
+            timestamp: Some(1_700_000_000),
+        }
+    }
+
+    fn sample_image(format: ImageFormat) -> Vec<u8> {
+        let img = DynamicImage::new_rgb8(8, 6);
+        let mut buf = Cursor::new(Vec::new());
+        img.write_to(&mut buf, format).unwrap();
+        buf.into_inner()
+    }
+
+    fn encode_as(format: OutputFormat) -> Vec<u8> {
+        let opts = SaveOptions {
+            format,
+            ..Default::default()
+        };
+        output::encode(sample_image(ImageFormat::Png), &opts, &sample_meta())
+            .unwrap()
+            .bytes
+    }
+
+    fn read_back(bytes: &[u8], ext: &str) -> Value {
+        let path =
+            std::env::temp_dir().join(format!("autowhisk_meta_{}.{}", uuid::Uuid::new_v4(), ext));
+        std::fs::write(&path, bytes).unwrap();
+        let result = read_image_metadata(&path.to_string_lossy());
+        let _ = std::fs::remove_file(&path);
+        result.unwrap()
+    }
+
+    fn assert_round_trip(bytes: &[u8], ext: &str, format: &str) {
+        let value = read_back(bytes, ext);
+        assert_eq!(value["format"], format);
+        assert_eq!(value["found"], true);
+        let meta: ImageMetadata = serde_json::from_value(value["metadata"].clone()).unwrap();
+        let expected = sample_meta();
+        assert_eq!(meta.prompt, expected.prompt);
+        assert_eq!(meta.seed, expected.seed);
+        assert_eq!(meta.aspect_ratio, expected.aspect_ratio);
+        assert_eq!(meta.model, expected.model);
+        assert_eq!(meta.workflow_id, expected.workflow_id);
+        assert_eq!(meta.account_email, expected.account_email);
+        assert_eq!(meta.timestamp, expected.timestamp);
+        image::load_from_memory(bytes).expect("embedded file still decodes");
+    }
+
+    fn exif_segment() -> Vec<u8> {
+        let mut payload = EXIF_HEADER.to_vec();
+        payload.extend_from_slice(b"II*\0\x08\0\0\0\0\0");
+        let mut segment = vec![0xFF, 0xE1];
+        segment.extend_from_slice(&((payload.len() + 2) as u16).to_be_bytes());
+        segment.extend(payload);
+        segment
+    }
+
+    #[test]
+    fn png_round_trip() {
+        assert_round_trip(&encode_as(OutputFormat::Png), "png", "png");
+    }
+
+    #[test]
+    fn jpeg_round_trip() {
+        assert_round_trip(&encode_as(OutputFormat::Jpeg), "jpg", "jpeg");
+    }
+
+    #[test]
+    fn webp_round_trip() {
+        let bytes = encode_as(OutputFormat::Webp);
+        let tags: Vec<[u8; 4]> = webp_chunks(&bytes).into_iter().map(|(t, _)| t).collect();
+        assert_eq!(tags.first(), Some(b"VP8X"));
+        assert!(tags.contains(b"XMP "));
+        let riff_len = u32::from_le_bytes([bytes[4], bytes[5], bytes[6], bytes[7]]) as usize;
+        assert_eq!(riff_len + 8, bytes.len());
+        assert_round_trip(&bytes, "webp", "webp");
+    }
+
+    #[test]
+    fn jpeg_with_exif_keeps_exif_first() {
+        let plain = sample_image(ImageFormat::Jpeg);
+        let exif = exif_segment();
+        let mut source = plain[..2].to_vec();
+        source.extend_from_slice(&exif);
+        source.extend_from_slice(&plain[2..]);
+
+        let embedded = embed_xmp_jpeg(&source, &sample_meta()).unwrap();
+        let (segments, _) = jpeg_segments(&embedded);
+        let exif_at = segments
+            .iter()
+            .position(|(m, s)| is_app1(*m, s, EXIF_HEADER))
+            .expect("EXIF segment kept");
+        let xmp_at = segments
+            .iter()
+            .position(|(m, s)| is_app1(*m, s, XMP_HEADER))
+            .expect("XMP segment added");
+        assert!(exif_at < xmp_at);
+        assert_eq!(segments[exif_at].1, &exif[..]);
+        assert_round_trip(&embedded, "jpg", "jpeg");
+    }
+
+    #[test]
+    fn jpeg_embed_replaces_previous_packet() {
+        let first = embed_xmp_jpeg(&sample_image(ImageFormat::Jpeg), &sample_meta()).unwrap();
+        let other = ImageMetadata {
+            prompt: "replaced".to_string(),
+            ..Default::default()
+        };
+        let second = embed_xmp_jpeg(&first, &other).unwrap();
+        let packets = jpeg_segments(&second)
+            .0
+            .iter()
+            .filter(|(m, s)| is_app1(*m, s, XMP_HEADER))
+            .count();
+        assert_eq!(packets, 1);
+        let xmp = read_jpeg_xmp(&second).unwrap();
+        assert_eq!(parse_xmp_packet(&xmp).prompt, "replaced");
+    }
+
+    #[test]
+    fn xmp_drops_control_characters_and_keeps_whitespace() {
+        let meta = ImageMetadata {
+            prompt: "line one\r\nline\ttwo\u{1}\u{1b}[0m\u{7f}".to_string(),
+            ..sample_meta()
+        };
+        let packet = build_xmp_packet(&meta);
+        assert!(packet.contains("line one&#13;&#10;line&#9;two[0m\""));
+        assert!(!packet.contains(['\u{1}', '\u{1b}', '\u{7f}', '\r', '\t']));
+        assert_eq!(parse_xmp_packet(&packet).prompt, "line one\r\nline\ttwo[0m");
+    }
+
+    #[test]
+    fn unsupported_format_is_rejected() {
+        let path =
+            std::env::temp_dir().join(format!("autowhisk_meta_{}.gif", uuid::Uuid::new_v4()));
+        std::fs::write(&path, b"GIF89a....").unwrap();
+        let result = read_image_metadata(&path.to_string_lossy());
+        let _ = std::fs::remove_file(&path);
+        assert!(result.is_err());
+    }
+}
@@ -0,0 +1,177 @@
+use crate::metadata::{self, ImageMetadata};
+use crate::reference::{self, SourceFormat};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::DynamicImage;
+use serde::{Deserialize, Serialize};
+
+const DEFAULT_QUALITY: u8 = 90;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum OutputFormat {
+    Original,
+    #[default]
+    Png,
+    Jpeg,
+    Webp,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SaveOptions {
+    #[serde(default)]
+    pub format: OutputFormat,
+    #[serde(default)]
+    pub quality: Option<u8>,
+    #[serde(default)]
+    pub lossless: Option<bool>,
+    #[serde(default)]
+    pub width: Option<u32>,
+    #[serde(default)]
+    pub height: Option<u32>,
+}
+
+impl SaveOptions {
+    pub fn validate(&self) -> Result<(), String> {
+        if let Some(q) = self.quality {
+            if !(1..=100).contains(&q) {
+                return Err(format!("Quality must be between 1 and 100, got {}", q));
+            }
+        }
+        if self.width == Some(0) || self.height == Some(0) {
+            return Err("Resize width/height must be greater than 0".to_string());
+        }
+        Ok(())
+    }
+
+    fn wants_resize(&self) -> bool {
+        self.width.is_some() || self.height.is_some()
+    }
+}
+
+pub struct EncodedImage {
+    pub bytes: Vec<u8>,
+    pub ext: &'static str,
+    pub width: u32,
+    pub height: u32,
+}
+
+pub fn detect_ext(bytes: &[u8]) -> Option<&'static str> {
+    match reference::sniff_format(bytes)? {
+        SourceFormat::Png => Some("png"),
+        SourceFormat::Jpeg => Some("jpg"),
+        SourceFormat::Webp => Some("webp"),
+        _ => None,
+    }
+}
+
+fn resize(img: DynamicImage, width: Option<u32>, height: Option<u32>) -> DynamicImage {
+    let (w, h) = (img.width(), img.height());
+    match (width, height) {
+        (Some(tw), Some(th)) => img.resize(tw, th, FilterType::Lanczos3),
+        (Some(tw), None) => {
+            let th = ((h as u64 * tw as u64) / w.max(1) as u64).max(1) as u32;
+            img.resize_exact(tw, th, FilterType::Lanczos3)
+        }
+        (None, Some(th)) => {
+            let tw = ((w as u64 * th as u64) / h.max(1) as u64).max(1) as u32;
+            img.resize_exact(tw, th, FilterType::Lanczos3)
+        }
+        (None, None) => img,
+    }
+}
+
+fn encode_jpeg(img: &DynamicImage, quality: u8) -> Result<Vec<u8>, String> {
+    let mut buf = Vec::new();
+    let encoder = JpegEncoder::new_with_quality(&mut buf, quality);
+    img.to_rgb8()
+        .write_with_encoder(encoder)
+        .map_err(|e| format!("JPEG encode: {}", e))?;
+    Ok(buf)
+}
+
+fn encode_webp(img: &DynamicImage, lossless: bool, quality: u8) -> Vec<u8> {
+    let (w, h) = (img.width(), img.height());
+    let encode = |enc: webp::Encoder| {
+        if lossless {
+            enc.encode_lossless().to_vec()
+        } else {
+            enc.encode(quality as f32).to_vec()
+        }
+    };
+    if img.color().has_alpha() {
+        let rgba = img.to_rgba8();
+        encode(webp::Encoder::from_rgba(rgba.as_raw(), w, h))
+    } else {
+        let rgb = img.to_rgb8();
+        encode(webp::Encoder::from_rgb(rgb.as_raw(), w, h))
+    }
+}
+
+pub fn encode(
+    source: Vec<u8>,
+    opts: &SaveOptions,
+    meta: &ImageMetadata,
+) -> Result<EncodedImage, String> {
+    let source_ext = detect_ext(&source);
+
+    let mut format = opts.format;
+    if format == OutputFormat::Original && opts.wants_resize() {
+        // Resizing means re-encoding, so stay as close to the source format as possible.
+        format = match source_ext {
+            Some("png") => OutputFormat::Png,
+            Some("webp") => OutputFormat::Webp,
+            _ => OutputFormat::Jpeg,
+        };
+    }
+
+    if format == OutputFormat::Original {
+        let (width, height) = image::ImageReader::new(std::io::Cursor::new(&source))
+            .with_guessed_format()
+            .ok()
+            .and_then(|r| r.into_dimensions().ok())
+            .unwrap_or((0, 0));
+        let ext = source_ext.unwrap_or("jpg");
+        let bytes = match ext {
+            "jpg" => metadata::embed_xmp_jpeg(&source, meta).unwrap_or(source),
+            _ => source,
+        };
+        return Ok(EncodedImage {
+            bytes,
+            ext,
+            width,
+            height,
+        });
+    }
+
+    let img = image::load_from_memory(&source).map_err(|e| format!("Decode error: {}", e))?;
+    let img = resize(img, opts.width, opts.height);
+    let (width, height) = (img.width(), img.height());
+    let quality = opts.quality.unwrap_or(DEFAULT_QUALITY);
+
+    let (bytes, ext) = match format {
+        OutputFormat::Png => (metadata::encode_png(&img, meta)?, "png"),
+        OutputFormat::Jpeg => {
+            let jpeg = encode_jpeg(&img, quality)?;
+            (metadata::embed_xmp_jpeg(&jpeg, meta)?, "jpg")
+        }
+        OutputFormat::Webp => {
+            let lossless = opts.lossless.unwrap_or(opts.quality.is_none());
+            let webp = encode_webp(&img, lossless, quality);
+            let has_alpha = img.color().has_alpha();
+            (
+                metadata::embed_xmp_webp(&webp, width, height, has_alpha, meta)?,
+                "webp",
+            )
+        }
+        OutputFormat::Original => unreachable!(),
+    };
+
+    Ok(EncodedImage {
+        bytes,
+        ext,
+        width,
+        height,
+    })
+}
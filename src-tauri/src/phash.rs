@@ -0,0 +1,295 @@
+use image::imageops::FilterType;
+use image::DynamicImage;
+use serde_json::{json, Value};
+use std::collections::{HashMap, VecDeque};
+use std::path::Path;
+use std::sync::{Mutex, OnceLock};
+
+pub const DEFAULT_THRESHOLD: u32 = 8;
+const MAX_TRACKED_BATCHES: usize = 32;
+const IMAGE_EXTS: [&str; 4] = ["png", "jpg", "jpeg", "webp"];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ImageHashes {
+    pub dhash: u64,
+    pub phash: u64,
+}
+
+impl ImageHashes {
+    // The larger of the two Hamming distances: an image only counts as a near-duplicate
+    // when both the frequency (phash) and gradient (dhash) fingerprints agree, which cuts
+    // false matches between flat, similarly lit images.
+    pub fn distance(&self, other: &ImageHashes) -> u32 {
+        let phash = (self.phash ^ other.phash).count_ones();
+        let dhash = (self.dhash ^ other.dhash).count_ones();
+        phash.max(dhash)
+    }
+}
+
+pub fn to_hex(hash: u64) -> String {
+    format!("{:016x}", hash)
+}
+
+pub fn dhash(img: &DynamicImage) -> u64 {
+    let small = img.resize_exact(9, 8, FilterType::Triangle).to_luma8();
+    let mut hash = 0u64;
+    for y in 0..8 {
+        for x in 0..8 {
+            let left = small.get_pixel(x, y)[0];
+            let right = small.get_pixel(x + 1, y)[0];
+            hash = (hash << 1) | (left > right) as u64;
+        }
+    }
+    hash
+}
+
+pub fn phash(img: &DynamicImage) -> u64 {
+    const N: usize = 32;
+    let small = img
+        .resize_exact(N as u32, N as u32, FilterType::Triangle)
+        .to_luma8();
+    let pixels: Vec<f64> = small.pixels().map(|p| p[0] as f64).collect();
+
+    let cos: Vec<f64> = (0..8 * N)
+        .map(|i| {
+            let (u, x) = (i / N, i % N);
+            (std::f64::consts::PI * (2 * x + 1) as f64 * u as f64 / (2 * N) as f64).cos()
+        })
+        .collect();
+
+    // Only the low 8x8 frequencies are needed, so the DCT is computed separably for those.
+    let mut rows = vec![0f64; N * 8];
+    for y in 0..N {
+        for u in 0..8 {
+            rows[y * 8 + u] = (0..N).map(|x| pixels[y * N + x] * cos[u * N + x]).sum();
+        }
+    }
+    let mut coeffs = [0f64; 64];
+    for v in 0..8 {
+        for u in 0..8 {
+            coeffs[v * 8 + u] = (0..N).map(|y| rows[y * 8 + u] * cos[v * N + y]).sum();
+        }
+    }
+
+    let mut ac: Vec<f64> = coeffs[1..].to_vec();
+    ac.sort_by(|a, b| a.partial_cmp(b).unwrap_or(std::cmp::Ordering::Equal));
+    let median = ac[ac.len() / 2];
+
+    coeffs
+        .iter()
+        .fold(0u64, |hash, &c| (hash << 1) | (c > median) as u64)
+}
+
+pub fn hash_image(img: &DynamicImage) -> ImageHashes {
+    ImageHashes {
+        dhash: dhash(img),
+        phash: phash(img),
+    }
+}
+
+pub fn hash_bytes(bytes: &[u8]) -> Result<ImageHashes, String> {
+    let img = image::load_from_memory(bytes).map_err(|e| e.to_string())?;
+    Ok(hash_image(&img))
+}
+
+pub fn hash_file(path: &Path) -> Result<ImageHashes, String> {
+    let img = image::open(path).map_err(|e| e.to_string())?;
+    Ok(hash_image(&img))
+}
+
+#[derive(Default)]
+struct BatchRegistry {
+    batches: HashMap<String, Vec<(String, ImageHashes)>>,
+    order: VecDeque<String>,
+}
+
+fn registry() -> &'static Mutex<BatchRegistry> {
+    static REGISTRY: OnceLock<Mutex<BatchRegistry>> = OnceLock::new();
+    REGISTRY.get_or_init(|| Mutex::new(BatchRegistry::default()))
+}
+
+// Records the image under its batch and returns the closest earlier image within the threshold.
+pub fn check_batch_duplicate(
+    batch_id: &str,
+    label: &str,
+    hashes: ImageHashes,
+    threshold: u32,
+) -> Option<(String, u32)> {
+    let mut reg = registry().lock().unwrap_or_else(|e| e.into_inner());
+    if !reg.batches.contains_key(batch_id) {
+        reg.order.push_back(batch_id.to_string());
+        while reg.order.len() > MAX_TRACKED_BATCHES {
+            if let Some(old) = reg.order.pop_front() {
+                reg.batches.remove(&old);
+            }
+        }
+    }
+
+    let seen = reg.batches.entry(batch_id.to_string()).or_default();
+    let closest = seen
+        .iter()
+        .map(|(other, h)| (other.clone(), hashes.distance(h)))
+        .filter(|(_, d)| *d <= threshold)
+        .min_by_key(|(_, d)| *d);
+    seen.push((label.to_string(), hashes));
+    closest
+}
+
+fn list_folder_images(folder: &str) -> Result<Vec<String>, String> {
+    let entries =
+        std::fs::read_dir(folder).map_err(|e| format!("Cannot read {}: {}", folder, e))?;
+    let mut paths: Vec<String> = entries
+        .filter_map(|e| e.ok())
+        .map(|e| e.path())
+        .filter(|p| {
+            p.extension()
+                .and_then(|e| e.to_str())
+                .map(|e| IMAGE_EXTS.contains(&e.to_lowercase().as_str()))
+                .unwrap_or(false)
+        })
+        .map(|p| p.to_string_lossy().to_string())
+        .collect();
+    paths.sort();
+    Ok(paths)
+}
+
+fn find_root(parent: &mut [usize], i: usize) -> usize {
+    let mut root = i;
+    while parent[root] != root {
+        root = parent[root];
+    }
+    let mut cur = i;
+    while parent[cur] != root {
+        let next = parent[cur];
+        parent[cur] = root;
+        cur = next;
+    }
+    root
+}
+
+fn group_duplicates(paths: Vec<String>, threshold: u32) -> Value {
+    let mut hashed: Vec<(String, ImageHashes)> = Vec::new();
+    let mut skipped = Vec::new();
+    for path in paths {
+        match hash_file(Path::new(&path)) {
+            Ok(h) => hashed.push((path, h)),
+            Err(e) => skipped.push(json!({ "path": path, "error": e })),
+        }
+    }
+
+    let mut parent: Vec<usize> = (0..hashed.len()).collect();
+    for i in 0..hashed.len() {
+        for j in (i + 1)..hashed.len() {
+            if hashed[i].1.distance(&hashed[j].1) <= threshold {
+                let (a, b) = (find_root(&mut parent, i), find_root(&mut parent, j));
+                if a != b {
+                    parent[b] = a;
+                }
+            }
+        }
+    }
+
+    let mut groups: HashMap<usize, Vec<usize>> = HashMap::new();
+    for i in 0..hashed.len() {
+        let root = find_root(&mut parent, i);
+        groups.entry(root).or_default().push(i);
+    }
+
+    let mut result: Vec<Value> = groups
+        .into_values()
+        .filter(|members| members.len() > 1)
+        .map(|members| {
+            let keep = members[0];
+            let images: Vec<Value> = members
+                .iter()
+                .map(|&m| {
+                    json!({
+                        "path": hashed[m].0,
+                        "phash": to_hex(hashed[m].1.phash),
+                        "dhash": to_hex(hashed[m].1.dhash),
+                        "distance": hashed[keep].1.distance(&hashed[m].1)
+                    })
+                })
+                .collect();
+            json!({ "images": images })
+        })
+        .collect();
+    result.sort_by_key(|g| g["images"][0]["path"].as_str().unwrap_or("").to_string());
+
+    json!({
+        "scanned": hashed.len(),
+        "threshold": threshold,
+        "groups": result,
+        "skipped": skipped
+    })
+}
+
+pub fn find_duplicates(
+    folder: Option<&str>,
+    paths: Option<Vec<String>>,
+    batch_id: Option<&str>,
+    threshold: Option<u32>,
+) -> Result<Value, String> {
+    let mut all = paths.unwrap_or_default();
+    if let Some(folder) = folder {
+        all.extend(list_folder_images(folder)?);
+    }
+    if let Some(batch) = batch_id {
+        all.extend(crate::library::batch_paths(batch)?);
+    }
+    let mut seen = std::collections::HashSet::new();
+    all.retain(|p| seen.insert(p.clone()));
+
+    if all.is_empty() {
+        return Err("No images to compare".to_string());
+    }
+    Ok(group_duplicates(
+        all,
+        threshold.unwrap_or(DEFAULT_THRESHOLD),
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage};
+
+    // 8x8 blocks of pseudo-random grey levels, so resizing keeps the structure.
+    fn blocks(width: u32, height: u32, seed: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_fn(width, height, |x, y| {
+            let (bx, by) = (x * 8 / width, y * 8 / height);
+            let v = ((bx * 73 + by * 151 + seed * 97).wrapping_mul(2654435761) >> 24) as u8;
+            Rgb([v, v, v])
+        }))
+    }
+
+    #[test]
+    fn resized_copy_is_close() {
+        let a = hash_image(&blocks(256, 192, 1));
+        let b = hash_image(&blocks(128, 96, 1));
+        assert_eq!(a.distance(&a), 0);
+        assert!(a.distance(&b) <= DEFAULT_THRESHOLD);
+    }
+
+    #[test]
+    fn different_image_is_far() {
+        let a = hash_image(&blocks(256, 192, 1));
+        let b = hash_image(&blocks(256, 192, 2));
+        assert!(a.distance(&b) > DEFAULT_THRESHOLD);
+    }
+
+    #[test]
+    fn distance_uses_both_hashes() {
+        let base = ImageHashes { dhash: 0, phash: 0 };
+        let dhash_only = ImageHashes {
+            dhash: 0xFFFF,
+            phash: 0,
+        };
+        let phash_only = ImageHashes {
+            dhash: 0,
+            phash: 0xFF,
+        };
+        assert_eq!(base.distance(&dhash_only), 16);
+        assert_eq!(base.distance(&phash_only), 8);
+    }
+}
@@ -0,0 +1,216 @@
+use crate::settings::load_settings;
+use crate::whisk::AspectRatio;
+use serde::{Deserialize, Serialize};
+
+const MAX_NAME_LEN: usize = 60;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct StylePreset {
+    pub name: String,
+    #[serde(default)]
+    pub prefix: Option<String>,
+    #[serde(default)]
+    pub suffix: Option<String>,
+    #[serde(default)]
+    pub aspect_ratio: Option<String>,
+    #[serde(default)]
+    pub count: Option<u32>,
+    #[serde(default)]
+    pub negative: Vec<String>,
+}
+
+fn non_empty(value: &Option<String>) -> Option<&str> {
+    value.as_deref().map(str::trim).filter(|s| !s.is_empty())
+}
+
+// Appends ", tail" unless the prompt already carries it, so re-running a batch whose
+// prompts were pasted with the tail does not double it.
+fn append_tail(prompt: &str, tail: &str) -> String {
+    if prompt.to_lowercase().contains(&tail.to_lowercase()) {
+        return prompt.to_string();
+    }
+    let head = prompt.trim_end().trim_end_matches(',').trim_end();
+    if head.is_empty() {
+        tail.to_string()
+    } else {
+        format!("{}, {}", head, tail)
+    }
+}
+
+impl StylePreset {
+    pub fn validate(&self) -> Result<(), String> {
+        let name = self.name.trim();
+        if name.is_empty() {
+            return Err("Style preset name is empty".to_string());
+        }
+        if name.chars().count() > MAX_NAME_LEN {
+            return Err(format!(
+                "Style preset name is longer than {} characters",
+                MAX_NAME_LEN
+            ));
+        }
+        if let Some(ratio) = non_empty(&self.aspect_ratio) {
+            AspectRatio::parse(ratio).map_err(|e| format!("Preset '{}': {}", name, e))?;
+        }
+        if self.count == Some(0) {
+            return Err(format!("Preset '{}': count must be at least 1", name));
+        }
+        Ok(())
+    }
+
+    // Whisk has no negative prompt field, so negatives become "no X" phrases at the end.
+    pub fn negative_phrase(&self) -> Option<String> {
+        let phrases: Vec<String> = self
+            .negative
+            .iter()
+            .map(|p| p.trim())
+            .filter(|p| !p.is_empty())
+            .map(|p| {
+                if p.to_lowercase().starts_with("no ") {
+                    p.to_string()
+                } else {
+                    format!("no {}", p)
+                }
+            })
+            .collect();
+        if phrases.is_empty() {
+            None
+        } else {
+            Some(phrases.join(", "))
+        }
+    }
+
+    pub fn apply(&self, prompt: &str) -> String {
+        let mut out = prompt.trim().to_string();
+        if let Some(prefix) = non_empty(&self.prefix) {
+            if !out.to_lowercase().starts_with(&prefix.to_lowercase()) {
+                out = format!("{} {}", prefix, out);
+            }
+        }
+        if let Some(suffix) = non_empty(&self.suffix) {
+            out = append_tail(&out, suffix);
+        }
+        if let Some(negative) = self.negative_phrase() {
+            out = append_tail(&out, &negative);
+        }
+        out
+    }
+
+    pub fn default_aspect_ratio(&self) -> Option<String> {
+        non_empty(&self.aspect_ratio).map(str::to_string)
+    }
+}
+
+pub fn validate_presets(presets: &[StylePreset]) -> Result<(), String> {
+    let mut seen: Vec<String> = Vec::new();
+    for preset in presets {
+        preset.validate()?;
+        let key = preset.name.trim().to_lowercase();
+        if seen.contains(&key) {
+            return Err(format!("Duplicate style preset: {}", preset.name.trim()));
+        }
+        seen.push(key);
+    }
+    Ok(())
+}
+
+// Explicit ratio and count from the caller win over the preset defaults.
+pub fn resolve_task(
+    preset: Option<&StylePreset>,
+    prompt: &str,
+    aspect_ratio: Option<String>,
+    count: Option<u32>,
+) -> (String, String, u32) {
+    let ratio = aspect_ratio
+        .or_else(|| preset.and_then(|p| p.default_aspect_ratio()))
+        .unwrap_or_else(|| "16:9".to_string());
+    let count = count.or_else(|| preset.and_then(|p| p.count)).unwrap_or(1);
+    let prompt = match preset {
+        Some(p) => p.apply(prompt),
+        None => prompt.to_string(),
+    };
+    (prompt, ratio, count)
+}
+
+pub fn find_preset(name: &str) -> Result<StylePreset, String> {
+    let wanted = name.trim();
+    load_settings()
+        .style_presets
+        .unwrap_or_default()
+        .into_iter()
+        .find(|p| p.name.trim().eq_ignore_ascii_case(wanted))
+        .ok_or_else(|| format!("Unknown style preset: {}", wanted))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn noir() -> StylePreset {
+        StylePreset {
+            name: "Noir".to_string(),
+            prefix: Some("film noir still,".to_string()),
+            suffix: Some(" high contrast ".to_string()),
+            aspect_ratio: Some("9:16".to_string()),
+            count: Some(3),
+            negative: vec![
+                "text".to_string(),
+                "No watermark".to_string(),
+                " ".to_string(),
+            ],
+        }
+    }
+
+    #[test]
+    fn apply_adds_prefix_suffix_and_negatives_once() {
+        let preset = noir();
+        let styled = preset.apply("  a detective in the rain, ");
+        assert_eq!(
+            styled,
+            "film noir still, a detective in the rain, high contrast, no text, No watermark"
+        );
+        assert_eq!(preset.apply(&styled), styled);
+        assert_eq!(StylePreset::default().apply(" plain "), "plain");
+    }
+
+    #[test]
+    fn caller_values_win_over_preset_defaults() {
+        let preset = noir();
+        let (prompt, ratio, count) = resolve_task(Some(&preset), "a cat", None, None);
+        assert!(prompt.starts_with("film noir still, a cat"));
+        assert_eq!((ratio.as_str(), count), ("9:16", 3));
+
+        let (_, ratio, count) =
+            resolve_task(Some(&preset), "a cat", Some("1:1".to_string()), Some(1));
+        assert_eq!((ratio.as_str(), count), ("1:1", 1));
+
+        let (prompt, ratio, count) = resolve_task(None, "a cat", None, None);
+        assert_eq!(
+            (prompt.as_str(), ratio.as_str(), count),
+            ("a cat", "16:9", 1)
+        );
+    }
+
+    #[test]
+    fn validation() {
+        assert!(validate_presets(&[noir()]).is_ok());
+        let mut renamed = noir();
+        renamed.name = " noir ".to_string();
+        assert_eq!(
+            validate_presets(&[noir(), renamed]).unwrap_err(),
+            "Duplicate style preset: noir"
+        );
+        let bad_ratio = StylePreset {
+            aspect_ratio: Some("wide".to_string()),
+            ..noir()
+        };
+        assert!(bad_ratio.validate().is_err());
+        let zero = StylePreset {
+            count: Some(0),
+            ..noir()
+        };
+        assert!(zero.validate().is_err());
+        assert!(StylePreset::default().validate().is_err());
+    }
+}
@@ -0,0 +1,1725 @@
+use crate::util::re;
+use crate::whisk::AspectRatio;
+use regex::{Captures, Regex};
+use serde::{Deserialize, Serialize};
+
+// The first 14 are the doodle-script words the Merge Overlap tool has always used; the
+// splitter also accepts the photo and cinema vocabulary after them.
+const SCRIPT_PROMPT_KEYWORDS: [&str; 31] = [
+    "illustration",
+    "background",
+    "character",
+    "scene",
+    "drawing",
+    "doodle",
+    "sketch",
+    "art style",
+    "minimalist",
+    "hand-drawn",
+    "pure black",
+    "pure white",
+    "outline",
+    "line art",
+    "figure",
+    "image shows",
+    "visual",
+    "centered",
+    "frame",
+    "camera",
+    "shot",
+    "cinematic",
+    "composition",
+    "lighting",
+    "render",
+    "style",
+    "aesthetic",
+    "color palette",
+    "texture",
+    "gradient",
+    "shading",
+];
+pub(crate) const DOODLE_PROMPT_KEYWORDS: &[&str] = SCRIPT_PROMPT_KEYWORDS.split_at(14).0;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SplitOptions {
+    #[serde(default)]
+    pub filter: Option<String>,
+    #[serde(default)]
+    pub format: Option<String>,
+    #[serde(default)]
+    pub keep_source_order: bool,
+    #[serde(default)]
+    pub keep_numbers: bool,
+    #[serde(default)]
+    pub default_aspect_ratio: Option<String>,
+    #[serde(default)]
+    pub default_count: Option<u32>,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct SplitResult {
+    pub format: String,
+    pub label: String,
+    pub confidence: f32,
+    // True when the format has per-prompt headers; a line list that merely looks like
+    // "title - text" is not structured and should stay one prompt per line.
+    pub structured: bool,
+    pub images: Vec<String>,
+    pub videos: Vec<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub numbers: Option<Vec<Option<u32>>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub numbering: Option<NumberingReport>,
+    pub tasks: Vec<SplitTask>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub warnings: Vec<String>,
+}
+
+// One image prompt ready to queue: ratio and count come from its header when it has
+// them, otherwise from the batch defaults in SplitOptions. Paired formats keep the
+// matching video prompt on the same task.
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct SplitTask {
+    pub number: Option<u32>,
+    pub prompt: String,
+    pub video_prompt: Option<String>,
+    pub aspect_ratio: Option<String>,
+    pub count: Option<u32>,
+    pub ratio_from_header: bool,
+    pub count_from_header: bool,
+}
+
+#[derive(Debug, Serialize, Clone, PartialEq, Eq)]
+#[serde(rename_all = "camelCase")]
+pub struct NumberSpan {
+    pub from: u32,
+    pub to: u32,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct NumberingReport {
+    pub first: Option<u32>,
+    pub last: Option<u32>,
+    pub gaps: Vec<NumberSpan>,
+    pub duplicates: Vec<u32>,
+}
+
+// One prompt from a format whose headers carry a number (Image 7, FRAME 3, ...).
+#[derive(Debug, Clone)]
+pub struct NumberedPrompt {
+    pub number: Option<u32>,
+    pub image: String,
+    pub video: Option<String>,
+    pub aspect_ratio: Option<String>,
+    pub count: Option<u32>,
+}
+
+impl NumberedPrompt {
+    fn image(number: Option<u32>, image: String) -> Self {
+        NumberedPrompt {
+            number,
+            image,
+            video: None,
+            aspect_ratio: None,
+            count: None,
+        }
+    }
+
+    // Only the first non-empty line of a header is read, so a ratio that appears in a
+    // description the header regex happens to span is not picked up.
+    fn with_header(mut self, header: &str) -> Self {
+        let line = header.lines().map(str::trim).find(|l| !l.is_empty());
+        if let Some(line) = line {
+            self.aspect_ratio = header_aspect_ratio(line);
+            self.count = header_count(line);
+        }
+        self
+    }
+}
+
+// "9:16", "16/9", "4x3" or a word like "portrait". A pair that is not a Whisk ratio is
+// only taken when the header says "ratio"/"aspect", so "(0:08)" timings are ignored.
+pub fn header_aspect_ratio(header: &str) -> Option<String> {
+    let pair = re!(r"\b([1-9]\d?)(?:\s*[:×/]\s*|x)([1-9]\d?)\b");
+    let mentions_ratio = re!(r"(?i)\b(?:ratio|aspect)\b").is_match(header);
+    for caps in pair.captures_iter(header) {
+        let ratio = format!("{}:{}", &caps[1], &caps[2]);
+        if mentions_ratio || AspectRatio::parse(&ratio).is_ok() {
+            return Some(ratio);
+        }
+    }
+    let word = re!(r"(?i)\b(landscape|horizontal|wide|portrait|vertical|square)\b");
+    word.captures(header)
+        .map(|caps| match caps[1].to_lowercase().as_str() {
+            "portrait" | "vertical" => "9:16",
+            "square" => "1:1",
+            _ => "16:9",
+        })
+        .map(str::to_string)
+}
+
+// "x3", "3 images", "4 variations", "count: 2".
+pub fn header_count(header: &str) -> Option<u32> {
+    let count = re!(
+        r"(?i)\bx\s*([1-9]\d?)\b|\b([1-9]\d?)\s*(?:images?|variations?|versions?|shots?|outputs?)\b|\b(?:count|qty)\s*[:=]?\s*([1-9]\d?)\b"
+    );
+    let caps = count.captures(header)?;
+    (1..=3)
+        .find_map(|i| caps.get(i))
+        .and_then(|m| m.as_str().parse().ok())
+}
+
+// Parsed "1-10,15,20-" expression; an open end means "and everything after".
+#[derive(Debug, Clone, Default)]
+pub struct NumberRanges(Vec<(u32, Option<u32>)>);
+
+impl NumberRanges {
+    pub fn parse(expr: &str) -> Result<Option<Self>, String> {
+        let mut ranges = Vec::new();
+        for part in expr.split([',', ';']) {
+            let part: String = part.chars().filter(|c| !c.is_whitespace()).collect();
+            if part.is_empty() {
+                continue;
+            }
+            let invalid = || format!("Invalid number range: {}", part);
+            let range = match part.split_once('-') {
+                Some((from, to)) => {
+                    let from = if from.is_empty() {
+                        1
+                    } else {
+                        from.parse().map_err(|_| invalid())?
+                    };
+                    let to = if to.is_empty() {
+                        None
+                    } else {
+                        Some(to.parse().map_err(|_| invalid())?)
+                    };
+                    if to.is_some_and(|to| to < from) {
+                        return Err(invalid());
+                    }
+                    (from, to)
+                }
+                None => {
+                    let n = part.parse().map_err(|_| invalid())?;
+                    (n, Some(n))
+                }
+            };
+            ranges.push(range);
+        }
+        Ok(if ranges.is_empty() {
+            None
+        } else {
+            Some(NumberRanges(ranges))
+        })
+    }
+
+    pub fn contains(&self, n: u32) -> bool {
+        self.0
+            .iter()
+            .any(|&(from, to)| n >= from && to.is_none_or(|to| n <= to))
+    }
+}
+
+pub fn collapse_whitespace(s: &str) -> String {
+    s.split_whitespace().collect::<Vec<_>>().join(" ")
+}
+
+pub(crate) fn parse_num(caps: &Captures, group: usize) -> u32 {
+    caps.get(group)
+        .and_then(|m| m.as_str().parse().ok())
+        .unwrap_or(0)
+}
+
+pub(crate) struct Section<'a> {
+    pub caps: Captures<'a>,
+    pub body: &'a str,
+}
+
+// Equivalent of `HEADER(.*?)(?=TERMINATOR|\Z)`: the body runs from the end of the header
+// (or the start of `body_group`) to the next terminator match, skipping `min_chars` first.
+pub(crate) fn sections<'a>(
+    raw: &'a str,
+    header: &Regex,
+    terminator: &Regex,
+    body_group: Option<usize>,
+    min_chars: usize,
+) -> Vec<Section<'a>> {
+    let mut out = Vec::new();
+    let mut pos = 0;
+    while pos <= raw.len() {
+        let Some(caps) = header.captures_at(raw, pos) else {
+            break;
+        };
+        let whole = caps.get(0).map(|m| m.range()).unwrap_or(pos..pos);
+        let start = body_group
+            .and_then(|g| caps.get(g))
+            .map(|m| m.start())
+            .unwrap_or(whole.end);
+        let skip: Vec<usize> = raw[start..]
+            .chars()
+            .take(min_chars)
+            .map(char::len_utf8)
+            .collect();
+        if skip.len() < min_chars {
+            break;
+        }
+        let search_from = start + skip.iter().sum::<usize>();
+        let end = terminator
+            .find_at(raw, search_from)
+            .map(|m| m.start())
+            .unwrap_or(raw.len());
+        out.push(Section {
+            caps,
+            body: &raw[start..end],
+        });
+        pos = end.max(whole.end).max(whole.start + 1);
+    }
+    out
+}
+
+// Text between consecutive matches of `re`; the last chunk runs to the end of input.
+pub(crate) fn between_matches<'a>(raw: &'a str, re: &Regex) -> Vec<&'a str> {
+    let matches: Vec<_> = re.find_iter(raw).collect();
+    matches
+        .iter()
+        .enumerate()
+        .map(|(i, m)| {
+            let end = matches.get(i + 1).map(|n| n.start()).unwrap_or(raw.len());
+            &raw[m.end()..end]
+        })
+        .collect()
+}
+
+fn collapsed_bodies(sections: Vec<Section>) -> Vec<String> {
+    sections
+        .into_iter()
+        .map(|s| collapse_whitespace(s.body))
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+fn numbered_bodies(sections: Vec<Section>, num_group: usize) -> Vec<NumberedPrompt> {
+    sections
+        .into_iter()
+        .map(|s| {
+            NumberedPrompt::image(
+                Some(parse_num(&s.caps, num_group)),
+                collapse_whitespace(s.body),
+            )
+            .with_header(&s.caps[0])
+        })
+        .collect()
+}
+
+pub fn is_prompt_image_video_format(raw: &str) -> bool {
+    re!(r"(?i)PROMPT\s+\d+").is_match(raw)
+        && re!(r"(?i)IMAGE PROMPT\s*\([^)]*\)\s*:").is_match(raw)
+        && re!(r"(?i)VIDEO PROMPT\s*\([^)]*\)\s*:").is_match(raw)
+}
+
+pub fn parse_prompt_image_video_format(raw: &str) -> Vec<NumberedPrompt> {
+    let header = re!(
+        r"(?is)PROMPT\s+(\d+)\s*\n.*?IMAGE PROMPT\s*\(([^)]*)\)\s*:\s*\n(.*?)VIDEO PROMPT\s*\([^)]*\)\s*:\s*\n"
+    );
+    sections(raw, header, re!(r"(?i)PROMPT\s+\d+"), None, 0)
+        .into_iter()
+        .map(|s| {
+            let image = collapse_whitespace(s.caps.get(3).map(|m| m.as_str()).unwrap_or(""));
+            NumberedPrompt {
+                video: Some(collapse_whitespace(s.body)),
+                ..NumberedPrompt::image(Some(parse_num(&s.caps, 1)), image)
+            }
+            .with_header(&s.caps[2])
+        })
+        .collect()
+}
+
+pub fn parse_flow_video_format(raw: &str) -> (Vec<String>, Vec<String>) {
+    let header = re!(r"(?is)IMAGE PROMPT:(.*?)FLOW VIDEO PROMPT:");
+    let terminator = re!(r"(?i)\nScene|\nIMAGE PROMPT:");
+    sections(raw, header, terminator, None, 0)
+        .into_iter()
+        .map(|s| {
+            (
+                collapse_whitespace(s.caps.get(1).map(|m| m.as_str()).unwrap_or("")),
+                collapse_whitespace(s.body),
+            )
+        })
+        .unzip()
+}
+
+// Flow blocks carry no numbers, so the position stands in for one and filters work the
+// same as for the other formats.
+pub fn flow_video_pairs(raw: &str) -> Vec<NumberedPrompt> {
+    let (images, videos) = parse_flow_video_format(raw);
+    images
+        .into_iter()
+        .zip(videos)
+        .zip(1u32..)
+        .map(|((image, video), n)| NumberedPrompt {
+            video: Some(video),
+            ..NumberedPrompt::image(Some(n), image)
+        })
+        .collect()
+}
+
+pub fn parse_json_format(raw: &str) -> Vec<String> {
+    let mut results = Vec::new();
+    let mut depth = 0i32;
+    let mut start: Option<usize> = None;
+    for (i, c) in raw.char_indices() {
+        if c == '{' {
+            if depth == 0 {
+                start = Some(i);
+            }
+            depth += 1;
+        } else if c == '}' {
+            depth -= 1;
+            if depth == 0 {
+                if let Some(s) = start.take() {
+                    let clean = collapse_whitespace(&raw[s..=i]);
+                    if !clean.is_empty() {
+                        results.push(clean);
+                    }
+                }
+            }
+        }
+    }
+    results
+}
+
+pub fn is_image_marker_format(raw: &str) -> bool {
+    re!(r"(?i)\[\s*IMAGE\s*\d+\s*\]").is_match(raw)
+}
+
+pub fn parse_image_marker_format(raw: &str) -> Vec<NumberedPrompt> {
+    let header = re!(r"(?is)\[\s*IMAGE\s*(\d+)\s*\]\s*");
+    let terminator = re!(r"(?i)\[\s*IMAGE\s*\d+\s*\]");
+    numbered_bodies(sections(raw, header, terminator, None, 0), 1)
+}
+
+pub fn is_image_ratio_format(raw: &str) -> bool {
+    re!(r"(?i)Image\s+\d+[^\n]*ratio").is_match(raw)
+}
+
+pub fn parse_image_ratio_format(raw: &str) -> Vec<NumberedPrompt> {
+    let header = re!(r"(?i)(Image\s+(\d+)[^\n]*ratio[^\n]*)\n");
+    let terminator = re!(r"(?i)Image\s+\d+[^\n]*ratio");
+    numbered_bodies(sections(raw, header, terminator, None, 0), 2)
+}
+
+pub fn is_inline_paren_format(raw: &str) -> bool {
+    if re!(r"(?i)^\s*Motion\s+Prompt\s*\d+").is_match(raw)
+        || re!(r"(?i)^\s*Image\s*\d+").is_match(raw)
+        || re!(r"(?i)\)\.\s*Image\s*\d+").is_match(raw)
+        || re!(r"(?i)\)\.\s*Motion\s+Prompt\s*\d+").is_match(raw)
+        || re!(r"(?m)(?:^|\n)[A-Z][A-Za-z\s]+?\s*[—–-]\s*").is_match(raw)
+        || re!(r"(?i)(?:^|\n)\s*Scene\s*:").is_match(raw)
+    {
+        return false;
+    }
+    re!(r"\)\.\s*[A-Z]").is_match(raw)
+}
+
+pub fn parse_inline_paren_format(raw: &str) -> Vec<String> {
+    let boundary = re!(r"\)\.\s*[A-Z]");
+    let mut results = Vec::new();
+    let mut seg_start = 0;
+    let mut pos = 0;
+    while let Some(m) = boundary.find_at(raw, pos) {
+        let clean = collapse_whitespace(&raw[seg_start..m.start() + 2]);
+        if !clean.is_empty() {
+            results.push(clean);
+        }
+        seg_start = m.end() - 1;
+        pos = seg_start;
+    }
+    let clean = collapse_whitespace(&raw[seg_start..]);
+    if !clean.is_empty() {
+        results.push(clean);
+    }
+    results
+}
+
+pub fn is_title_dash_format(raw: &str) -> bool {
+    if re!(r"(?im)^\s*Motion\s+Prompt\s*\d+").is_match(raw)
+        || re!(r"(?im)^\s*Image\s*\d+").is_match(raw)
+    {
+        return false;
+    }
+    re!(r"(?m)(?:^|\n)[A-Z][A-Za-z\s]+?\s*[—–-]\s*\S").is_match(raw)
+}
+
+pub fn parse_title_dash_format(raw: &str) -> Vec<String> {
+    between_matches(raw, re!(r"(?m)(?:^|\n)([A-Z][A-Za-z\s]+?)\s*[—–-]\s*"))
+        .into_iter()
+        .map(collapse_whitespace)
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+pub fn is_motion_prompt_format(raw: &str) -> bool {
+    re!(r"(?i)Motion\s+Prompt\s*\d+").is_match(raw)
+}
+
+pub fn parse_motion_prompt_format(raw: &str) -> Vec<NumberedPrompt> {
+    let header = re!(r"(?is)Motion\s+Prompt\s*(\d+)\s*(?:\([^)]*\))?\s*\n?");
+    let terminator = re!(r"(?i)Motion\s+Prompt\s*\d+");
+    numbered_bodies(sections(raw, header, terminator, None, 0), 1)
+}
+
+pub fn is_script_placeholder_format(raw: &str) -> bool {
+    re!(r"(?is)Image\s+\d+\s*:.*?SCRIPT\s+PLACEHOLDER\s*:").is_match(raw)
+}
+
+// Narration is short; the image prompt is the first long line that opens with "A " or
+// uses one of the keywords.
+pub(crate) fn is_script_prompt_line(line: &str, keywords: &[&str]) -> bool {
+    let lower = line.to_lowercase();
+    line.chars().count() > 50
+        && (line.starts_with("A ") || keywords.iter().any(|kw| lower.contains(kw)))
+}
+
+fn extract_script_prompt(content: &str) -> String {
+    let content = content.trim();
+    let lines: Vec<&str> = content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect();
+
+    let prompt_start = lines
+        .iter()
+        .position(|line| is_script_prompt_line(line, &SCRIPT_PROMPT_KEYWORDS));
+
+    match prompt_start {
+        Some(i) => collapse_whitespace(&lines[i..].join(" ")),
+        None => {
+            let parts: Vec<&str> = re!(r"\n\s*\n")
+                .split(content)
+                .map(str::trim)
+                .filter(|p| !p.is_empty())
+                .collect();
+            if parts.len() > 1 {
+                let chosen = parts
+                    .iter()
+                    .find(|p| p.starts_with("A ") && p.chars().count() > 50)
+                    .or(parts.last())
+                    .copied()
+                    .unwrap_or(content);
+                collapse_whitespace(chosen)
+            } else {
+                collapse_whitespace(content)
+            }
+        }
+    }
+}
+
+pub fn parse_script_placeholder_format(raw: &str) -> Vec<NumberedPrompt> {
+    let header = re!(r"(?is)Image\s+(\d+)\s*:.*?SCRIPT\s+PLACEHOLDER\s*:\s*");
+    let terminator = re!(r"(?i)Image\s+\d+\s*:");
+    sections(raw, header, terminator, None, 0)
+        .into_iter()
+        .filter(|s| !s.body.trim().is_empty())
+        .map(|s| {
+            NumberedPrompt::image(Some(parse_num(&s.caps, 1)), extract_script_prompt(s.body))
+                .with_header(&s.caps[0])
+        })
+        .collect()
+}
+
+pub fn is_image_inline_format(raw: &str) -> bool {
+    if re!(r"(?i)Image\s+\d+\s*:").is_match(raw) || is_image_ratio_format(raw) {
+        return false;
+    }
+    re!(r"(?i)Image\s*\d+\s*\n?[A-Z]").is_match(raw)
+}
+
+pub fn parse_image_inline_format(raw: &str) -> Vec<NumberedPrompt> {
+    let header = re!(r"(?is)Image\s*(\d+)\s*\n?([A-Z])");
+    let terminator = re!(r"(?i)Image\s*\d+\s*\n?[A-Z]");
+    numbered_bodies(sections(raw, header, terminator, Some(2), 1), 1)
+}
+
+pub fn is_numbered_paren_title_format(raw: &str) -> bool {
+    re!(r"\d+\)\s*[A-Za-z][^—–]{0,50}[—–]").is_match(raw)
+}
+
+pub fn parse_numbered_paren_title_format(raw: &str) -> Vec<String> {
+    between_matches(raw, re!(r"(\d+)\)\s*([A-Za-z][^—–]{0,50})[—–]\s*"))
+        .into_iter()
+        .map(collapse_whitespace)
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+pub fn is_numbered_scene_format(raw: &str) -> bool {
+    re!(r"(?m)^\d+\)\s*\w+").is_match(raw)
+}
+
+pub fn parse_numbered_scene_format(raw: &str) -> Vec<String> {
+    let header = re!(r"(?m)^\d+\)\s*[^\n]+\n");
+    collapsed_bodies(sections(raw, header, re!(r"(?m)^\d+\)"), None, 1))
+}
+
+pub fn is_numbered_title_format(raw: &str) -> bool {
+    let mut count = 0;
+    for caps in re!(r"(?m)^\d+\.\s*([^\n]+)\n").captures_iter(raw) {
+        let title = caps[1].trim();
+        if title.chars().count() > 50 || title.contains([',', ';', ':']) {
+            return false;
+        }
+        count += 1;
+    }
+    count >= 1
+}
+
+pub fn parse_numbered_title_format(raw: &str) -> Vec<String> {
+    let header = re!(r"(?m)^\d+\.\s*[^\n]+\n");
+    collapsed_bodies(sections(raw, header, header, None, 1))
+}
+
+pub fn is_car_year_format(raw: &str) -> bool {
+    re!(r"\((1[89]\d{2}|20\d{2})\)\s*[—–-]").is_match(&raw.replace("**", ""))
+}
+
+// Start of the car name inside `text`: skips an optional "N. " list prefix before the first capital.
+fn name_offset(text: &str) -> Option<usize> {
+    re!(r"(\d+\.\s*)?([A-Z])")
+        .captures(text)
+        .map(|c| c.get(2).map(|m| m.start()).unwrap_or(0))
+}
+
+fn camel_break(text: &str) -> Option<usize> {
+    re!(r"[a-z]([A-Z])")
+        .captures(text)
+        .and_then(|c| c.get(1).map(|m| m.start()))
+}
+
+pub fn parse_car_year_format(raw: &str) -> Vec<String> {
+    let cleaned = raw.replace("**", "");
+    let years: Vec<_> = re!(r"\((1[89]\d{2}|20\d{2})\)\s*[—–-]")
+        .find_iter(&cleaned)
+        .collect();
+    let list_prefix = re!(r"^\d+\.\s*");
+    let trailing_number = re!(r"\s+\d+\.\s*$");
+    let mut results = Vec::new();
+
+    for (i, year) in years.iter().enumerate() {
+        let search_start = if i == 0 { 0 } else { years[i - 1].end() };
+        let before = &cleaned[search_start..year.start()];
+        let name_start = search_start
+            + if i == 0 {
+                name_offset(before).unwrap_or(0)
+            } else {
+                camel_break(before)
+                    .or_else(|| name_offset(before))
+                    .unwrap_or(0)
+            };
+
+        let end = match years.get(i + 1) {
+            Some(next) => {
+                let between = &cleaned[year.end()..next.start()];
+                match camel_break(between).or_else(|| name_offset(between)) {
+                    Some(off) => year.end() + off,
+                    None => next.start(),
+                }
+            }
+            None => cleaned.len(),
+        };
+        if end <= name_start {
+            continue;
+        }
+
+        let prompt = cleaned[name_start..end].trim();
+        let prompt = list_prefix.replace(prompt, "");
+        let prompt = trailing_number.replace(&prompt, "");
+        let prompt = collapse_whitespace(&prompt);
+        if !prompt.is_empty() {
+            results.push(prompt);
+        }
+    }
+    results
+}
+
+pub fn is_title_dash_content_format(raw: &str) -> bool {
+    re!(r"[A-Z][A-Za-z]+(?:\s+[A-Z][A-Za-z]+)*\s*[—–-]\s+").is_match(raw)
+        && !re!(r"(?i)Image\s+\d+").is_match(raw)
+}
+
+pub fn parse_title_dash_content(raw: &str) -> Vec<String> {
+    let header = re!(r"[A-Z][A-Za-z]+(?:\s+[A-Z][A-Za-z]+)*\s*[—–-]\s*");
+    let terminator = re!(r"[A-Z][A-Za-z]+(?:\s+[A-Z][A-Za-z]+)*\s*[—–-]");
+    let results: Vec<String> = sections(raw, header, terminator, None, 1)
+        .into_iter()
+        .map(|s| s.body.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .collect();
+    if !results.is_empty() {
+        return results;
+    }
+
+    raw.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .map(
+            |line| match re!(r"^[A-Z][A-Za-z\s]+\s*[—–-]\s*(.+)$").captures(line) {
+                Some(c) => c[1].trim().to_string(),
+                None => line.to_string(),
+            },
+        )
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+pub fn parse_image_colon_format(raw: &str) -> Vec<NumberedPrompt> {
+    let header = re!(r"(?i)Image\s+(\d+)\s*:\s*");
+    let terminator = re!(r"(?i)Image\s+\d+\s*:");
+    numbered_bodies(sections(raw, header, terminator, None, 0), 1)
+}
+
+pub fn parse_image_newline_format(raw: &str) -> Vec<NumberedPrompt> {
+    let header = re!(r"(?im)^\s*Image\s+(\d+)\s*\n");
+    let terminator = re!(r"(?im)^\s*Image\s+\d+");
+    numbered_bodies(sections(raw, header, terminator, None, 0), 1)
+}
+
+fn is_scene_title(line: &str) -> bool {
+    let line = line.trim();
+    if line.chars().count() > 60
+        || re!(r"(?i)^\d+-second").is_match(line)
+        || !line.chars().any(|c| c.is_ascii_alphabetic())
+        || line.contains(['.', ',', ';'])
+    {
+        return false;
+    }
+    let words: Vec<&str> = line.split_whitespace().collect();
+    if words.len() > 6 {
+        return false;
+    }
+    words.iter().any(|w| {
+        w.chars()
+            .next()
+            .map(|c| c.is_ascii_alphabetic() && c.is_ascii_uppercase())
+            .unwrap_or(false)
+    })
+}
+
+pub fn is_title_content_format(raw: &str) -> bool {
+    let lines: Vec<&str> = raw
+        .trim()
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty())
+        .collect();
+    if lines.len() < 2 {
+        return false;
+    }
+    let titles = lines.iter().filter(|l| is_scene_title(l)).count();
+    let content = lines.len() - titles;
+    titles >= 1 && content >= 1 && titles <= lines.len() / 2 + 1
+}
+
+pub fn parse_title_content_format(raw: &str) -> Vec<String> {
+    let mut results = Vec::new();
+    let mut has_title = false;
+    let mut content: Vec<&str> = Vec::new();
+    for line in raw.trim().lines().map(str::trim).filter(|l| !l.is_empty()) {
+        if is_scene_title(line) {
+            if has_title && !content.is_empty() {
+                let full = collapse_whitespace(&content.join(" "));
+                if !full.is_empty() {
+                    results.push(full);
+                }
+            }
+            has_title = true;
+            content.clear();
+        } else {
+            content.push(line);
+        }
+    }
+    if has_title && !content.is_empty() {
+        let full = collapse_whitespace(&content.join(" "));
+        if !full.is_empty() {
+            results.push(full);
+        }
+    }
+    results
+}
+
+pub fn parse_scene_format(raw: &str) -> Vec<NumberedPrompt> {
+    let header = re!(r"(?im)^\s*Scene\s+(\d+)\s*\n");
+    let terminator = re!(r"(?im)^\s*Scene\s+\d+");
+    numbered_bodies(sections(raw, header, terminator, None, 0), 1)
+}
+
+pub fn parse_scene_colon_format(raw: &str) -> Vec<String> {
+    let starts: Vec<usize> = re!(r"(?i)(?:^|\n)\s*Scene\s*:")
+        .find_iter(raw)
+        .map(|m| m.start())
+        .collect();
+    starts
+        .iter()
+        .enumerate()
+        .map(|(i, &start)| {
+            let end = starts.get(i + 1).copied().unwrap_or(raw.len());
+            collapse_whitespace(&raw[start..end])
+        })
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+fn strip_prompt_label(chunk: &str) -> String {
+    re!(r"(?i)^(?:Prompt:|Image Prompt:)\s*")
+        .replace(&collapse_whitespace(chunk), "")
+        .trim()
+        .to_string()
+}
+
+pub fn parse_prompt_format(raw: &str) -> Vec<String> {
+    re!(r"(?i)(?:^|\n)\s*Prompt:")
+        .split(raw)
+        .map(strip_prompt_label)
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+pub fn parse_paragraph_format(raw: &str) -> Vec<String> {
+    re!(r"\n\s*\n")
+        .split(raw)
+        .map(strip_prompt_label)
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+pub fn is_frame_text_version_format(raw: &str) -> bool {
+    re!(r"(?i)FRAME\s+\d+").is_match(raw)
+        && re!(r"(?i)TEXT VERSION\s*\(English\)\s*:").is_match(raw)
+        && re!(r"(?i)NO-TEXT VERSION\s*\(English\)").is_match(raw)
+}
+
+// Text before the first FRAME marker has no number and is always kept.
+pub fn parse_frame_text_version_format(raw: &str) -> Vec<NumberedPrompt> {
+    let frame = re!(r"(?i)FRAME\s+(\d+)");
+    let mut cuts: Vec<usize> = frame.find_iter(raw).map(|m| m.start()).collect();
+    cuts.insert(0, 0);
+    cuts.push(raw.len());
+
+    let header = re!(r"(?is)TEXT VERSION\s*\(English\)\s*:\s*");
+    let terminator = re!(r"(?i)NO-TEXT VERSION\s*\(English\)|FRAME\s+\d+");
+    let mut results = Vec::new();
+    for pair in cuts.windows(2) {
+        let block = &raw[pair[0]..pair[1]];
+        if block.trim().is_empty() {
+            continue;
+        }
+        let number = frame.captures(block).map(|caps| parse_num(&caps, 1));
+        if let Some(s) = sections(block, header, terminator, None, 0)
+            .into_iter()
+            .next()
+        {
+            let entry = NumberedPrompt::image(number, collapse_whitespace(s.body));
+            results.push(if number.is_some() {
+                entry.with_header(block)
+            } else {
+                entry
+            });
+        }
+    }
+    results
+}
+
+pub fn is_scene_addon_format(raw: &str) -> bool {
+    re!(r"(?i)SCENE ADD-ON \(English\):").is_match(raw)
+}
+
+pub fn parse_scene_addon_format(raw: &str) -> Vec<String> {
+    let header = re!(r"(?i)SCENE ADD-ON \(English\):\s*");
+    let placeholder = re!(r"(?is)SCRIPT PLACEHOLDER:");
+    let placeholder_end = re!(r"(?i)SCENE ADD-ON|FRAME");
+    sections(raw, header, re!(r"(?i)FRAME \d+"), None, 0)
+        .into_iter()
+        .map(|s| {
+            let mut kept = String::new();
+            let mut last = 0;
+            for p in sections(s.body, placeholder, placeholder_end, None, 0) {
+                let start = p.caps.get(0).map(|m| m.start()).unwrap_or(0);
+                kept.push_str(&s.body[last..start]);
+                last = start + (p.caps.get(0).map(|m| m.len()).unwrap_or(0)) + p.body.len();
+            }
+            kept.push_str(&s.body[last..]);
+            collapse_whitespace(&re!(r"(?i)FRAME \d+").replace_all(&kept, ""))
+        })
+        .filter(|s| !s.is_empty())
+        .collect()
+}
+
+pub fn is_prompt_addon_format(raw: &str) -> bool {
+    re!(r"(?i)PROMPT\s*\(ADD-ON\)\s*:").is_match(raw)
+}
+
+pub fn parse_prompt_addon_format(raw: &str) -> Vec<String> {
+    let header = re!(r"(?i)PROMPT\s*\(ADD-ON\)\s*:\s*");
+    let terminator =
+        re!(r"(?i)FRAME\s+\d+|SCRIPT\s+PLACEHOLDER|TEXT\s+IN\s+VIDEO|PROMPT\s*\(ADD-ON\)");
+    collapsed_bodies(sections(raw, header, terminator, None, 0))
+}
+
+pub fn is_scene_prompt_english_format(raw: &str) -> bool {
+    re!(r"(?i)SCENE\s+PROMPT\s*\(English\)\s*:").is_match(raw)
+}
+
+pub fn parse_scene_prompt_english_format(raw: &str) -> Vec<String> {
+    let header = re!(r"(?i)SCENE\s+PROMPT\s*\(English\)\s*:\s*");
+    let terminator = re!(r"(?i)FRAME\s+\d+|SCRIPT\s+PLACEHOLDER|TEXT\s+OVERLAY|SCENE\s+PROMPT");
+    collapsed_bodies(sections(raw, header, terminator, None, 0))
+}
+
+pub fn is_frame_colon_format(raw: &str) -> bool {
+    re!(r"(?i)FRAME\s+\d+\s*:").is_match(raw)
+}
+
+pub fn parse_frame_colon_format(raw: &str) -> Vec<String> {
+    let header = re!(r"(?i)FRAME\s+\d+\s*:\s*");
+    collapsed_bodies(sections(raw, header, re!(r"(?i)FRAME\s+\d+\s*:"), None, 0))
+}
+
+pub fn is_frame_pipe_scene_prompt_format(raw: &str) -> bool {
+    re!(r"(?i)FRAME\s+\d+\s*\|").is_match(raw)
+        && re!(r"(?i)SCRIPT\s+PLACEHOLDER\s*:").is_match(raw)
+        && re!(r"(?i)SCENE\s+PROMPT\s*(?:\([^)]*\))?\s*:").is_match(raw)
+}
+
+pub fn parse_frame_pipe_scene_prompt_format(raw: &str) -> Vec<String> {
+    let header = re!(r"(?i)SCENE\s+PROMPT\s*(?:\([^)]*\))?\s*:\s*");
+    collapsed_bodies(sections(raw, header, re!(r"(?i)FRAME\s+\d+"), None, 0))
+}
+
+pub fn is_flow_video_format(raw: &str) -> bool {
+    raw.to_uppercase().contains("FLOW VIDEO PROMPT:")
+}
+
+pub fn is_json_car_format(raw: &str) -> bool {
+    raw.trim_start().starts_with('{') && raw.contains("car_name_model")
+}
+
+pub fn is_image_colon_format(raw: &str) -> bool {
+    re!(r"(?i)Image\s+\d+\s*:").is_match(raw)
+}
+
+pub fn is_image_newline_format(raw: &str) -> bool {
+    re!(r"(?im)^\s*Image\s+\d+").is_match(raw)
+}
+
+pub fn is_scene_colon_format(raw: &str) -> bool {
+    re!(r"(?i)(?:^|\n)\s*Scene\s*:").is_match(raw)
+}
+
+pub fn is_scene_format(raw: &str) -> bool {
+    re!(r"(?im)^\s*Scene\s+\d+").is_match(raw)
+}
+
+pub fn is_prompt_format(raw: &str) -> bool {
+    raw.to_lowercase().matches("prompt:").count() > 1
+}
+
+struct FormatSpec {
+    name: &'static str,
+    label: &'static str,
+    detect: fn(&str) -> bool,
+    // Splits on layout (dashes, short title lines, a stray "prompt:") rather than on a
+    // label of its own, so it also fires on labelled text and keeps those labels.
+    layout: bool,
+}
+
+// Ordered the same way as the frontend's detectFormat chain; earlier entries are more
+// specific and win ties.
+const FORMATS: &[FormatSpec] = &[
+    FormatSpec {
+        name: "prompt_image_video",
+        label: "PROMPT / IMAGE / VIDEO",
+        detect: is_prompt_image_video_format,
+        layout: false,
+    },
+    FormatSpec {
+        name: "flow_video",
+        label: "Flow Video Prompt",
+        detect: is_flow_video_format,
+        layout: false,
+    },
+    FormatSpec {
+        name: "json_car",
+        label: "JSON objects",
+        detect: is_json_car_format,
+        layout: false,
+    },
+    FormatSpec {
+        name: "frame_text_version",
+        label: "Frame Text Version",
+        detect: is_frame_text_version_format,
+        layout: false,
+    },
+    FormatSpec {
+        name: "scene_addon",
+        label: "Scene Add-on",
+        detect: is_scene_addon_format,
+        layout: false,
+    },
+    FormatSpec {
+        name: "prompt_addon",
+        label: "Prompt Add-on",
+        detect: is_prompt_addon_format,
+        layout: false,
+    },
+    FormatSpec {
+        name: "scene_prompt_english",
+        label: "Scene Prompt (English)",
+        detect: is_scene_prompt_english_format,
+        layout: false,
+    },
+    FormatSpec {
+        name: "frame_pipe_scene_prompt",
+        label: "Frame | Scene Prompt",
+        detect: is_frame_pipe_scene_prompt_format,
+        layout: false,
+    },
+    FormatSpec {
+        name: "frame_colon",
+        label: "Frame n:",
+        detect: is_frame_colon_format,
+        layout: false,
+    },
+    FormatSpec {
+        name: "script_placeholder",
+        label: "Script Placeholder",
+        detect: is_script_placeholder_format,
+        layout: false,
+    },
+    FormatSpec {
+        name: "image_marker",
+        label: "[IMAGE n]",
+        detect: is_image_marker_format,
+        layout: false,
+    },
+    FormatSpec {
+        name: "image_ratio",
+        label: "Image n ratio",
+        detect: is_image_ratio_format,
+        layout: false,
+    },
+    FormatSpec {
+        name: "inline_paren",
+        label: "Inline parenthesis",
+        detect: is_inline_paren_format,
+        layout: true,
+    },
+    FormatSpec {
+        name: "title_dash",
+        label: "Title dash",
+        detect: is_title_dash_format,
+        layout: true,
+    },
+    FormatSpec {
+        name: "motion_prompt",
+        label: "Motion Prompt",
+        detect: is_motion_prompt_format,
+        layout: false,
+    },
+    FormatSpec {
+        name: "image_inline",
+        label: "Image n inline",
+        detect: is_image_inline_format,
+        layout: false,
+    },
+    FormatSpec {
+        name: "numbered_paren_title",
+        label: "n) Title —",
+        detect: is_numbered_paren_title_format,
+        layout: true,
+    },
+    FormatSpec {
+        name: "numbered_scene",
+        label: "n) Scene",
+        detect: is_numbered_scene_format,
+        layout: true,
+    },
+    FormatSpec {
+        name: "numbered_title",
+        label: "n. Title",
+        detect: is_numbered_title_format,
+        layout: true,
+    },
+    FormatSpec {
+        name: "car_year",
+        label: "Car (year) —",
+        detect: is_car_year_format,
+        layout: true,
+    },
+    FormatSpec {
+        name: "title_dash_content",
+        label: "Title — content",
+        detect: is_title_dash_content_format,
+        layout: true,
+    },
+    FormatSpec {
+        name: "image_colon",
+        label: "Image n:",
+        detect: is_image_colon_format,
+        layout: false,
+    },
+    FormatSpec {
+        name: "image_newline",
+        label: "Image n",
+        detect: is_image_newline_format,
+        layout: false,
+    },
+    FormatSpec {
+        name: "title_content",
+        label: "Title + content",
+        detect: is_title_content_format,
+        layout: true,
+    },
+    FormatSpec {
+        name: "scene_colon",
+        label: "Scene:",
+        detect: is_scene_colon_format,
+        layout: false,
+    },
+    FormatSpec {
+        name: "scene",
+        label: "Scene n",
+        detect: is_scene_format,
+        layout: false,
+    },
+    FormatSpec {
+        name: "prompt",
+        label: "Prompt:",
+        detect: is_prompt_format,
+        layout: true,
+    },
+];
+
+const PARAGRAPH: &str = "paragraph";
+const PARAGRAPH_LABEL: &str = "Paragraphs";
+const PARAGRAPH_SCORE: f32 = 0.2;
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct FormatCandidate {
+    pub format: String,
+    pub label: String,
+    pub score: f32,
+    pub prompt_count: usize,
+    pub coverage: f32,
+    pub reason: String,
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct Detection {
+    pub format: String,
+    pub label: String,
+    pub confidence: f32,
+    pub forced: bool,
+    pub reason: String,
+    pub alternatives: Vec<FormatCandidate>,
+}
+
+fn text_len(s: &str) -> usize {
+    s.chars().filter(|c| !c.is_whitespace()).count()
+}
+
+fn round2(v: f32) -> f32 {
+    (v * 100.0).round() / 100.0
+}
+
+fn measure_format(raw: &str, spec: &FormatSpec) -> FormatCandidate {
+    let (images, videos) = parse_with_format(raw, spec.name);
+    let prompt_count = images.len();
+    let kept: usize = images
+        .iter()
+        .chain(videos.iter())
+        .map(|p| text_len(p))
+        .sum();
+    let coverage = (kept as f32 / text_len(raw).max(1) as f32).min(1.0);
+    let reason = if prompt_count == 0 {
+        format!(
+            "{} markers found but no prompts could be extracted",
+            spec.label
+        )
+    } else {
+        format!(
+            "{} markers found; {} prompt{} covering {:.0}% of the text",
+            spec.label,
+            prompt_count,
+            if prompt_count == 1 { "" } else { "s" },
+            coverage * 100.0
+        )
+    };
+    FormatCandidate {
+        format: spec.name.to_string(),
+        label: spec.label.to_string(),
+        score: 0.0,
+        prompt_count,
+        coverage: round2(coverage),
+        reason,
+    }
+}
+
+// A format scores well when it is specific (early in the chain), keeps as much text as
+// the best candidate and yields more than one prompt. Coverage is relative because every
+// parser drops its own headers, so absolute coverage never reaches 1.0. Layout formats
+// are halved when a labelled format also yields prompts: the text they keep is mostly the
+// other format's labels, which would otherwise count as coverage.
+fn score_candidates(candidates: &mut [FormatCandidate], ranks: &[usize]) {
+    let best_coverage = candidates
+        .iter()
+        .filter(|c| c.prompt_count > 0)
+        .map(|c| c.coverage)
+        .fold(0.0f32, f32::max);
+    let labelled = candidates
+        .iter()
+        .zip(ranks)
+        .any(|(c, &rank)| c.prompt_count > 0 && !FORMATS[rank].layout);
+    for (candidate, &rank) in candidates.iter_mut().zip(ranks) {
+        if candidate.prompt_count == 0 || best_coverage == 0.0 {
+            continue;
+        }
+        let coverage = candidate.coverage / best_coverage;
+        let specificity = 1.0 - rank as f32 / FORMATS.len() as f32;
+        let count_factor = 1.0 - 1.0 / (candidate.prompt_count as f32 + 1.0);
+        let score = 0.45 * coverage + 0.35 * specificity + 0.2 * count_factor;
+        candidate.score = round2(if labelled && FORMATS[rank].layout {
+            score * 0.5
+        } else {
+            score
+        });
+    }
+}
+
+fn paragraph_candidate(raw: &str, reason: &str) -> FormatCandidate {
+    FormatCandidate {
+        format: PARAGRAPH.to_string(),
+        label: PARAGRAPH_LABEL.to_string(),
+        score: PARAGRAPH_SCORE,
+        prompt_count: parse_paragraph_format(raw).len(),
+        coverage: 1.0,
+        reason: reason.to_string(),
+    }
+}
+
+pub fn format_names() -> Vec<&'static str> {
+    FORMATS
+        .iter()
+        .map(|f| f.name)
+        .chain(std::iter::once(PARAGRAPH))
+        .collect()
+}
+
+// Scores every format whose markers appear in `raw`. `forced` skips the choice but still
+// reports how well the forced format fits, so the UI can warn about a bad override.
+pub fn detect_format(raw: &str, forced: Option<&str>) -> Result<Detection, String> {
+    let forced = forced.map(str::trim).filter(|f| !f.is_empty());
+    if let Some(name) = forced {
+        if !format_names().contains(&name) {
+            return Err(format!("Unknown prompt format: {}", name));
+        }
+    }
+
+    let (ranks, mut candidates): (Vec<usize>, Vec<FormatCandidate>) = FORMATS
+        .iter()
+        .enumerate()
+        .filter(|(_, spec)| (spec.detect)(raw) || forced == Some(spec.name))
+        .map(|(rank, spec)| (rank, measure_format(raw, spec)))
+        .unzip();
+    score_candidates(&mut candidates, &ranks);
+    // Stable sort keeps chain order for equal scores.
+    candidates.sort_by(|a, b| b.score.total_cmp(&a.score));
+    if candidates.iter().all(|c| c.score == 0.0) {
+        candidates.push(paragraph_candidate(
+            raw,
+            "No known markers found; splitting on blank lines",
+        ));
+    } else if forced == Some(PARAGRAPH) {
+        candidates.push(paragraph_candidate(raw, "Splitting on blank lines"));
+    }
+
+    let chosen_idx = match forced {
+        Some(name) => candidates
+            .iter()
+            .position(|c| c.format == name)
+            .unwrap_or(0),
+        None => candidates.iter().position(|c| c.score > 0.0).unwrap_or(0),
+    };
+    let chosen = candidates.remove(chosen_idx);
+    let alternatives: Vec<FormatCandidate> =
+        candidates.into_iter().filter(|c| c.score > 0.0).collect();
+
+    // A close runner-up means the text is ambiguous, which lowers confidence.
+    let runner_up = alternatives.first().map(|c| c.score).unwrap_or(0.0);
+    let margin = if chosen.score > 0.0 {
+        ((chosen.score - runner_up) / chosen.score).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    let confidence = if alternatives.is_empty() {
+        chosen.score
+    } else {
+        round2(chosen.score * (0.75 + 0.25 * margin))
+    };
+
+    Ok(Detection {
+        reason: if forced.is_some() {
+            format!("Forced by caller. {}", chosen.reason)
+        } else {
+            chosen.reason
+        },
+        format: chosen.format,
+        label: chosen.label,
+        confidence,
+        forced: forced.is_some(),
+        alternatives,
+    })
+}
+
+pub fn numbered_entries(raw: &str, format: &str) -> Option<Vec<NumberedPrompt>> {
+    Some(match format {
+        "prompt_image_video" => parse_prompt_image_video_format(raw),
+        "frame_text_version" => parse_frame_text_version_format(raw),
+        "script_placeholder" => parse_script_placeholder_format(raw),
+        "image_marker" => parse_image_marker_format(raw),
+        "image_ratio" => parse_image_ratio_format(raw),
+        "motion_prompt" => parse_motion_prompt_format(raw),
+        "image_inline" => parse_image_inline_format(raw),
+        "image_colon" => parse_image_colon_format(raw),
+        "image_newline" => parse_image_newline_format(raw),
+        "scene" => parse_scene_format(raw),
+        "flow_video" => flow_video_pairs(raw),
+        _ => return None,
+    })
+}
+
+pub fn parse_with_format(raw: &str, format: &str) -> (Vec<String>, Vec<String>) {
+    if let Some(mut entries) = numbered_entries(raw, format) {
+        entries.sort_by_key(|e| e.number);
+        let (images, videos, _) = flatten(entries);
+        return (images, videos);
+    }
+    let images = match format {
+        "json_car" => parse_json_format(raw),
+        "scene_addon" => parse_scene_addon_format(raw),
+        "prompt_addon" => parse_prompt_addon_format(raw),
+        "scene_prompt_english" => parse_scene_prompt_english_format(raw),
+        "frame_pipe_scene_prompt" => parse_frame_pipe_scene_prompt_format(raw),
+        "frame_colon" => parse_frame_colon_format(raw),
+        "inline_paren" => parse_inline_paren_format(raw),
+        "title_dash" => parse_title_dash_format(raw),
+        "numbered_paren_title" => parse_numbered_paren_title_format(raw),
+        "numbered_scene" => parse_numbered_scene_format(raw),
+        "numbered_title" => parse_numbered_title_format(raw),
+        "car_year" => parse_car_year_format(raw),
+        "title_dash_content" => parse_title_dash_content(raw),
+        "title_content" => parse_title_content_format(raw),
+        "scene_colon" => parse_scene_colon_format(raw),
+        "prompt" => parse_prompt_format(raw),
+        _ => parse_paragraph_format(raw),
+    };
+    (images, Vec::new())
+}
+
+fn flatten(entries: Vec<NumberedPrompt>) -> (Vec<String>, Vec<String>, Vec<Option<u32>>) {
+    let mut images = Vec::new();
+    let mut videos = Vec::new();
+    let mut numbers = Vec::new();
+    for entry in entries {
+        if !entry.image.is_empty() {
+            images.push(entry.image);
+            numbers.push(entry.number);
+        }
+        if let Some(video) = entry.video.filter(|v| !v.is_empty()) {
+            videos.push(video);
+        }
+    }
+    (images, videos, numbers)
+}
+
+fn split_tasks(
+    entries: &[NumberedPrompt],
+    default_ratio: Option<String>,
+    default_count: Option<u32>,
+    warnings: &mut Vec<String>,
+) -> Vec<SplitTask> {
+    entries
+        .iter()
+        .filter(|e| !e.image.is_empty())
+        .map(|e| {
+            let ratio = e
+                .aspect_ratio
+                .as_deref()
+                .and_then(|r| match AspectRatio::parse(r) {
+                    Ok(parsed) => Some(parsed.ratio().to_string()),
+                    Err(_) => {
+                        let label = e
+                            .number
+                            .map_or_else(|| "Preamble".to_string(), |n| format!("Prompt #{}", n));
+                        warnings.push(format!(
+                            "{}: unsupported ratio {} in header, using the batch default",
+                            label, r
+                        ));
+                        None
+                    }
+                });
+            SplitTask {
+                number: e.number,
+                prompt: e.image.clone(),
+                video_prompt: e.video.clone().filter(|v| !v.is_empty()),
+                ratio_from_header: ratio.is_some(),
+                count_from_header: e.count.is_some(),
+                aspect_ratio: ratio.or_else(|| default_ratio.clone()),
+                count: e.count.or(default_count),
+            }
+        })
+        .collect()
+}
+
+pub fn numbering_report(entries: &[NumberedPrompt]) -> NumberingReport {
+    let mut nums: Vec<u32> = entries.iter().filter_map(|e| e.number).collect();
+    nums.sort_unstable();
+    let mut report = NumberingReport {
+        first: nums.first().copied(),
+        last: nums.last().copied(),
+        ..Default::default()
+    };
+    for pair in nums.windows(2) {
+        let (prev, next) = (pair[0], pair[1]);
+        if next == prev && report.duplicates.last() != Some(&next) {
+            report.duplicates.push(next);
+        } else if next > prev.saturating_add(1) {
+            report.gaps.push(NumberSpan {
+                from: prev.saturating_add(1),
+                to: next - 1,
+            });
+        }
+    }
+    report
+}
+
+// Numbered formats filter and sort on the number in the source; everything else is
+// filtered by position, so "1-10" always means something.
+pub fn split_prompts(raw: &str, options: &SplitOptions) -> Result<SplitResult, String> {
+    let filter = NumberRanges::parse(options.filter.as_deref().unwrap_or(""))?;
+    let default_ratio = options
+        .default_aspect_ratio
+        .as_deref()
+        .map(AspectRatio::parse)
+        .transpose()?
+        .map(|r| r.ratio().to_string());
+    if options.default_count == Some(0) {
+        return Err("Default count must be at least 1".to_string());
+    }
+    let detection = detect_format(raw, options.format.as_deref())?;
+    let selected = |n: Option<u32>| match (&filter, n) {
+        (Some(f), Some(n)) => f.contains(n),
+        _ => true,
+    };
+
+    let (entries, numbering) = match numbered_entries(raw, &detection.format) {
+        Some(mut entries) => {
+            let report = numbering_report(&entries);
+            if !options.keep_source_order {
+                entries.sort_by_key(|e| e.number);
+            }
+            entries.retain(|e| selected(e.number));
+            (entries, Some(report))
+        }
+        None => {
+            // Only the numbered and paired formats above carry video prompts.
+            let (images, _) = parse_with_format(raw, &detection.format);
+            let entries = images
+                .into_iter()
+                .zip(1u32..)
+                .filter(|(_, n)| selected(Some(*n)))
+                .map(|(text, n)| NumberedPrompt::image(Some(n), text))
+                .collect();
+            (entries, None)
+        }
+    };
+    let mut warnings = Vec::new();
+    let tasks = split_tasks(
+        &entries,
+        default_ratio,
+        options.default_count,
+        &mut warnings,
+    );
+    let (images, videos, numbers) = flatten(entries);
+
+    Ok(SplitResult {
+        format: detection.format,
+        label: detection.label,
+        confidence: detection.confidence,
+        structured: numbering.is_some(),
+        images,
+        videos,
+        numbers: options.keep_numbers.then_some(numbers),
+        numbering,
+        tasks,
+        warnings,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // Expected splits for every detected format. The Split Prompt tab and the AutoWhisk bulk
+    // add both call split_prompts, so there is no second splitter to keep in step.
+    #[derive(serde::Deserialize)]
+    struct Case {
+        input: String,
+        format: String,
+        images: Vec<String>,
+        videos: Vec<String>,
+    }
+
+    fn corpus() -> Vec<Case> {
+        serde_json::from_str(include_str!("../../fixtures/split_corpus.json")).unwrap()
+    }
+
+    #[test]
+    fn matches_splitter_logic_corpus() {
+        for case in corpus() {
+            let result = split_prompts(&case.input, &SplitOptions::default()).unwrap();
+            assert_eq!(result.format, case.format, "input: {:?}", case.input);
+            assert_eq!(result.images, case.images, "input: {:?}", case.input);
+            assert_eq!(result.videos, case.videos, "input: {:?}", case.input);
+        }
+    }
+
+    #[test]
+    fn every_format_is_covered_by_the_corpus() {
+        let corpus = corpus();
+        for name in format_names() {
+            assert!(
+                corpus.iter().any(|c| c.format == name),
+                "no corpus case for {}",
+                name
+            );
+        }
+    }
+
+    #[test]
+    fn number_ranges() {
+        let ranges = NumberRanges::parse("1-3, 7;20-").unwrap().unwrap();
+        let kept: Vec<u32> = (1..=25).filter(|&n| ranges.contains(n)).collect();
+        assert_eq!(kept, vec![1, 2, 3, 7, 20, 21, 22, 23, 24, 25]);
+
+        let ranges = NumberRanges::parse("-5").unwrap().unwrap();
+        assert!(ranges.contains(1) && ranges.contains(5) && !ranges.contains(6));
+
+        assert!(NumberRanges::parse("").unwrap().is_none());
+        assert!(NumberRanges::parse(" , ;").unwrap().is_none());
+        assert_eq!(
+            NumberRanges::parse("5-3").unwrap_err(),
+            "Invalid number range: 5-3"
+        );
+        assert_eq!(
+            NumberRanges::parse("abc").unwrap_err(),
+            "Invalid number range: abc"
+        );
+        assert!(NumberRanges::parse("1-x").is_err());
+    }
+
+    #[test]
+    fn filters_apply_to_source_numbers() {
+        let raw = "[IMAGE 3] bird\n[IMAGE 1] cat\n[IMAGE 2] dog";
+        let options = SplitOptions {
+            filter: Some("-2".to_string()),
+            keep_numbers: true,
+            ..Default::default()
+        };
+        let result = split_prompts(raw, &options).unwrap();
+        assert_eq!(result.images, vec!["cat", "dog"]);
+        assert_eq!(result.numbers, Some(vec![Some(1), Some(2)]));
+
+        let options = SplitOptions {
+            keep_source_order: true,
+            ..Default::default()
+        };
+        let result = split_prompts(raw, &options).unwrap();
+        assert_eq!(result.images, vec!["bird", "cat", "dog"]);
+
+        let options = SplitOptions {
+            filter: Some("5-3".to_string()),
+            ..Default::default()
+        };
+        assert!(split_prompts(raw, &options).is_err());
+    }
+
+    fn numbered(numbers: &[u32]) -> Vec<NumberedPrompt> {
+        numbers
+            .iter()
+            .map(|&n| NumberedPrompt::image(Some(n), n.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn numbering_report_finds_gaps_and_duplicates() {
+        let report = numbering_report(&numbered(&[1, 2, 5, 12, 12, 12, 13]));
+        assert_eq!(report.first, Some(1));
+        assert_eq!(report.last, Some(13));
+        assert_eq!(
+            report.gaps,
+            vec![
+                NumberSpan { from: 3, to: 4 },
+                NumberSpan { from: 6, to: 11 }
+            ]
+        );
+        assert_eq!(report.duplicates, vec![12]);
+
+        let report = numbering_report(&[]);
+        assert_eq!(report.first, None);
+        assert!(report.gaps.is_empty() && report.duplicates.is_empty());
+    }
+
+    #[test]
+    fn numbering_report_handles_u32_max() {
+        let report = numbering_report(&numbered(&[u32::MAX, u32::MAX - 2, u32::MAX]));
+        assert_eq!(
+            report.gaps,
+            vec![NumberSpan {
+                from: u32::MAX - 1,
+                to: u32::MAX - 1
+            }]
+        );
+        assert_eq!(report.duplicates, vec![u32::MAX]);
+    }
+
+    #[test]
+    fn header_ratio_and_count() {
+        assert_eq!(
+            header_aspect_ratio("IMAGE PROMPT (9:16)").as_deref(),
+            Some("9:16")
+        );
+        assert_eq!(
+            header_aspect_ratio("Image 2 - 16/9").as_deref(),
+            Some("16:9")
+        );
+        assert_eq!(header_aspect_ratio("Image 3 (4x3)").as_deref(), Some("4:3"));
+        assert_eq!(
+            header_aspect_ratio("Image 1 ratio 2:1").as_deref(),
+            Some("2:1")
+        );
+        assert_eq!(header_aspect_ratio("VIDEO PROMPT (0:08)"), None);
+        assert_eq!(
+            header_aspect_ratio("Image 4 portrait").as_deref(),
+            Some("9:16")
+        );
+        assert_eq!(
+            header_aspect_ratio("Image 5 square").as_deref(),
+            Some("1:1")
+        );
+        assert_eq!(header_aspect_ratio("Image 6"), None);
+
+        assert_eq!(header_count("Image 1 x3"), Some(3));
+        assert_eq!(header_count("Image 1 (4 variations)"), Some(4));
+        assert_eq!(header_count("Image 1 count: 2"), Some(2));
+        assert_eq!(header_count("Image 1"), None);
+    }
+
+    #[test]
+    fn tasks_take_header_values_over_defaults() {
+        let raw = "Image 1 (ratio 9:16) x2\na tall tower\nImage 2 (ratio 16:9)\na wide field\nImage 3 (ratio 2:1)\na banner";
+        let options = SplitOptions {
+            default_aspect_ratio: Some("1:1".to_string()),
+            default_count: Some(4),
+            ..Default::default()
+        };
+        let result = split_prompts(raw, &options).unwrap();
+        let tasks: Vec<_> = result
+            .tasks
+            .iter()
+            .map(|t| (t.aspect_ratio.as_deref(), t.count, t.ratio_from_header))
+            .collect();
+        assert_eq!(
+            tasks,
+            vec![
+                (Some("9:16"), Some(2), true),
+                (Some("16:9"), Some(4), true),
+                (Some("1:1"), Some(4), false),
+            ]
+        );
+        assert_eq!(result.warnings.len(), 1);
+
+        let options = SplitOptions {
+            default_count: Some(0),
+            ..Default::default()
+        };
+        assert!(split_prompts(raw, &options).is_err());
+    }
+
+    #[test]
+    fn plain_line_list_is_not_structured() {
+        // Reads as "title - text" to the detector, but has no per-prompt headers, so the
+        // bulk add keeps it one task per line.
+        let result = split_prompts(
+            "A cat - sleeping\nA dog - running",
+            &SplitOptions::default(),
+        )
+        .unwrap();
+        assert_eq!(result.format, "title_dash");
+        assert!(!result.structured);
+
+        let result = split_prompts(
+            "Image 1 (ratio 9:16) x2\na tall tower\nImage 2 (ratio 16:9)\na wide field",
+            &SplitOptions::default(),
+        )
+        .unwrap();
+        assert!(result.structured);
+        assert_eq!(result.images, ["a tall tower", "a wide field"]);
+    }
+
+    #[test]
+    fn detection_scores_and_overrides() {
+        let raw =
+            "Motion Prompt 1\nzoom in slowly on the car\nMotion Prompt 2\npan left over the city";
+        let detection = detect_format(raw, None).unwrap();
+        assert_eq!(detection.format, "motion_prompt");
+        assert!(detection.confidence > 0.5);
+        assert!(!detection.forced);
+
+        let forced = detect_format(raw, Some("paragraph")).unwrap();
+        assert_eq!(forced.format, "paragraph");
+        assert!(forced.forced);
+        assert!(forced
+            .alternatives
+            .iter()
+            .any(|c| c.format == "motion_prompt"));
+
+        assert!(detect_format(raw, Some("nope")).is_err());
+
+        let plain = detect_format("just some words", None).unwrap();
+        assert_eq!(plain.format, "paragraph");
+        assert_eq!(plain.confidence, PARAGRAPH_SCORE);
+        assert!(plain.alternatives.is_empty());
+    }
+
+    #[test]
+    fn layout_formats_yield_to_labelled_ones() {
+        let case = corpus()
+            .into_iter()
+            .find(|c| c.format == "frame_text_version")
+            .unwrap();
+        let detection = detect_format(&case.input, None).unwrap();
+        assert_eq!(detection.format, "frame_text_version");
+        let title_dash = detection
+            .alternatives
+            .iter()
+            .find(|c| c.format == "title_dash")
+            .unwrap();
+        assert!(title_dash.score < 0.5);
+    }
+}
@@ -0,0 +1,481 @@
+use base64::Engine;
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use image::metadata::Orientation;
+use image::{DynamicImage, ImageDecoder, ImageFormat, ImageReader};
+use serde::{Deserialize, Serialize};
+use std::io::Cursor;
+use std::path::Path;
+
+pub const DEFAULT_MAX_EDGE: u32 = 2048;
+pub const MIN_MAX_EDGE: u32 = 256;
+pub const MAX_MAX_EDGE: u32 = 8192;
+const JPEG_QUALITY: u8 = 90;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SourceFormat {
+    Png,
+    Jpeg,
+    Webp,
+    Gif,
+    Bmp,
+    Tiff,
+    Heic,
+    Avif,
+}
+
+impl SourceFormat {
+    pub fn name(self) -> &'static str {
+        match self {
+            SourceFormat::Png => "png",
+            SourceFormat::Jpeg => "jpeg",
+            SourceFormat::Webp => "webp",
+            SourceFormat::Gif => "gif",
+            SourceFormat::Bmp => "bmp",
+            SourceFormat::Tiff => "tiff",
+            SourceFormat::Heic => "heic",
+            SourceFormat::Avif => "avif",
+        }
+    }
+
+    fn image_format(self) -> Option<ImageFormat> {
+        match self {
+            SourceFormat::Png => Some(ImageFormat::Png),
+            SourceFormat::Jpeg => Some(ImageFormat::Jpeg),
+            SourceFormat::Webp => Some(ImageFormat::WebP),
+            SourceFormat::Gif => Some(ImageFormat::Gif),
+            SourceFormat::Bmp => Some(ImageFormat::Bmp),
+            SourceFormat::Tiff => Some(ImageFormat::Tiff),
+            SourceFormat::Heic | SourceFormat::Avif => None,
+        }
+    }
+}
+
+pub fn sniff_format(bytes: &[u8]) -> Option<SourceFormat> {
+    if bytes.starts_with(b"\x89PNG\r\n\x1a\n") {
+        Some(SourceFormat::Png)
+    } else if bytes.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some(SourceFormat::Jpeg)
+    } else if bytes.len() >= 12 && &bytes[..4] == b"RIFF" && &bytes[8..12] == b"WEBP" {
+        Some(SourceFormat::Webp)
+    } else if bytes.starts_with(b"GIF87a") || bytes.starts_with(b"GIF89a") {
+        Some(SourceFormat::Gif)
+    } else if bytes.starts_with(b"BM") {
+        Some(SourceFormat::Bmp)
+    } else if bytes.starts_with(b"II*\0") || bytes.starts_with(b"MM\0*") {
+        Some(SourceFormat::Tiff)
+    } else if bytes.len() >= 12 && &bytes[4..8] == b"ftyp" {
+        match &bytes[8..12] {
+            b"avif" | b"avis" => Some(SourceFormat::Avif),
+            b"heic" | b"heix" | b"hevc" | b"hevx" | b"heim" | b"heis" | b"mif1" | b"msf1" => {
+                Some(SourceFormat::Heic)
+            }
+            _ => None,
+        }
+    } else {
+        None
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum RefCategory {
+    #[default]
+    Subject,
+    Scene,
+    Style,
+}
+
+impl RefCategory {
+    pub fn api_name(self) -> &'static str {
+        match self {
+            RefCategory::Subject => "MEDIA_CATEGORY_SUBJECT",
+            RefCategory::Scene => "MEDIA_CATEGORY_SCENE",
+            RefCategory::Style => "MEDIA_CATEGORY_STYLE",
+        }
+    }
+
+    pub fn name(self) -> &'static str {
+        match self {
+            RefCategory::Subject => "subject",
+            RefCategory::Scene => "scene",
+            RefCategory::Style => "style",
+        }
+    }
+
+    pub fn default_caption(self) -> &'static str {
+        match self {
+            RefCategory::Subject => "Reference image for Whisk",
+            RefCategory::Scene => "Scene reference for Whisk",
+            RefCategory::Style => "Style reference for Whisk",
+        }
+    }
+}
+
+// Accepts either a bare path / data URL or an object carrying category and caption.
+#[derive(Debug, Deserialize, Clone)]
+#[serde(untagged)]
+pub enum ReferenceInput {
+    Plain(String),
+    Detailed {
+        input: String,
+        #[serde(default)]
+        category: RefCategory,
+        #[serde(default)]
+        caption: Option<String>,
+    },
+}
+
+impl ReferenceInput {
+    pub fn input(&self) -> &str {
+        match self {
+            ReferenceInput::Plain(s) => s,
+            ReferenceInput::Detailed { input, .. } => input,
+        }
+    }
+
+    pub fn category(&self) -> RefCategory {
+        match self {
+            ReferenceInput::Plain(_) => RefCategory::default(),
+            ReferenceInput::Detailed { category, .. } => *category,
+        }
+    }
+
+    pub fn caption(&self) -> &str {
+        match self {
+            ReferenceInput::Detailed {
+                caption: Some(c), ..
+            } if !c.trim().is_empty() => c.trim(),
+            _ => self.category().default_caption(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct RecipeReference {
+    pub media_name: String,
+    #[serde(default)]
+    pub category: RefCategory,
+    #[serde(default)]
+    pub caption: Option<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct ReferenceError {
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl ReferenceError {
+    pub fn new(code: &'static str, message: impl Into<String>) -> Self {
+        ReferenceError {
+            code,
+            message: message.into(),
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct PreparedReference {
+    #[serde(skip)]
+    pub bytes: Vec<u8>,
+    pub mime: &'static str,
+    pub format: &'static str,
+    pub width: u32,
+    pub height: u32,
+    pub size: usize,
+    pub original_format: &'static str,
+    pub original_width: u32,
+    pub original_height: u32,
+    pub original_size: usize,
+    pub converted: bool,
+    pub resized: bool,
+    pub exif_stripped: bool,
+}
+
+fn encode(img: &DynamicImage, as_jpeg: bool) -> Result<Vec<u8>, ReferenceError> {
+    let mut buf = Vec::new();
+    if as_jpeg {
+        JpegEncoder::new_with_quality(&mut buf, JPEG_QUALITY)
+            .encode_image(&img.to_rgb8())
+            .map_err(|e| ReferenceError::new("encode_failed", e.to_string()))?;
+    } else {
+        img.write_to(&mut Cursor::new(&mut buf), ImageFormat::Png)
+            .map_err(|e| ReferenceError::new("encode_failed", e.to_string()))?;
+    }
+    Ok(buf)
+}
+
+pub fn read_input(input: &str) -> Result<Vec<u8>, ReferenceError> {
+    if input.starts_with("data:") {
+        let Some((_, b64_data)) = input.split_once(',') else {
+            return Err(ReferenceError::new(
+                "invalid_data_url",
+                "Data URL has no payload",
+            ));
+        };
+        let engine = base64::engine::general_purpose::STANDARD;
+        engine
+            .decode(b64_data.trim())
+            .map_err(|e| ReferenceError::new("invalid_data_url", e.to_string()))
+    } else if Path::new(input).exists() {
+        std::fs::read(input).map_err(|e| ReferenceError::new("read_failed", e.to_string()))
+    } else {
+        Err(ReferenceError::new(
+            "file_not_found",
+            format!("File not found: {}", input),
+        ))
+    }
+}
+
+pub fn input_label(input: &str) -> String {
+    match input.split_once(',') {
+        Some((header, _)) if input.starts_with("data:") => header.to_string(),
+        _ => input.to_string(),
+    }
+}
+
+// The image crate has no HEIC or AVIF decoder, so those go through whichever converter the
+// system has: sips ships with macOS, ImageMagick and libheif are common elsewhere.
+const SYSTEM_CONVERTERS: [(&str, &[&str]); 3] = [
+    ("sips", &["-s", "format", "png", "{in}", "--out", "{out}"]),
+    ("magick", &["{in}", "{out}"]),
+    ("heif-convert", &["{in}", "{out}"]),
+];
+
+fn convert_with_system_tool(bytes: &[u8], source: SourceFormat) -> Result<Vec<u8>, ReferenceError> {
+    let unsupported = |detail: String| {
+        ReferenceError::new(
+            "unsupported_format",
+            format!(
+                "{} images need a converter ({}); install ImageMagick or libheif, or export them as JPEG or PNG first",
+                source.name().to_uppercase(),
+                detail
+            ),
+        )
+    };
+    let stem =
+        std::env::temp_dir().join(format!("autowhisk_ref_{}", uuid::Uuid::new_v4().simple()));
+    let input = stem.with_extension(source.name());
+    let output = stem.with_extension("png");
+    std::fs::write(&input, bytes)
+        .map_err(|e| ReferenceError::new("convert_failed", e.to_string()))?;
+
+    let mut failures = Vec::new();
+    let mut converted = None;
+    for (tool, args) in SYSTEM_CONVERTERS {
+        let args: Vec<&std::ffi::OsStr> = args
+            .iter()
+            .map(|a| match *a {
+                "{in}" => input.as_os_str(),
+                "{out}" => output.as_os_str(),
+                a => std::ffi::OsStr::new(a),
+            })
+            .collect();
+        let mut command = std::process::Command::new(tool);
+        command.args(&args);
+        #[cfg(windows)]
+        {
+            use std::os::windows::process::CommandExt;
+            command.creation_flags(0x0800_0000); // CREATE_NO_WINDOW
+        }
+        match command.output() {
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+            Err(e) => failures.push(format!("{}: {}", tool, e)),
+            Ok(out) if out.status.success() => match std::fs::read(&output) {
+                Ok(png) => {
+                    converted = Some(png);
+                    break;
+                }
+                Err(e) => failures.push(format!("{}: {}", tool, e)),
+            },
+            Ok(out) => failures.push(format!(
+                "{}: {}",
+                tool,
+                String::from_utf8_lossy(&out.stderr).trim()
+            )),
+        }
+    }
+    let _ = std::fs::remove_file(&input);
+    let _ = std::fs::remove_file(&output);
+
+    converted.ok_or_else(|| {
+        if failures.is_empty() {
+            unsupported("none found".to_string())
+        } else {
+            unsupported(failures.join("; "))
+        }
+    })
+}
+
+// Whisk accepts PNG, JPEG and WebP. GIF, BMP and TIFF are converted, and the bytes are only
+// passed through untouched when no rotation, resize or EXIF removal is needed. HEIC and AVIF
+// are converted with a system tool when one is installed.
+pub fn prepare_reference(bytes: &[u8], max_edge: u32) -> Result<PreparedReference, ReferenceError> {
+    let source = sniff_format(bytes)
+        .ok_or_else(|| ReferenceError::new("unsupported_format", "Unrecognized image format"))?;
+    let system_png;
+    let (decode_bytes, format) = match source.image_format() {
+        Some(format) => (bytes, format),
+        None => {
+            system_png = convert_with_system_tool(bytes, source)?;
+            (system_png.as_slice(), ImageFormat::Png)
+        }
+    };
+
+    let mut decoder = ImageReader::with_format(Cursor::new(decode_bytes), format)
+        .into_decoder()
+        .map_err(|e| {
+            ReferenceError::new(
+                "decode_failed",
+                format!("Cannot read {}: {}", source.name(), e),
+            )
+        })?;
+    let has_exif = matches!(decoder.exif_metadata(), Ok(Some(_)));
+    let orientation = decoder.orientation().unwrap_or(Orientation::NoTransforms);
+    let mut img = DynamicImage::from_decoder(decoder).map_err(|e| {
+        ReferenceError::new(
+            "decode_failed",
+            format!("Cannot decode {}: {}", source.name(), e),
+        )
+    })?;
+    let (original_width, original_height) = (img.width(), img.height());
+
+    img.apply_orientation(orientation);
+    let max_edge = max_edge.clamp(MIN_MAX_EDGE, MAX_MAX_EDGE);
+    let resized = img.width().max(img.height()) > max_edge;
+    if resized {
+        img = img.resize(max_edge, max_edge, FilterType::Lanczos3);
+    }
+
+    let passthrough = matches!(
+        source,
+        SourceFormat::Png | SourceFormat::Jpeg | SourceFormat::Webp
+    ) && !resized
+        && !has_exif
+        && orientation == Orientation::NoTransforms;
+
+    let (out, out_format) = if passthrough {
+        (bytes.to_vec(), source)
+    } else {
+        let as_jpeg = match source {
+            SourceFormat::Jpeg => true,
+            SourceFormat::Png => false,
+            _ => !img.color().has_alpha(),
+        };
+        let out_format = if as_jpeg {
+            SourceFormat::Jpeg
+        } else {
+            SourceFormat::Png
+        };
+        (encode(&img, as_jpeg)?, out_format)
+    };
+
+    Ok(PreparedReference {
+        mime: match out_format {
+            SourceFormat::Jpeg => "image/jpeg",
+            SourceFormat::Webp => "image/webp",
+            _ => "image/png",
+        },
+        format: out_format.name(),
+        width: img.width(),
+        height: img.height(),
+        size: out.len(),
+        original_format: source.name(),
+        original_width,
+        original_height,
+        original_size: bytes.len(),
+        converted: out_format != source,
+        resized,
+        exif_stripped: has_exif,
+        bytes: out,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{Rgb, RgbImage, Rgba, RgbaImage};
+
+    fn encoded(img: DynamicImage, format: ImageFormat) -> Vec<u8> {
+        let mut buf = Vec::new();
+        img.write_to(&mut Cursor::new(&mut buf), format).unwrap();
+        buf
+    }
+
+    fn rgb(w: u32, h: u32) -> DynamicImage {
+        DynamicImage::ImageRgb8(RgbImage::from_pixel(w, h, Rgb([200, 40, 40])))
+    }
+
+    #[test]
+    fn sniffs_magic_bytes() {
+        assert_eq!(
+            sniff_format(&encoded(rgb(4, 4), ImageFormat::Png)),
+            Some(SourceFormat::Png)
+        );
+        assert_eq!(
+            sniff_format(&encoded(rgb(4, 4), ImageFormat::Jpeg)),
+            Some(SourceFormat::Jpeg)
+        );
+        assert_eq!(sniff_format(b"GIF89a...."), Some(SourceFormat::Gif));
+        assert_eq!(
+            sniff_format(b"\0\0\0\x18ftypheic\0\0\0\0"),
+            Some(SourceFormat::Heic)
+        );
+        assert_eq!(
+            sniff_format(b"\0\0\0\x18ftypavif\0\0\0\0"),
+            Some(SourceFormat::Avif)
+        );
+        assert_eq!(sniff_format(b"not an image"), None);
+    }
+
+    #[test]
+    fn heic_and_unknown_bytes_are_unsupported() {
+        let err = prepare_reference(b"\0\0\0\x18ftypheic\0\0\0\0", DEFAULT_MAX_EDGE).unwrap_err();
+        assert_eq!(err.code, "unsupported_format");
+        assert!(err.message.starts_with("HEIC images need a converter"));
+        let err = prepare_reference(b"garbage", DEFAULT_MAX_EDGE).unwrap_err();
+        assert_eq!(err.code, "unsupported_format");
+    }
+
+    #[test]
+    fn small_png_passes_through() {
+        let bytes = encoded(rgb(32, 16), ImageFormat::Png);
+        let prepared = prepare_reference(&bytes, DEFAULT_MAX_EDGE).unwrap();
+        assert_eq!(prepared.bytes, bytes);
+        assert_eq!(prepared.mime, "image/png");
+        assert!(!prepared.converted && !prepared.resized && !prepared.exif_stripped);
+    }
+
+    #[test]
+    fn max_edge_is_clamped_to_settings_range() {
+        let bytes = encoded(rgb(600, 300), ImageFormat::Jpeg);
+        let prepared = prepare_reference(&bytes, 10).unwrap();
+        assert!(prepared.resized);
+        assert_eq!(
+            (prepared.width, prepared.height),
+            (MIN_MAX_EDGE, MIN_MAX_EDGE / 2)
+        );
+        assert_eq!(prepared.format, "jpeg");
+
+        let prepared = prepare_reference(&bytes, u32::MAX).unwrap();
+        assert!(!prepared.resized);
+    }
+
+    #[test]
+    fn other_formats_are_converted() {
+        let bmp = encoded(rgb(8, 8), ImageFormat::Bmp);
+        let prepared = prepare_reference(&bmp, DEFAULT_MAX_EDGE).unwrap();
+        assert!(prepared.converted);
+        assert_eq!((prepared.original_format, prepared.format), ("bmp", "jpeg"));
+
+        let gif = encoded(
+            DynamicImage::ImageRgba8(RgbaImage::from_pixel(8, 8, Rgba([0, 0, 0, 0]))),
+            ImageFormat::Gif,
+        );
+        let prepared = prepare_reference(&gif, DEFAULT_MAX_EDGE).unwrap();
+        assert_eq!(prepared.mime, "image/png");
+    }
+}
@@ -0,0 +1,184 @@
+use crate::lint::LintOptions;
+use crate::output::SaveOptions;
+use crate::presets::StylePreset;
+use crate::reference;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+const MAX_OUTPUT_FOLDERS: usize = 50;
+
+// Parsed once and refreshed on save; hot paths like the thumbnail protocol read it
+// on every request.
+static CACHE: Mutex<Option<Settings>> = Mutex::new(None);
+// Serialises read-modify-write updates so concurrent commands don't drop each other's changes.
+static LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct Settings {
+    #[serde(default)]
+    pub filename_template: Option<String>,
+    #[serde(default)]
+    pub save_options: Option<SaveOptions>,
+    #[serde(default)]
+    pub library_enabled: Option<bool>,
+    #[serde(default)]
+    pub thumbnail_sizes: Option<Vec<u32>>,
+    #[serde(default)]
+    pub reference_max_edge: Option<u32>,
+    #[serde(default)]
+    pub style_presets: Option<Vec<StylePreset>>,
+    #[serde(default)]
+    pub lint: Option<LintOptions>,
+    #[serde(default)]
+    pub output_folders: Option<Vec<String>>,
+}
+
+#[cfg(not(test))]
+pub fn app_data_dir() -> PathBuf {
+    let exe = std::env::current_exe().unwrap_or_default();
+    exe.parent()
+        .unwrap_or(std::path::Path::new("."))
+        .to_path_buf()
+}
+
+// Tests get a per-process scratch folder so they never touch the real settings, stores
+// and databases next to the binary.
+#[cfg(test)]
+pub fn app_data_dir() -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("autowhisk_test_{}", std::process::id()));
+    let _ = fs::create_dir_all(&dir);
+    dir
+}
+
+fn get_settings_path() -> PathBuf {
+    app_data_dir().join("settings.json")
+}
+
+fn read_settings_file() -> Settings {
+    let path = get_settings_path();
+    if !path.exists() {
+        return Settings::default();
+    }
+    match fs::read_to_string(&path) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
+        Err(_) => Settings::default(),
+    }
+}
+
+pub fn load_settings() -> Settings {
+    let mut cache = CACHE.lock().unwrap_or_else(|e| e.into_inner());
+    cache.get_or_insert_with(read_settings_file).clone()
+}
+
+fn save_settings(settings: &Settings) -> Result<(), String> {
+    let path = get_settings_path();
+    let json = serde_json::to_string_pretty(settings).map_err(|e| e.to_string())?;
+    fs::write(&path, json).map_err(|e| format!("Failed to save: {}", e))?;
+    *CACHE.lock().unwrap_or_else(|e| e.into_inner()) = Some(settings.clone());
+    Ok(())
+}
+
+fn update_store<T>(f: impl FnOnce(&mut Settings) -> Result<T, String>) -> Result<T, String> {
+    let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut settings = load_settings();
+    let out = f(&mut settings)?;
+    save_settings(&settings)?;
+    Ok(out)
+}
+
+// Folders the user picked or generated into; the thumbnail protocol only serves files
+// under these and the library.
+pub fn remember_output_folder(folder: &str) -> Result<(), String> {
+    let folder = folder.trim();
+    if folder.is_empty() {
+        return Ok(());
+    }
+    if load_settings()
+        .output_folders
+        .is_some_and(|folders| folders.iter().any(|f| f == folder))
+    {
+        return Ok(());
+    }
+    update_store(|settings| {
+        let folders = settings.output_folders.get_or_insert_with(Vec::new);
+        if !folders.iter().any(|f| f == folder) {
+            folders.push(folder.to_string());
+            if folders.len() > MAX_OUTPUT_FOLDERS {
+                folders.remove(0);
+            }
+        }
+        Ok(())
+    })
+}
+
+pub fn get_settings() -> Result<Value, String> {
+    serde_json::to_value(load_settings()).map_err(|e| e.to_string())
+}
+
+pub fn update_settings(patch: Value) -> Result<Value, String> {
+    let Value::Object(patch) = patch else {
+        return Err("Settings patch must be a JSON object".to_string());
+    };
+
+    update_store(|store| {
+        let mut current = serde_json::to_value(&*store).map_err(|e| e.to_string())?;
+        if let Value::Object(map) = &mut current {
+            for (key, value) in patch {
+                map.insert(key, value);
+            }
+        }
+
+        let settings: Settings =
+            serde_json::from_value(current).map_err(|e| format!("Invalid settings: {}", e))?;
+        if let Some(template) = &settings.filename_template {
+            crate::filename::validate_template(template)?;
+        }
+        if let Some(opts) = &settings.save_options {
+            opts.validate()?;
+        }
+        if let Some(sizes) = &settings.thumbnail_sizes {
+            if sizes.iter().any(|s| !(32..=1024).contains(s)) {
+                return Err("Thumbnail sizes must be between 32 and 1024".to_string());
+            }
+        }
+        if let Some(edge) = settings.reference_max_edge {
+            if !(reference::MIN_MAX_EDGE..=reference::MAX_MAX_EDGE).contains(&edge) {
+                return Err(format!(
+                    "Reference max edge must be between {} and {}",
+                    reference::MIN_MAX_EDGE,
+                    reference::MAX_MAX_EDGE
+                ));
+            }
+        }
+        if let Some(presets) = &settings.style_presets {
+            crate::presets::validate_presets(presets)?;
+        }
+        if let Some(lint) = &settings.lint {
+            lint.validate()?;
+        }
+        *store = settings;
+        serde_json::to_value(&*store).map_err(|e| e.to_string())
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn concurrent_updates_keep_every_folder() {
+        let tag = uuid::Uuid::new_v4().simple().to_string();
+        let folders: Vec<String> = (0..8).map(|i| format!("/tmp/{}/{}", tag, i)).collect();
+        std::thread::scope(|scope| {
+            for folder in &folders {
+                scope.spawn(move || remember_output_folder(folder).unwrap());
+            }
+        });
+        let saved = read_settings_file().output_folders.unwrap_or_default();
+        assert!(folders.iter().all(|f| saved.contains(f)));
+    }
+}
@@ -0,0 +1,607 @@
+use crate::settings::app_data_dir;
+use crate::util::re;
+use rand::distributions::{Distribution, WeightedIndex};
+use rand::rngs::StdRng;
+use rand::SeedableRng;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+const DEFAULT_LIMIT: usize = 500;
+const MAX_LIMIT: usize = 10_000;
+const MAX_DEPTH: usize = 8;
+
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "lowercase")]
+pub enum ExpandMode {
+    #[default]
+    Cartesian,
+    Zip,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateOptions {
+    pub template: String,
+    #[serde(default)]
+    pub csv_path: Option<String>,
+    #[serde(default)]
+    pub csv_text: Option<String>,
+    #[serde(default)]
+    pub variables: Option<Vec<TemplateVariable>>,
+    #[serde(default)]
+    pub mode: ExpandMode,
+    #[serde(default)]
+    pub seed: Option<u64>,
+    #[serde(default)]
+    pub variations: Option<u32>,
+    #[serde(default)]
+    pub limit: Option<usize>,
+    #[serde(default)]
+    pub wildcards_dir: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplateVariable {
+    pub name: String,
+    pub values: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplatePreview {
+    pub prompts: Vec<String>,
+    pub count: usize,
+    pub truncated: bool,
+    pub seed: u64,
+    pub mode: ExpandMode,
+    pub variables: Vec<String>,
+    pub missing_variables: Vec<String>,
+    pub missing_wildcards: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct TemplatePage {
+    pub prompts: Vec<String>,
+    pub offset: usize,
+    pub next_offset: Option<usize>,
+    pub count: usize,
+    pub seed: u64,
+    pub missing_variables: Vec<String>,
+    pub missing_wildcards: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+enum Part {
+    Text(String),
+    Var(String),
+    Choice(Vec<(f64, String)>),
+    Wildcard(String),
+}
+
+// `{name}` is a variable, `{a|2::b}` a weighted inline choice and `__name__` a line from
+// wildcards/name.txt. Braces that are neither (JSON, say) stay as text.
+fn parse_template(template: &str) -> Vec<Part> {
+    let token = re!(r"\{([^{}]*)\}|__([A-Za-z0-9_\-/]+?)__");
+    let var_name = re!(r"^\s*[A-Za-z_][A-Za-z0-9_ \-]*\s*$");
+    let mut parts = Vec::new();
+    let mut last = 0;
+    for caps in token.captures_iter(template) {
+        let Some(whole) = caps.get(0) else { continue };
+        let part = if let Some(name) = caps.get(2) {
+            Part::Wildcard(name.as_str().to_string())
+        } else {
+            let inner = caps.get(1).map(|m| m.as_str()).unwrap_or("");
+            if inner.contains('|') {
+                Part::Choice(inner.split('|').map(weighted).collect())
+            } else if var_name.is_match(inner) {
+                Part::Var(inner.trim().to_string())
+            } else {
+                continue;
+            }
+        };
+        if whole.start() > last {
+            parts.push(Part::Text(template[last..whole.start()].to_string()));
+        }
+        parts.push(part);
+        last = whole.end();
+    }
+    if last < template.len() {
+        parts.push(Part::Text(template[last..].to_string()));
+    }
+    parts
+}
+
+// "3::red" has weight 3; anything without a valid prefix weighs 1.
+fn weighted(option: &str) -> (f64, String) {
+    if let Some((w, text)) = option.split_once("::") {
+        if let Ok(w) = w.trim().parse::<f64>() {
+            if w.is_finite() && w >= 0.0 {
+                return (w, text.trim().to_string());
+            }
+        }
+    }
+    (1.0, option.trim().to_string())
+}
+
+fn split_csv_line(line: &str, delimiter: char) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+    while let Some(c) = chars.next() {
+        match c {
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            c if c == delimiter && !quoted => fields.push(std::mem::take(&mut field)),
+            c => field.push(c),
+        }
+    }
+    fields.push(field);
+    fields.into_iter().map(|f| f.trim().to_string()).collect()
+}
+
+// Quoted fields may span lines, so records are rebuilt before splitting.
+fn csv_records(text: &str) -> Vec<String> {
+    let mut records = Vec::new();
+    let mut current = String::new();
+    for line in text.lines() {
+        if !current.is_empty() {
+            current.push('\n');
+        }
+        current.push_str(line);
+        if current.matches('"').count().is_multiple_of(2) {
+            records.push(std::mem::take(&mut current));
+        }
+    }
+    if !current.is_empty() {
+        records.push(current);
+    }
+    records
+}
+
+// First row names the variables; each later row is one set of values.
+pub fn parse_csv(text: &str) -> Result<Vec<TemplateVariable>, String> {
+    let text = text.trim_start_matches('\u{feff}');
+    let records: Vec<String> = csv_records(text)
+        .into_iter()
+        .filter(|r| !r.trim().is_empty())
+        .collect();
+    let Some(header) = records.first() else {
+        return Err("CSV has no header row".to_string());
+    };
+    let delimiter = [',', ';', '\t']
+        .into_iter()
+        .max_by_key(|d| header.matches(*d).count())
+        .unwrap_or(',');
+    let names = split_csv_line(header, delimiter);
+    if names.iter().any(|n| n.is_empty()) {
+        return Err("CSV header has an empty column name".to_string());
+    }
+    let mut columns: Vec<TemplateVariable> = names
+        .into_iter()
+        .map(|name| TemplateVariable {
+            name,
+            values: Vec::new(),
+        })
+        .collect();
+    for record in &records[1..] {
+        let fields = split_csv_line(record, delimiter);
+        for (i, column) in columns.iter_mut().enumerate() {
+            column
+                .values
+                .push(fields.get(i).cloned().unwrap_or_default());
+        }
+    }
+    Ok(columns)
+}
+
+fn wildcards_root(custom: Option<&str>) -> PathBuf {
+    match custom.filter(|d| !d.trim().is_empty()) {
+        Some(dir) => PathBuf::from(dir),
+        None => app_data_dir().join("wildcards"),
+    }
+}
+
+fn load_wildcard(root: &Path, name: &str) -> Option<Vec<(f64, String)>> {
+    if name.split('/').any(|seg| seg.is_empty() || seg == "..") {
+        return None;
+    }
+    let content = fs::read_to_string(root.join(format!("{}.txt", name))).ok()?;
+    let entries: Vec<(f64, String)> = content
+        .lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(weighted)
+        .collect();
+    if entries.is_empty() {
+        None
+    } else {
+        Some(entries)
+    }
+}
+
+struct Renderer {
+    rng: StdRng,
+    root: PathBuf,
+    wildcards: HashMap<String, Option<Vec<(f64, String)>>>,
+    missing_variables: Vec<String>,
+    missing_wildcards: Vec<String>,
+}
+
+impl Renderer {
+    fn pick(&mut self, options: &[(f64, String)]) -> String {
+        match WeightedIndex::new(options.iter().map(|(w, _)| *w)) {
+            Ok(dist) => options[dist.sample(&mut self.rng)].1.clone(),
+            // All weights zero: fall back to the first option rather than failing.
+            Err(_) => options.first().map(|(_, t)| t.clone()).unwrap_or_default(),
+        }
+    }
+
+    fn render(&mut self, template: &str, values: &HashMap<String, String>, depth: usize) -> String {
+        if depth > MAX_DEPTH {
+            return template.to_string();
+        }
+        let mut out = String::new();
+        for part in parse_template(template) {
+            match part {
+                Part::Text(text) => out.push_str(&text),
+                Part::Var(name) => match values.get(&name) {
+                    Some(value) => out.push_str(value),
+                    None => {
+                        if !self.missing_variables.contains(&name) {
+                            self.missing_variables.push(name.clone());
+                        }
+                        out.push_str(&format!("{{{}}}", name));
+                    }
+                },
+                Part::Choice(options) => {
+                    let choice = self.pick(&options);
+                    out.push_str(&self.render(&choice, values, depth + 1));
+                }
+                Part::Wildcard(name) => {
+                    let root = self.root.clone();
+                    let entries = self
+                        .wildcards
+                        .entry(name.clone())
+                        .or_insert_with(|| load_wildcard(&root, &name))
+                        .clone();
+                    match entries {
+                        Some(entries) => {
+                            let choice = self.pick(&entries);
+                            out.push_str(&self.render(&choice, values, depth + 1));
+                        }
+                        None => {
+                            if !self.missing_wildcards.contains(&name) {
+                                self.missing_wildcards.push(name.clone());
+                            }
+                            out.push_str(&format!("__{}__", name));
+                        }
+                    }
+                }
+            }
+        }
+        out
+    }
+}
+
+fn used_variables(template: &str) -> Vec<String> {
+    let mut names = Vec::new();
+    for part in parse_template(template) {
+        if let Part::Var(name) = part {
+            if !names.contains(&name) {
+                names.push(name);
+            }
+        }
+    }
+    names
+}
+
+fn dedup(values: &[String]) -> Vec<String> {
+    let mut out: Vec<String> = Vec::new();
+    for v in values {
+        if !out.contains(v) {
+            out.push(v.clone());
+        }
+    }
+    out
+}
+
+type ComboAt<'a> = Box<dyn Fn(usize) -> HashMap<String, String> + 'a>;
+
+// Value sets to render with. Cartesian crosses the distinct values of every variable the
+// template uses; zip walks the columns side by side, like the rows of a CSV.
+fn combinations<'a>(
+    variables: &'a [TemplateVariable],
+    mode: ExpandMode,
+    warnings: &mut Vec<String>,
+) -> (usize, ComboAt<'a>) {
+    match mode {
+        ExpandMode::Cartesian => {
+            let lists: Vec<(String, Vec<String>)> = variables
+                .iter()
+                .map(|v| (v.name.clone(), dedup(&v.values)))
+                .filter(|(_, values)| !values.is_empty())
+                .collect();
+            let total = lists
+                .iter()
+                .try_fold(1usize, |acc, (_, values)| acc.checked_mul(values.len()))
+                .unwrap_or(usize::MAX);
+            let at = move |mut index: usize| {
+                let mut combo = HashMap::new();
+                for (name, values) in lists.iter().rev() {
+                    combo.insert(name.clone(), values[index % values.len()].clone());
+                    index /= values.len();
+                }
+                combo
+            };
+            (total, Box::new(at))
+        }
+        ExpandMode::Zip => {
+            let lengths: Vec<usize> = variables.iter().map(|v| v.values.len()).collect();
+            let total = lengths.iter().copied().min().unwrap_or(1);
+            if lengths.iter().any(|&l| l != total) {
+                warnings.push(format!(
+                    "Variables have different lengths; zipping stops after {} rows",
+                    total
+                ));
+            }
+            let at = move |index: usize| {
+                variables
+                    .iter()
+                    .map(|v| (v.name.clone(), v.values[index].clone()))
+                    .collect()
+            };
+            (total, Box::new(at))
+        }
+    }
+}
+
+// Inputs resolved once and shared by the preview and the paged expansion.
+struct Expansion {
+    variables: Vec<TemplateVariable>,
+    used: Vec<String>,
+    warnings: Vec<String>,
+    variations: usize,
+    seed: u64,
+}
+
+fn prepare(options: &TemplateOptions) -> Result<Expansion, String> {
+    if options.template.trim().is_empty() {
+        return Err("Template is empty".to_string());
+    }
+
+    let mut variables: Vec<TemplateVariable> = Vec::new();
+    if let Some(path) = options.csv_path.as_deref().filter(|p| !p.trim().is_empty()) {
+        let text = fs::read_to_string(path).map_err(|e| format!("Cannot read CSV: {}", e))?;
+        variables.extend(parse_csv(&text)?);
+    }
+    if let Some(text) = options.csv_text.as_deref().filter(|t| !t.trim().is_empty()) {
+        variables.extend(parse_csv(text)?);
+    }
+    // Inline variables override CSV columns of the same name.
+    for var in options.variables.clone().unwrap_or_default() {
+        variables.retain(|v| v.name != var.name);
+        variables.push(var);
+    }
+
+    let used = used_variables(&options.template);
+    let mut warnings = Vec::new();
+    let unused: Vec<&str> = variables
+        .iter()
+        .filter(|v| !used.contains(&v.name))
+        .map(|v| v.name.as_str())
+        .collect();
+    if !unused.is_empty() {
+        warnings.push(format!("Not used by the template: {}", unused.join(", ")));
+    }
+    // Unused columns would only multiply or truncate the output.
+    variables.retain(|v| used.contains(&v.name));
+
+    Ok(Expansion {
+        variables,
+        used,
+        warnings,
+        variations: options.variations.unwrap_or(1).max(1) as usize,
+        // Random seeds stay below 2^32 so the frontend can round-trip them as numbers.
+        seed: options.seed.unwrap_or_else(|| rand::random::<u32>() as u64),
+    })
+}
+
+// Adding the index would hand neighbouring seeds shifted copies of the same streams
+// (seed 1 prompt 0 == seed 0 prompt 1), so the index is spread with the golden-ratio
+// constant before mixing it in.
+fn prompt_seed(seed: u64, index: usize) -> u64 {
+    seed ^ (index as u64).wrapping_mul(0x9E37_79B9_7F4A_7C15)
+}
+
+// Renders prompts `start..start + len` of the full expansion. Every prompt gets its own
+// RNG derived from the seed and its index, so any page can be produced without rendering
+// the ones before it.
+fn render_range(
+    options: &TemplateOptions,
+    expansion: &mut Expansion,
+    start: usize,
+    len: usize,
+) -> (Vec<String>, usize, Renderer) {
+    let (combos, combo_at) =
+        combinations(&expansion.variables, options.mode, &mut expansion.warnings);
+    let count = combos.saturating_mul(expansion.variations);
+    let mut renderer = Renderer {
+        rng: StdRng::seed_from_u64(expansion.seed),
+        root: wildcards_root(options.wildcards_dir.as_deref()),
+        wildcards: HashMap::new(),
+        missing_variables: Vec::new(),
+        missing_wildcards: Vec::new(),
+    };
+
+    let end = start.saturating_add(len).min(count);
+    let mut prompts = Vec::new();
+    let mut current = None;
+    let mut values = HashMap::new();
+    for index in start..end {
+        let combo_index = index / expansion.variations;
+        if current != Some(combo_index) {
+            values = combo_at(combo_index);
+            current = Some(combo_index);
+        }
+        renderer.rng = StdRng::seed_from_u64(prompt_seed(expansion.seed, index));
+        let prompt = renderer.render(&options.template, &values, 0);
+        prompts.push(collapse_spaces(&prompt));
+    }
+    (prompts, count, renderer)
+}
+
+pub fn preview_template(options: &TemplateOptions) -> Result<TemplatePreview, String> {
+    let mut expansion = prepare(options)?;
+    let limit = options.limit.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let (prompts, count, renderer) = render_range(options, &mut expansion, 0, limit);
+
+    Ok(TemplatePreview {
+        truncated: prompts.len() < count,
+        prompts,
+        count,
+        seed: expansion.seed,
+        mode: options.mode,
+        variables: expansion.used,
+        missing_variables: renderer.missing_variables,
+        missing_wildcards: renderer.missing_wildcards,
+        warnings: expansion.warnings,
+    })
+}
+
+// Batch entry point: no cap on the total, the caller walks `nextOffset` until it is null
+// and queues each page for generation. Pass back the returned seed to keep pages stable.
+pub fn expand_template(
+    options: &TemplateOptions,
+    offset: usize,
+    page_size: Option<usize>,
+) -> Result<TemplatePage, String> {
+    let mut expansion = prepare(options)?;
+    let page_size = page_size.unwrap_or(DEFAULT_LIMIT).clamp(1, MAX_LIMIT);
+    let (prompts, count, renderer) = render_range(options, &mut expansion, offset, page_size);
+    let next = offset.saturating_add(prompts.len());
+
+    Ok(TemplatePage {
+        prompts,
+        offset,
+        next_offset: (next < count).then_some(next),
+        count,
+        seed: expansion.seed,
+        missing_variables: renderer.missing_variables,
+        missing_wildcards: renderer.missing_wildcards,
+        warnings: expansion.warnings,
+    })
+}
+
+// Empty values leave doubled spaces and dangling commas; tidy those without touching
+// line breaks the user typed.
+fn collapse_spaces(prompt: &str) -> String {
+    let prompt = re!(r"[ \t]{2,}").replace_all(prompt, " ");
+    let prompt = re!(r"\s+,").replace_all(&prompt, ",");
+    prompt.trim().to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn options(template: &str) -> TemplateOptions {
+        TemplateOptions {
+            template: template.to_string(),
+            csv_text: Some("character,location\nknight,castle\n\"wizard, old\",forest\n".into()),
+            seed: Some(42),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn cartesian_and_zip_counts() {
+        let mut o = options("{character} in {location}");
+        let p = preview_template(&o).unwrap();
+        assert_eq!(p.count, 4);
+        assert_eq!(p.prompts[0], "knight in castle");
+        assert_eq!(p.prompts[3], "wizard, old in forest");
+
+        o.mode = ExpandMode::Zip;
+        o.variations = Some(2);
+        let p = preview_template(&o).unwrap();
+        assert_eq!(p.count, 4);
+        assert_eq!(p.prompts[2], "wizard, old in forest");
+    }
+
+    #[test]
+    fn reports_missing_variables_and_keeps_json_braces() {
+        let p = preview_template(&options("{missing} __nope__ {\"json\": 1}")).unwrap();
+        assert_eq!(p.missing_variables, vec!["missing"]);
+        assert_eq!(p.missing_wildcards, vec!["nope"]);
+        assert!(p.prompts[0].ends_with("{\"json\": 1}"));
+    }
+
+    #[test]
+    fn preview_is_capped_but_expand_pages_past_it() {
+        let values: Vec<String> = (0..200).map(|i| i.to_string()).collect();
+        let o = TemplateOptions {
+            template: "{a}-{b} {x|y|z}".to_string(),
+            variables: Some(vec![
+                TemplateVariable {
+                    name: "a".into(),
+                    values: values.clone(),
+                },
+                TemplateVariable {
+                    name: "b".into(),
+                    values,
+                },
+            ]),
+            seed: Some(7),
+            limit: Some(usize::MAX),
+            ..Default::default()
+        };
+        let preview = preview_template(&o).unwrap();
+        assert_eq!(preview.count, 40_000);
+        assert_eq!(preview.prompts.len(), MAX_LIMIT);
+        assert!(preview.truncated);
+
+        let first = expand_template(&o, 0, Some(1_000)).unwrap();
+        assert_eq!(first.prompts[..], preview.prompts[..1_000]);
+        assert_eq!(first.next_offset, Some(1_000));
+
+        let page = expand_template(&o, 25_000, Some(1_000)).unwrap();
+        assert_eq!(page.prompts.len(), 1_000);
+        assert!(page.prompts[0].starts_with("125-0 "));
+
+        let last = expand_template(&o, 39_990, Some(1_000)).unwrap();
+        assert_eq!(last.prompts.len(), 10);
+        assert_eq!(last.next_offset, None);
+        assert!(last.prompts[9].starts_with("199-199 "));
+        assert!(expand_template(&o, 50_000, None)
+            .unwrap()
+            .prompts
+            .is_empty());
+    }
+
+    #[test]
+    fn neighbouring_seeds_are_not_shifted_copies() {
+        let run = |seed| {
+            preview_template(&TemplateOptions {
+                template: "{1|2|3|4|5|6|7|8|9}{1|2|3|4|5|6|7|8|9}{1|2|3|4|5|6|7|8|9}".into(),
+                seed: Some(seed),
+                variations: Some(20),
+                ..Default::default()
+            })
+            .unwrap()
+            .prompts
+        };
+        let (zero, one) = (run(0), run(1));
+        assert_eq!(zero, run(0));
+        assert_ne!(zero[1..], one[..19]);
+        assert_ne!(zero, one);
+    }
+}
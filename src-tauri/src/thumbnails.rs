@@ -0,0 +1,294 @@
+use crate::library::library_dir;
+use crate::manifest::sha256_hex;
+use crate::settings::{self, app_data_dir};
+use image::codecs::jpeg::JpegEncoder;
+use image::imageops::FilterType;
+use serde_json::{json, Value};
+use std::fs::File;
+use std::path::{Path, PathBuf};
+use std::time::{Duration, SystemTime};
+use tokio::sync::Semaphore;
+
+pub const SCHEME: &str = "whisk-thumb";
+
+const DEFAULT_SIZES: [u32; 3] = [128, 256, 512];
+const MAX_AGE: Duration = Duration::from_secs(14 * 24 * 3600);
+const MAX_CACHE_BYTES: u64 = 512 * 1024 * 1024;
+const CLEANUP_INTERVAL: Duration = Duration::from_secs(6 * 3600);
+
+// A gallery opening 200 tiles queues here instead of decoding 200 full-size images at
+// once; cache hits only hold a slot for a file read.
+static DECODE_SLOTS: Semaphore = Semaphore::const_new(4);
+
+fn cache_dir() -> PathBuf {
+    app_data_dir().join("thumbnails")
+}
+
+// Snaps the request up to a configured size so the cache holds a few variants per image.
+fn resolve_size(requested: u32) -> u32 {
+    let mut sizes = settings::load_settings()
+        .thumbnail_sizes
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| DEFAULT_SIZES.to_vec());
+    sizes.sort_unstable();
+    sizes
+        .iter()
+        .copied()
+        .find(|&s| s >= requested)
+        .unwrap_or_else(|| *sizes.last().unwrap_or(&requested))
+        .clamp(32, 1024)
+}
+
+// Only files under a configured output folder or the library may be thumbnailed, so a
+// page cannot use the protocol to read arbitrary images off the disk.
+pub fn is_allowed(path: &Path) -> bool {
+    let Ok(path) = path.canonicalize() else {
+        return false;
+    };
+    settings::load_settings()
+        .output_folders
+        .unwrap_or_default()
+        .iter()
+        .map(PathBuf::from)
+        .chain(std::iter::once(library_dir()))
+        .filter_map(|root| root.canonicalize().ok())
+        .any(|root| path.starts_with(root))
+}
+
+fn cache_key(path: &Path, size: u32) -> Result<String, String> {
+    let meta = std::fs::metadata(path).map_err(|e| format!("{}: {}", path.display(), e))?;
+    let mtime = meta
+        .modified()
+        .ok()
+        .and_then(|t| t.duration_since(SystemTime::UNIX_EPOCH).ok())
+        .unwrap_or_default()
+        .as_nanos();
+    let raw = format!("{}|{}|{}|{}", path.display(), mtime, meta.len(), size);
+    Ok(sha256_hex(raw.as_bytes()))
+}
+
+fn touch(path: &Path) {
+    if let Ok(file) = File::options().write(true).open(path) {
+        let _ = file.set_modified(SystemTime::now());
+    }
+}
+
+pub fn ensure_thumbnail(path: &str, size: u32) -> Result<PathBuf, String> {
+    let source = Path::new(path);
+    let size = resolve_size(size);
+    let key = cache_key(source, size)?;
+    let dir = cache_dir();
+    let thumb_path = dir.join(format!("{}.jpg", key));
+
+    if thumb_path.exists() {
+        touch(&thumb_path);
+        return Ok(thumb_path);
+    }
+
+    let img = image::open(source).map_err(|e| format!("{}: {}", path, e))?;
+    let small = img.resize(size, size, FilterType::Triangle).to_rgb8();
+
+    let mut buf = Vec::new();
+    JpegEncoder::new_with_quality(&mut buf, 82)
+        .encode_image(&small)
+        .map_err(|e| e.to_string())?;
+
+    std::fs::create_dir_all(&dir).map_err(|e| e.to_string())?;
+    // Write to a temp name first so a concurrent reader never sees a half-written file.
+    let tmp = dir.join(format!("{}.{}.tmp", key, uuid::Uuid::new_v4().simple()));
+    std::fs::write(&tmp, &buf).map_err(|e| e.to_string())?;
+    std::fs::rename(&tmp, &thumb_path).map_err(|e| {
+        let _ = std::fs::remove_file(&tmp);
+        e.to_string()
+    })?;
+    Ok(thumb_path)
+}
+
+fn percent_encode(s: &str) -> String {
+    s.bytes()
+        .map(|b| match b {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                (b as char).to_string()
+            }
+            _ => format!("%{:02X}", b),
+        })
+        .collect()
+}
+
+fn percent_decode(s: &str) -> String {
+    let bytes = s.as_bytes();
+    let mut out = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        if bytes[i] == b'%' && i + 2 < bytes.len() {
+            let hex = std::str::from_utf8(&bytes[i + 1..i + 3]).unwrap_or("");
+            if let Ok(b) = u8::from_str_radix(hex, 16) {
+                out.push(b);
+                i += 3;
+                continue;
+            }
+        }
+        out.push(bytes[i]);
+        i += 1;
+    }
+    String::from_utf8_lossy(&out).to_string()
+}
+
+pub fn url_for(path: &str, size: u32) -> String {
+    let base = if cfg!(windows) {
+        format!("http://{}.localhost", SCHEME)
+    } else {
+        format!("{}://localhost", SCHEME)
+    };
+    format!("{}/{}?path={}", base, size, percent_encode(path))
+}
+
+pub fn get_thumbnail(path: &str, size: u32) -> Result<Value, String> {
+    if !is_allowed(Path::new(path)) {
+        return Err(format!(
+            "Not inside an output folder or the library: {}",
+            path
+        ));
+    }
+    let thumb = ensure_thumbnail(path, size)?;
+    Ok(json!({
+        "path": path,
+        "size": resolve_size(size),
+        "cachePath": thumb.to_string_lossy(),
+        "url": url_for(path, size)
+    }))
+}
+
+pub fn handle_request(uri_path: &str, query: Option<&str>) -> (u16, &'static str, Vec<u8>) {
+    let size: u32 = uri_path
+        .trim_matches('/')
+        .parse()
+        .unwrap_or(DEFAULT_SIZES[1]);
+    let source = query
+        .unwrap_or("")
+        .split('&')
+        .find_map(|pair| pair.strip_prefix("path="))
+        .map(percent_decode);
+
+    let Some(source) = source else {
+        return (400, "text/plain", b"missing path".to_vec());
+    };
+    if !is_allowed(Path::new(&source)) {
+        return (403, "text/plain", b"path not allowed".to_vec());
+    }
+    match ensure_thumbnail(&source, size).and_then(|p| std::fs::read(p).map_err(|e| e.to_string()))
+    {
+        Ok(bytes) => (200, "image/jpeg", bytes),
+        Err(e) => (404, "text/plain", e.into_bytes()),
+    }
+}
+
+pub async fn serve(uri_path: String, query: Option<String>) -> (u16, &'static str, Vec<u8>) {
+    let Ok(_slot) = DECODE_SLOTS.acquire().await else {
+        return (503, "text/plain", b"shutting down".to_vec());
+    };
+    tokio::task::spawn_blocking(move || handle_request(&uri_path, query.as_deref()))
+        .await
+        .unwrap_or_else(|e| (500, "text/plain", e.to_string().into_bytes()))
+}
+
+pub fn cleanup() -> Value {
+    let Ok(entries) = std::fs::read_dir(cache_dir()) else {
+        return json!({ "removed": 0, "remaining": 0 });
+    };
+
+    let now = SystemTime::now();
+    let mut removed = 0u32;
+    let mut kept: Vec<(PathBuf, SystemTime, u64)> = Vec::new();
+    for entry in entries.filter_map(|e| e.ok()) {
+        let path = entry.path();
+        let Ok(meta) = entry.metadata() else {
+            continue;
+        };
+        let modified = meta.modified().unwrap_or(now);
+        let age = now.duration_since(modified).unwrap_or_default();
+        let is_tmp = path.extension().map(|e| e == "tmp").unwrap_or(false);
+        if age > MAX_AGE || (is_tmp && age > Duration::from_secs(3600)) {
+            if std::fs::remove_file(&path).is_ok() {
+                removed += 1;
+            }
+        } else {
+            kept.push((path, modified, meta.len()));
+        }
+    }
+
+    // Over budget: drop least recently used entries first.
+    let mut total: u64 = kept.iter().map(|(_, _, len)| len).sum();
+    kept.sort_by_key(|(_, modified, _)| *modified);
+    let mut remaining = kept.len();
+    for (path, _, len) in &kept {
+        if total <= MAX_CACHE_BYTES {
+            break;
+        }
+        if std::fs::remove_file(path).is_ok() {
+            total -= len;
+            removed += 1;
+            remaining -= 1;
+        }
+    }
+
+    json!({
+        "removed": removed,
+        "remaining": remaining,
+        "totalBytes": total
+    })
+}
+
+pub fn spawn_cleanup_worker() {
+    std::thread::spawn(|| loop {
+        cleanup();
+        std::thread::sleep(CLEANUP_INTERVAL);
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use image::{DynamicImage, ImageFormat};
+
+    fn write_png(dir: &Path, name: &str) -> PathBuf {
+        std::fs::create_dir_all(dir).unwrap();
+        let path = dir.join(name);
+        DynamicImage::new_rgb8(40, 30)
+            .save_with_format(&path, ImageFormat::Png)
+            .unwrap();
+        path
+    }
+
+    fn request(path: &Path) -> u16 {
+        let query = format!("path={}", percent_encode(&path.to_string_lossy()));
+        handle_request("/64", Some(&query)).0
+    }
+
+    #[test]
+    fn serves_only_allowed_folders() {
+        let root = std::env::temp_dir().join(format!("autowhisk_thumb_{}", uuid::Uuid::new_v4()));
+        let allowed = write_png(&root.join("out"), "a.png");
+        let outside = write_png(&root.join("elsewhere"), "b.png");
+        settings::remember_output_folder(&root.join("out").to_string_lossy()).unwrap();
+
+        assert_eq!(request(&allowed), 200);
+        assert_eq!(request(&outside), 403);
+        let escaped = root.join("out").join("..").join("elsewhere").join("b.png");
+        assert_eq!(request(&escaped), 403);
+        assert!(get_thumbnail(&outside.to_string_lossy(), 64).is_err());
+        let _ = std::fs::remove_dir_all(&root);
+    }
+
+    #[test]
+    fn missing_path_is_a_bad_request() {
+        assert_eq!(handle_request("/64", None).0, 400);
+        assert_eq!(handle_request("/64", Some("size=1")).0, 400);
+    }
+
+    #[test]
+    fn percent_encoding_round_trips() {
+        let path = r"C:\Ảnh mới\a b&c=%.png";
+        assert_eq!(percent_decode(&percent_encode(path)), path);
+    }
+}
@@ -0,0 +1,155 @@
+use crate::settings::app_data_dir;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+const MAX_AGE_SECS: u64 = 7 * 24 * 3600;
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Serialize, Deserialize, Clone)]
+#[serde(rename_all = "camelCase")]
+struct CacheEntry {
+    sha256: String,
+    account_id: String,
+    workflow_id: String,
+    // Older cache files have no category/caption; those entries simply never match.
+    #[serde(default)]
+    category: String,
+    #[serde(default)]
+    caption: String,
+    // Entries from before resizing was keyed have 0 here and never match.
+    #[serde(default)]
+    max_edge: u32,
+    media_name: String,
+    created_at: u64,
+}
+
+// An upload is tied to the category and caption it was sent with, so the same bytes used
+// as a style and as a subject are separate media. The hash is of the source file, so the
+// max edge it was resized to is part of the key too.
+pub struct CacheKey<'a> {
+    pub sha256: &'a str,
+    pub account_id: &'a str,
+    pub workflow_id: &'a str,
+    pub category: &'a str,
+    pub caption: &'a str,
+    pub max_edge: u32,
+}
+
+impl CacheEntry {
+    fn matches(&self, key: &CacheKey) -> bool {
+        self.sha256 == key.sha256
+            && self.account_id == key.account_id
+            && self.workflow_id == key.workflow_id
+            && self.category == key.category
+            && self.caption == key.caption
+            && self.max_edge == key.max_edge
+    }
+}
+
+fn get_cache_path() -> PathBuf {
+    app_data_dir().join("upload_cache.json")
+}
+
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn load_entries() -> Vec<CacheEntry> {
+    match fs::read_to_string(get_cache_path()) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
+        Err(_) => Vec::new(),
+    }
+}
+
+fn save_entries(entries: &[CacheEntry]) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(entries).map_err(|e| e.to_string())?;
+    fs::write(get_cache_path(), json).map_err(|e| format!("Failed to save: {}", e))
+}
+
+pub fn lookup(key: &CacheKey) -> Option<String> {
+    let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let cutoff = now().saturating_sub(MAX_AGE_SECS);
+    load_entries()
+        .into_iter()
+        .find(|e| e.matches(key) && e.created_at >= cutoff)
+        .map(|e| e.media_name)
+}
+
+pub fn store(key: &CacheKey, media_name: &str) -> Result<(), String> {
+    let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let cutoff = now().saturating_sub(MAX_AGE_SECS);
+    let mut entries = load_entries();
+    entries.retain(|e| e.created_at >= cutoff && !e.matches(key));
+    entries.push(CacheEntry {
+        sha256: key.sha256.to_string(),
+        account_id: key.account_id.to_string(),
+        workflow_id: key.workflow_id.to_string(),
+        category: key.category.to_string(),
+        caption: key.caption.to_string(),
+        max_edge: key.max_edge,
+        media_name: media_name.to_string(),
+        created_at: now(),
+    });
+    save_entries(&entries)
+}
+
+pub fn invalidate_media(media_names: &[String]) -> Result<usize, String> {
+    if media_names.is_empty() {
+        return Ok(0);
+    }
+    let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut entries = load_entries();
+    let before = entries.len();
+    entries.retain(|e| !media_names.contains(&e.media_name));
+    let removed = before - entries.len();
+    if removed > 0 {
+        save_entries(&entries)?;
+    }
+    Ok(removed)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn key_includes_category_caption_and_max_edge() {
+        let sha = uuid::Uuid::new_v4().to_string();
+        let subject = CacheKey {
+            sha256: &sha,
+            account_id: "acc",
+            workflow_id: "wf",
+            category: "MEDIA_CATEGORY_SUBJECT",
+            caption: "a cat",
+            max_edge: 2048,
+        };
+        let style = CacheKey {
+            category: "MEDIA_CATEGORY_STYLE",
+            ..subject
+        };
+        let recaptioned = CacheKey {
+            caption: "a dog",
+            ..subject
+        };
+        let smaller = CacheKey {
+            max_edge: 1024,
+            ..subject
+        };
+        let media = format!("media-{}", sha);
+
+        store(&subject, &media).unwrap();
+        assert_eq!(lookup(&subject), Some(media.clone()));
+        assert_eq!(lookup(&style), None);
+        assert_eq!(lookup(&recaptioned), None);
+        assert_eq!(lookup(&smaller), None);
+
+        assert_eq!(invalidate_media(&[media]), Ok(1));
+        assert_eq!(lookup(&subject), None);
+    }
+}
@@ -0,0 +1,8 @@
+// Compiles a pattern once per call site.
+macro_rules! re {
+    ($pattern:expr) => {{
+        static RE: std::sync::OnceLock<regex::Regex> = std::sync::OnceLock::new();
+        RE.get_or_init(|| regex::Regex::new($pattern).expect("invalid regex"))
+    }};
+}
+pub(crate) use re;
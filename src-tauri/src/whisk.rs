@@ -1,12 +1,26 @@
+use crate::filename::{self, FilenameContext};
+use crate::image_cache;
+use crate::library::{self, LibraryImage};
+use crate::manifest::{self, ManifestEntry, ManifestImage};
+use crate::metadata::ImageMetadata;
+use crate::output::{self, OutputFormat, SaveOptions};
+use crate::phash;
+use crate::reference::{self, RecipeReference, ReferenceInput};
+use crate::settings;
+use crate::upload_cache;
+use crate::workflows;
 use base64::Engine;
 use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
 use serde_json::{json, Value};
 use std::collections::HashMap;
-use std::path::Path;
+use std::io::Write;
+use std::path::{Path, PathBuf};
 
 const GENERATE_URL: &str = "https://aisandbox-pa.googleapis.com/v1/whisk:generateImage";
 const WORKFLOW_URL: &str = "https://labs.google/fx/api/trpc/media.createOrUpdateWorkflow";
 const SESSION_URL: &str = "https://labs.google/fx/api/auth/session";
+const RECIPE_URL: &str = "https://aisandbox-pa.googleapis.com/v1/whisk:runImageRecipe";
+const RECIPE_MODEL: &str = "R2I";
 const UPLOAD_URL: &str = "https://labs.google/fx/api/trpc/backbone.uploadImage";
 const DELETE_MEDIA_URL: &str = "https://labs.google/fx/api/trpc/media.deleteMedia";
 
@@ -102,20 +116,30 @@ async fn fetch_bearer_token(cookies: &str) -> Result<Option<String>, String> {
     Ok(token.map(|s| s.to_string()))
 }
 
-async fn create_workflow(
+fn default_workflow_name() -> String {
+    format!("Whisk: {}", date_now_short())
+}
+
+async fn save_workflow(
     client: &reqwest::Client,
     cookies: &str,
     session_id: &str,
+    workflow_id: Option<&str>,
+    name: &str,
 ) -> Option<String> {
+    let mut client_context = json!({
+        "tool": "BACKBONE",
+        "sessionId": session_id
+    });
+    if let Some(id) = workflow_id {
+        client_context["workflowId"] = json!(id);
+    }
     let body = json!({
         "json": {
-            "clientContext": {
-                "tool": "BACKBONE",
-                "sessionId": session_id
-            },
+            "clientContext": client_context,
             "mediaGenerationIdsToCopy": [],
             "workflowMetadata": {
-                "workflowName": format!("Whisk: {}", date_now_short())
+                "workflowName": name
             }
         }
     });
@@ -141,6 +165,157 @@ async fn create_workflow(
         .get("workflowId")?
         .as_str()
         .map(|s| s.to_string())
+        .or_else(|| workflow_id.map(|s| s.to_string()))
+}
+
+struct ResolvedWorkflow {
+    id: String,
+    // A local uuid used when the server would not create a workflow; it is never recorded.
+    fallback: bool,
+}
+
+// Falls back to the account's default workflow, then to a freshly created one named `name`.
+// Without an account there is no default to look up.
+async fn resolve_workflow(
+    client: &reqwest::Client,
+    cookies: &str,
+    session_id: &str,
+    existing_workflow_id: Option<String>,
+    account_id: Option<&str>,
+    name: Option<&str>,
+    diag: &mut String,
+) -> ResolvedWorkflow {
+    let account_id = account_id.filter(|a| !a.is_empty());
+    let existing = existing_workflow_id
+        .filter(|id| !id.is_empty())
+        .or_else(|| account_id.and_then(workflows::default_for));
+    if let Some(id) = existing {
+        diag.push_str(&format!(
+            "[Reusing workflow: {}...] ",
+            &id[..8.min(id.len())]
+        ));
+        return ResolvedWorkflow {
+            id,
+            fallback: false,
+        };
+    }
+
+    let name = name
+        .and_then(workflows::clean_name)
+        .unwrap_or_else(default_workflow_name);
+    if !cookies.is_empty() {
+        diag.push_str("[Workflow creating...] ");
+        if let Some(wf_id) = save_workflow(client, cookies, session_id, None, &name).await {
+            diag.push_str(&format!(
+                "[Workflow OK: {}...] ",
+                &wf_id[..8.min(wf_id.len())]
+            ));
+            if let Err(e) =
+                workflows::record_workflow(&wf_id, account_id.unwrap_or_default(), &name)
+            {
+                diag.push_str(&format!("[Workflow record error: {}] ", e));
+            }
+            return ResolvedWorkflow {
+                id: wf_id,
+                fallback: false,
+            };
+        }
+        diag.push_str("[Workflow failed, using fallback] ");
+    }
+    ResolvedWorkflow {
+        id: uuid::Uuid::new_v4().to_string(),
+        fallback: true,
+    }
+}
+
+// The workflow a batch run should use for one account: its default, or a new workflow named
+// after the batch. Called once per account so parallel tasks share a single workflow.
+pub async fn batch_workflow(
+    cookies: &str,
+    account_id: Option<&str>,
+    batch_name: Option<&str>,
+) -> Result<Value, String> {
+    let client = build_client()?;
+    let mut diag = String::new();
+    let workflow = resolve_workflow(
+        &client,
+        cookies,
+        &session_id_now(),
+        None,
+        account_id,
+        batch_name,
+        &mut diag,
+    )
+    .await;
+    if workflow.fallback {
+        return Err(format!("Failed to create workflow | {}", diag));
+    }
+    let record = workflows::get_workflow(&workflow.id);
+    Ok(json!({
+        "id": workflow.id,
+        "name": record.map(|w| w.name).unwrap_or_default(),
+        "projectLink": workflows::project_link(&workflow.id)
+    }))
+}
+
+pub async fn create_named_workflow(
+    cookies: &str,
+    account_id: Option<&str>,
+    name: &str,
+) -> Result<Value, String> {
+    let name = workflows::clean_name(name).ok_or("Workflow name is empty")?;
+    let account_id = account_id.unwrap_or_default();
+    let client = build_client()?;
+    let wf_id = save_workflow(&client, cookies, &session_id_now(), None, &name)
+        .await
+        .ok_or("Failed to create workflow")?;
+    workflows::record_workflow(&wf_id, account_id, &name)?;
+    Ok(json!({
+        "id": wf_id,
+        "accountId": account_id,
+        "name": name,
+        "projectLink": workflows::project_link(&wf_id)
+    }))
+}
+
+pub async fn rename_workflow(
+    cookies: &str,
+    workflow_id: &str,
+    name: &str,
+) -> Result<Value, String> {
+    let name = workflows::clean_name(name).ok_or("Workflow name is empty")?;
+    if !cookies.is_empty() {
+        let client = build_client()?;
+        save_workflow(
+            &client,
+            cookies,
+            &session_id_now(),
+            Some(workflow_id),
+            &name,
+        )
+        .await
+        .ok_or("Failed to rename workflow")?;
+    }
+    if workflows::get_workflow(workflow_id).is_none() {
+        workflows::record_workflow(workflow_id, "", &name)?;
+    }
+    workflows::rename_workflow(workflow_id, &name)
+}
+
+struct UploadFailure {
+    code: &'static str,
+    message: String,
+    http_status: Option<u16>,
+}
+
+impl UploadFailure {
+    fn new(code: &'static str, message: impl Into<String>, http_status: Option<u16>) -> Self {
+        UploadFailure {
+            code,
+            message: message.into(),
+            http_status,
+        }
+    }
 }
 
 async fn upload_reference_image(
@@ -148,9 +323,10 @@ async fn upload_reference_image(
     cookies: &str,
     image_data: &[u8],
     mime: &str,
+    ref_input: &ReferenceInput,
     workflow_id: &str,
     session_id: &str,
-) -> Result<Option<String>, String> {
+) -> Result<String, UploadFailure> {
     let engine = base64::engine::general_purpose::STANDARD;
     let b64 = engine.encode(image_data);
     let raw_bytes = format!("data:{};base64,{}", mime, b64);
@@ -162,9 +338,9 @@ async fn upload_reference_image(
                 "sessionId": session_id
             },
             "uploadMediaInput": {
-                "mediaCategory": "MEDIA_CATEGORY_SUBJECT",
+                "mediaCategory": ref_input.category().api_name(),
                 "rawBytes": raw_bytes,
-                "caption": "Reference image for Whisk"
+                "caption": ref_input.caption()
             }
         }
     });
@@ -180,24 +356,38 @@ async fn upload_reference_image(
         .json(&body)
         .send()
         .await
-        .map_err(|e| e.to_string())?;
+        .map_err(|e| UploadFailure::new("network_error", e.to_string(), None))?;
 
-    if !resp.status().is_success() {
-        return Ok(None);
+    let status = resp.status();
+    if !status.is_success() {
+        let body_text = resp.text().await.unwrap_or_default();
+        let preview: String = body_text.chars().take(300).collect();
+        return Err(UploadFailure::new(
+            "http_error",
+            format!("{}: {}", status, preview),
+            Some(status.as_u16()),
+        ));
     }
 
     let data: Value = resp.json().await.unwrap_or(json!({}));
-    let media_name = data["result"]["data"]["json"]["name"]
+    data["result"]["data"]["json"]["name"]
         .as_str()
         .or_else(|| data["result"]["data"]["json"]["mediaName"].as_str())
-        .map(|s| s.to_string());
-    Ok(media_name)
+        .map(|s| s.to_string())
+        .ok_or_else(|| {
+            UploadFailure::new(
+                "no_media_name",
+                "Upload response has no media name",
+                Some(status.as_u16()),
+            )
+        })
 }
 
 pub async fn delete_reference_image(
     cookies: &str,
     media_names: Vec<String>,
 ) -> Result<bool, String> {
+    upload_cache::invalidate_media(&media_names)?;
     let client = build_client()?;
     let body = json!({
         "json": {
@@ -218,30 +408,71 @@ pub async fn delete_reference_image(
     Ok(resp.status().is_success())
 }
 
+// Everything one image request shares with the rest of its batch except the seed.
+#[derive(Clone)]
+struct GenerateCall {
+    prompt: String,
+    image_model: &'static str,
+    aspect_ratio: &'static str,
+    workflow_id: String,
+    session_id: String,
+    references: Vec<RecipeReference>,
+}
+
 async fn call_generate_api(
     client: &reqwest::Client,
     token: &str,
-    prompt: &str,
-    aspect_ratio: &str,
+    call: &GenerateCall,
     seed: u32,
-    workflow_id: &str,
-    session_id: &str,
     extra_headers: Option<&HashMap<String, String>>,
 ) -> Result<Option<String>, String> {
-    let body = json!({
-        "clientContext": {
-            "workflowId": workflow_id,
-            "tool": "BACKBONE",
-            "sessionId": session_id
-        },
-        "imageModelSettings": {
-            "imageModel": "IMAGEN_3_5",
-            "aspectRatio": aspect_ratio
-        },
-        "seed": seed,
-        "prompt": prompt,
-        "mediaCategory": "MEDIA_CATEGORY_BOARD"
+    let client_context = json!({
+        "workflowId": call.workflow_id,
+        "tool": "BACKBONE",
+        "sessionId": call.session_id
     });
+    let (url, body) = if call.references.is_empty() {
+        (
+            GENERATE_URL,
+            json!({
+                "clientContext": client_context,
+                "imageModelSettings": {
+                    "imageModel": call.image_model,
+                    "aspectRatio": call.aspect_ratio
+                },
+                "seed": seed,
+                "prompt": call.prompt,
+                "mediaCategory": "MEDIA_CATEGORY_BOARD"
+            }),
+        )
+    } else {
+        let inputs: Vec<Value> = call
+            .references
+            .iter()
+            .map(|r| {
+                json!({
+                    "caption": r.caption.as_deref().unwrap_or(r.category.default_caption()),
+                    "mediaInput": {
+                        "mediaCategory": r.category.api_name(),
+                        "mediaGenerationId": r.media_name
+                    }
+                })
+            })
+            .collect();
+        (
+            RECIPE_URL,
+            json!({
+                "clientContext": client_context,
+                "imageModelSettings": {
+                    "imageModel": RECIPE_MODEL,
+                    "aspectRatio": call.aspect_ratio
+                },
+                "seed": seed,
+                "userInstruction": call.prompt,
+                "recipeMediaInputs": inputs
+            }),
+        )
+    };
 
     let body_str = serde_json::to_string(&body).map_err(|e| e.to_string())?;
 
@@ -301,7 +532,7 @@ async fn call_generate_api(
     }
 
     let resp = client
-        .post(GENERATE_URL)
+        .post(url)
         .headers(headers)
         .body(body_str)
         .send()
@@ -332,6 +563,10 @@ async fn call_generate_api(
     Err(format!("No image in response: {}", preview))
 }
 
+fn is_missing_media_error(error: &str) -> bool {
+    error.starts_with("HTTP 404") || error.contains("NOT_FOUND")
+}
+
 fn extract_encoded_image(data: &Value) -> Option<String> {
     if let Some(panels) = data.get("imagePanels").and_then(|p| p.as_array()) {
         for panel in panels {
@@ -388,31 +623,292 @@ fn find_base64_deep(value: &Value) -> Option<String> {
     }
 }
 
-fn map_aspect_ratio(ratio: &str) -> &str {
-    match ratio {
-        "16:9" => "IMAGE_ASPECT_RATIO_LANDSCAPE",
-        "9:16" => "IMAGE_ASPECT_RATIO_PORTRAIT",
-        "1:1" => "IMAGE_ASPECT_RATIO_SQUARE",
-        _ => "IMAGE_ASPECT_RATIO_LANDSCAPE",
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ImageModel {
+    Imagen3_5,
+    Imagen4,
+    GemPix,
+}
+
+impl ImageModel {
+    pub const ALL: [ImageModel; 3] = [
+        ImageModel::Imagen3_5,
+        ImageModel::Imagen4,
+        ImageModel::GemPix,
+    ];
+    pub const DEFAULT: ImageModel = ImageModel::Imagen3_5;
+
+    pub fn api_name(self) -> &'static str {
+        match self {
+            ImageModel::Imagen3_5 => "IMAGEN_3_5",
+            ImageModel::Imagen4 => "IMAGEN_4",
+            ImageModel::GemPix => "GEM_PIX",
+        }
+    }
+
+    pub fn label(self) -> &'static str {
+        match self {
+            ImageModel::Imagen3_5 => "Imagen 3.5",
+            ImageModel::Imagen4 => "Imagen 4",
+            ImageModel::GemPix => "Gemini (Nano Banana)",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let v = value.trim();
+        Self::ALL
+            .into_iter()
+            .find(|m| m.api_name().eq_ignore_ascii_case(v))
+            .ok_or_else(|| {
+                let known: Vec<&str> = Self::ALL.iter().map(|m| m.api_name()).collect();
+                format!(
+                    "Unknown image model '{}' (expected one of {})",
+                    v,
+                    known.join(", ")
+                )
+            })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AspectRatio {
+    Landscape,
+    Portrait,
+    Square,
+    LandscapeFourThree,
+    PortraitThreeFour,
+}
+
+impl AspectRatio {
+    pub const ALL: [AspectRatio; 5] = [
+        AspectRatio::Landscape,
+        AspectRatio::Portrait,
+        AspectRatio::Square,
+        AspectRatio::LandscapeFourThree,
+        AspectRatio::PortraitThreeFour,
+    ];
+    pub const DEFAULT: AspectRatio = AspectRatio::Landscape;
+
+    pub fn ratio(self) -> &'static str {
+        match self {
+            AspectRatio::Landscape => "16:9",
+            AspectRatio::Portrait => "9:16",
+            AspectRatio::Square => "1:1",
+            AspectRatio::LandscapeFourThree => "4:3",
+            AspectRatio::PortraitThreeFour => "3:4",
+        }
+    }
+
+    pub fn api_name(self) -> &'static str {
+        match self {
+            AspectRatio::Landscape => "IMAGE_ASPECT_RATIO_LANDSCAPE",
+            AspectRatio::Portrait => "IMAGE_ASPECT_RATIO_PORTRAIT",
+            AspectRatio::Square => "IMAGE_ASPECT_RATIO_SQUARE",
+            AspectRatio::LandscapeFourThree => "IMAGE_ASPECT_RATIO_LANDSCAPE_FOUR_THREE",
+            AspectRatio::PortraitThreeFour => "IMAGE_ASPECT_RATIO_PORTRAIT_THREE_FOUR",
+        }
+    }
+
+    pub fn parse(value: &str) -> Result<Self, String> {
+        let v = value.trim();
+        Self::ALL
+            .into_iter()
+            .find(|r| r.ratio() == v || r.api_name().eq_ignore_ascii_case(v))
+            .ok_or_else(|| {
+                let known: Vec<&str> = Self::ALL.iter().map(|r| r.ratio()).collect();
+                format!(
+                    "Unknown aspect ratio '{}' (expected one of {})",
+                    v,
+                    known.join(", ")
+                )
+            })
+    }
+}
+
+pub fn list_capabilities() -> Value {
+    let models: Vec<Value> = ImageModel::ALL
+        .iter()
+        .map(|m| {
+            json!({
+                "id": m.api_name(),
+                "label": m.label(),
+                "isDefault": *m == ImageModel::DEFAULT
+            })
+        })
+        .collect();
+    let ratios: Vec<Value> = AspectRatio::ALL
+        .iter()
+        .map(|r| {
+            json!({
+                "ratio": r.ratio(),
+                "id": r.api_name(),
+                "isDefault": *r == AspectRatio::DEFAULT
+            })
+        })
+        .collect();
+    json!({
+        "models": models,
+        "aspectRatios": ratios,
+        "referenceCategories": ["subject", "scene", "style"]
+    })
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct TaskContext {
+    pub prompt: String,
+    pub aspect_ratio: String,
+    pub image_model: Option<String>,
+    pub count: u32,
+    pub save_folder: Option<String>,
+    pub existing_workflow_id: Option<String>,
+    pub batch_id: Option<String>,
+    pub batch_name: Option<String>,
+    pub task_index: Option<u32>,
+    pub account_id: Option<String>,
+    pub account_email: Option<String>,
+    pub file_template: String,
+    pub save_options: SaveOptions,
+    pub library: bool,
+    pub duplicate_threshold: Option<u32>,
+    pub references: Vec<RecipeReference>,
+    pub style_preset: Option<String>,
+    pub base_prompt: Option<String>,
+    pub video_prompt: Option<String>,
+}
+
+struct SavedImage {
+    path: PathBuf,
+    sha256: String,
+    bytes: Vec<u8>,
+    ext: &'static str,
+    // Set when the requested format failed and the original bytes were saved instead.
+    encode_error: Option<String>,
+}
+
+fn save_image(
+    folder: &Path,
+    bytes: &[u8],
+    file_template: &str,
+    mut ctx: FilenameContext,
+    meta: &ImageMetadata,
+    opts: &SaveOptions,
+) -> Result<SavedImage, String> {
+    std::fs::create_dir_all(folder).map_err(|e| e.to_string())?;
+
+    let (encoded, encode_error) = match output::encode(bytes.to_vec(), opts, meta) {
+        Ok(enc) => (enc, None),
+        Err(e) => {
+            let original = SaveOptions {
+                format: OutputFormat::Original,
+                ..Default::default()
+            };
+            (output::encode(bytes.to_vec(), &original, meta)?, Some(e))
+        }
+    };
+    ctx.ext = encoded.ext.to_string();
+
+    let file_name = filename::render(file_template, &ctx)?;
+    let path = filename::reserve_unique_path(folder, &file_name)?;
+    if let Err(e) = std::fs::write(&path, &encoded.bytes) {
+        let _ = std::fs::remove_file(&path);
+        return Err(e.to_string());
+    }
+    Ok(SavedImage {
+        path,
+        sha256: manifest::sha256_hex(&encoded.bytes),
+        bytes: encoded.bytes,
+        ext: encoded.ext,
+        encode_error,
+    })
+}
+
+// Image-to-video tools pick the prompt up from a .txt named after the image, so it is
+// written as `<image stem>.txt` next to the image. The image path is already unique; a .txt
+// that still exists under that name is left alone and reported instead of renumbered.
+fn write_video_prompt(image_path: &Path, video_prompt: &str) -> Result<PathBuf, String> {
+    let path = image_path.with_extension("txt");
+    let mut file = std::fs::OpenOptions::new()
+        .write(true)
+        .create_new(true)
+        .open(&path)
+        .map_err(|e| match e.kind() {
+            std::io::ErrorKind::AlreadyExists => {
+                format!(
+                    "{} already exists, video prompt not written",
+                    path.display()
+                )
+            }
+            _ => e.to_string(),
+        })?;
+    file.write_all(video_prompt.as_bytes())
+        .map_err(|e| e.to_string())?;
+    Ok(path)
+}
+
+fn unix_now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn write_manifest(folder: Option<&str>, entry: &ManifestEntry, diag: &mut String) {
+    if let Some(folder) = folder {
+        if let Err(e) = manifest::append_entry(Path::new(folder), entry) {
+            diag.push_str(&format!("[Manifest error: {}] ", e));
+        }
+    }
+}
+
+// Recipes only run on the reference model, so an explicit pick is rejected rather than
+// silently replaced.
+fn resolve_model(image_model: Option<&str>, has_references: bool) -> Result<&'static str, String> {
+    let explicit = image_model.map(str::trim).filter(|m| !m.is_empty());
+    match (explicit, has_references) {
+        (Some(m), true) if !m.eq_ignore_ascii_case(RECIPE_MODEL) => Err(format!(
+            "Model '{}' cannot be used with reference images; clear the model or remove the references",
+            m
+        )),
+        (_, true) => Ok(RECIPE_MODEL),
+        (Some(m), false) => Ok(ImageModel::parse(m)?.api_name()),
+        (None, false) => Ok(ImageModel::DEFAULT.api_name()),
     }
 }
 
 pub async fn generate_image_async(
     cookies: &str,
     bearer_token: &str,
-    prompt: &str,
-    aspect_ratio: &str,
-    count: u32,
-    save_folder: Option<&str>,
     extra_headers: Option<&HashMap<String, String>>,
-    existing_workflow_id: Option<String>,
+    task: &TaskContext,
 ) -> Result<Value, String> {
     let mut diag = String::new();
+    let prompt = task.prompt.as_str();
+    let aspect_ratio = task.aspect_ratio.as_str();
+    let count = task.count;
+    let save_folder = task.save_folder.as_deref();
+
+    filename::validate_template(&task.file_template)?;
+    task.save_options.validate()?;
 
-    let api_ratio = map_aspect_ratio(aspect_ratio);
+    let api_ratio = AspectRatio::parse(aspect_ratio)?.api_name();
+    let api_model = resolve_model(task.image_model.as_deref(), !task.references.is_empty())?;
     let session_id = session_id_now();
     let client = build_client()?;
 
+    let mut entry = ManifestEntry {
+        batch_id: task.batch_id.clone(),
+        task_index: task.task_index,
+        prompt: prompt.to_string(),
+        base_prompt: task.base_prompt.clone(),
+        style_preset: task.style_preset.clone(),
+        video_prompt: task.video_prompt.clone(),
+        account_id: task.account_id.clone(),
+        aspect_ratio: Some(aspect_ratio.to_string()),
+        model: Some(api_model.to_string()),
+        created_at: unix_now(),
+        ..Default::default()
+    };
+
     let mut token = bearer_token.to_string();
     if token.is_empty() || !token.starts_with("ya29.") {
         diag.push_str("[No bearer token, trying auto-fetch...] ");
@@ -429,9 +925,13 @@ pub async fn generate_image_async(
     }
 
     if token.is_empty() || !token.starts_with("ya29.") {
+        let error = format!("❌ Không có Bearer token hợp lệ. Hãy tạo 1 ảnh trên Whisk web trước rồi bắt lại cookie. {}", diag);
+        entry.status = "failed".to_string();
+        entry.error = Some(error.clone());
+        write_manifest(save_folder, &entry, &mut diag);
         return Ok(json!({
             "success": false,
-            "error": format!("❌ Không có Bearer token hợp lệ. Hãy tạo 1 ảnh trên Whisk web trước rồi bắt lại cookie. {}", diag)
+            "error": error
         }));
     }
 
@@ -442,27 +942,21 @@ pub async fn generate_image_async(
         token.len()
     ));
 
-    let mut workflow_id = existing_workflow_id.clone().unwrap_or_default();
-    if workflow_id.is_empty() {
-        workflow_id = uuid::Uuid::new_v4().to_string();
-        if !cookies.is_empty() {
-            diag.push_str("[Workflow creating...] ");
-            if let Some(wf_id) = create_workflow(&client, cookies, &session_id).await {
-                diag.push_str(&format!(
-                    "[Workflow OK: {}...] ",
-                    &wf_id[..8.min(wf_id.len())]
-                ));
-                workflow_id = wf_id;
-            } else {
-                diag.push_str("[Workflow failed, using fallback] ");
-            }
-        }
-    } else {
-        diag.push_str(&format!(
-            "[Reusing workflow: {}...] ",
-            &workflow_id[..8.min(workflow_id.len())]
-        ));
-    }
+    let account_key = task.account_id.clone().unwrap_or_default();
+    let workflow = resolve_workflow(
+        &client,
+        cookies,
+        &session_id,
+        task.existing_workflow_id.clone(),
+        task.account_id.as_deref(),
+        task.batch_name.as_deref(),
+        &mut diag,
+    )
+    .await;
+    let workflow_id = workflow.id.clone();
+    let project_link = workflows::project_link(&workflow_id);
+    entry.workflow_id = Some(workflow_id.clone());
+    entry.project_link = Some(project_link.clone());
 
     let seed_base: u32 = {
         use rand::Rng;
@@ -470,33 +964,28 @@ pub async fn generate_image_async(
     };
 
     diag.push_str(&format!(
-        "[API start: inputRatio={}, ratio={}, count={}] ",
-        aspect_ratio, api_ratio, count
+        "[API start: inputRatio={}, ratio={}, model={}, count={}] ",
+        aspect_ratio, api_ratio, api_model, count
     ));
 
+    let call = GenerateCall {
+        prompt: prompt.to_string(),
+        image_model: api_model,
+        aspect_ratio: api_ratio,
+        workflow_id: workflow_id.clone(),
+        session_id: session_id.clone(),
+        references: task.references.clone(),
+    };
     let mut tasks = Vec::new();
     for i in 0..count {
         let seed = seed_base + i;
         let client = client.clone();
         let token = token.clone();
-        let prompt = prompt.to_string();
-        let ratio = api_ratio.to_string();
-        let wf_id = workflow_id.clone();
-        let sess_id = session_id.clone();
+        let call = call.clone();
         let hdrs = extra_headers.cloned();
 
         tasks.push(tokio::spawn(async move {
-            call_generate_api(
-                &client,
-                &token,
-                &prompt,
-                &ratio,
-                seed,
-                &wf_id,
-                &sess_id,
-                hdrs.as_ref(),
-            )
-            .await
+            call_generate_api(&client, &token, &call, seed, hdrs.as_ref()).await
         }));
     }
 
@@ -504,55 +993,241 @@ pub async fn generate_image_async(
 
     let mut images = Vec::new();
     let engine = base64::engine::general_purpose::STANDARD;
+    let duplicate_batch = task
+        .batch_id
+        .clone()
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string());
 
     for (idx, result) in results.into_iter().enumerate() {
+        let seed = seed_base + idx as u32;
+        let mut record = ManifestImage {
+            n: idx as u32 + 1,
+            seed,
+            status: "failed".to_string(),
+            ..Default::default()
+        };
+
         let img_result = match result {
             Ok(Ok(Some(b64))) => Some(b64),
-            Ok(Ok(None)) => None,
+            Ok(Ok(None)) => {
+                record.error = Some("Empty response".to_string());
+                None
+            }
             Ok(Err(e)) => {
                 if images.is_empty() {
                     diag.push_str(&format!("[Error #{}: {}] ", idx + 1, e));
                 }
+                if !task.references.is_empty() && is_missing_media_error(&e) {
+                    let names: Vec<String> = task
+                        .references
+                        .iter()
+                        .map(|r| r.media_name.clone())
+                        .collect();
+                    if let Err(e) = upload_cache::invalidate_media(&names) {
+                        diag.push_str(&format!("[Upload cache error: {}] ", e));
+                    }
+                }
+                record.error = Some(e);
                 None
             }
             Err(e) => {
                 diag.push_str(&format!("[Task error #{}: {}] ", idx + 1, e));
+                record.error = Some(e.to_string());
                 None
             }
         };
 
         if let Some(b64) = img_result {
-            let mut saved_path: Option<String> = None;
-            let encoded_image = format!("data:image/jpeg;base64,{}", b64);
-
-            if let Some(folder) = save_folder {
-                if let Ok(bytes) = engine.decode(&b64) {
-                    let _ = std::fs::create_dir_all(folder);
-                    let now = std::time::SystemTime::now()
-                        .duration_since(std::time::UNIX_EPOCH)
-                        .unwrap_or_default()
-                        .as_secs();
-                    let file_name = format!("whisk_{}_{}.png", now, idx + 1);
-                    let path = Path::new(folder).join(&file_name);
-
-                    if let Ok(img) = image::load_from_memory(&bytes) {
-                        let _ = img.save_with_format(&path, image::ImageFormat::Png);
-                        saved_path = Some(path.to_string_lossy().to_string());
-                    } else if std::fs::write(&path, &bytes).is_ok() {
-                        saved_path = Some(path.to_string_lossy().to_string());
+            match engine.decode(&b64) {
+                Ok(bytes) => {
+                    let mut saved_path: Option<String> = None;
+                    let mut archived: Option<(Vec<u8>, &'static str)> = None;
+                    record.status = "generated".to_string();
+
+                    let now = unix_now();
+                    let meta = ImageMetadata {
+                        prompt: prompt.to_string(),
+                        seed: Some(seed),
+                        aspect_ratio: Some(aspect_ratio.to_string()),
+                        model: Some(api_model.to_string()),
+                        workflow_id: Some(workflow_id.clone()),
+                        account_email: task.account_email.clone(),
+                        timestamp: Some(now),
+                    };
+
+                    if let Some(folder) = save_folder {
+                        let ctx = FilenameContext {
+                            task_index: task.task_index,
+                            prompt: prompt.to_string(),
+                            seed,
+                            n: idx as u32 + 1,
+                            ext: String::new(),
+                            timestamp: now,
+                            ratio: aspect_ratio.to_string(),
+                            model: api_model.to_string(),
+                            workflow_id: workflow_id.clone(),
+                        };
+
+                        match save_image(
+                            Path::new(folder),
+                            &bytes,
+                            &task.file_template,
+                            ctx,
+                            &meta,
+                            &task.save_options,
+                        ) {
+                            Ok(saved) => {
+                                let path = saved.path.to_string_lossy().to_string();
+                                record.status = "saved".to_string();
+                                record.path = Some(path.clone());
+                                record.sha256 = Some(saved.sha256);
+                                if let Some(e) = &saved.encode_error {
+                                    diag.push_str(&format!(
+                                        "[Encode error #{}: {}, saved as original] ",
+                                        idx + 1,
+                                        e
+                                    ));
+                                    record.encode_error = Some(e.clone());
+                                }
+                                if let Some(video) = task.video_prompt.as_deref() {
+                                    match write_video_prompt(&saved.path, video) {
+                                        Ok(p) => {
+                                            record.video_prompt_path =
+                                                Some(p.to_string_lossy().to_string())
+                                        }
+                                        Err(e) => {
+                                            diag.push_str(&format!(
+                                                "[Video prompt error #{}: {}] ",
+                                                idx + 1,
+                                                e
+                                            ));
+                                            record.error = Some(e);
+                                        }
+                                    }
+                                }
+                                saved_path = Some(path);
+                                archived = Some((saved.bytes, saved.ext));
+                            }
+                            Err(e) => {
+                                diag.push_str(&format!("[Save error #{}: {}] ", idx + 1, e));
+                                record.error = Some(e);
+                            }
+                        }
+                    }
+
+                    // Decoding and hashing is CPU-bound, so keep it off the async workers.
+                    let hashes = if task.library || task.duplicate_threshold.is_some() {
+                        let data = bytes.clone();
+                        tokio::task::spawn_blocking(move || phash::hash_bytes(&data).ok())
+                            .await
+                            .ok()
+                            .flatten()
+                    } else {
+                        None
+                    };
+                    let mut similar: Option<(String, u32)> = None;
+                    if let (Some(threshold), Some(h)) = (task.duplicate_threshold, hashes) {
+                        let label = saved_path.clone().unwrap_or_else(|| match task.task_index {
+                            Some(t) => format!("task {} #{}", t, idx + 1),
+                            None => format!("#{}", idx + 1),
+                        });
+                        similar =
+                            phash::check_batch_duplicate(&duplicate_batch, &label, h, threshold);
+                        if let Some((other, distance)) = &similar {
+                            diag.push_str(&format!(
+                                "[Near-duplicate #{}: {} (distance {})] ",
+                                idx + 1,
+                                other,
+                                distance
+                            ));
+                            record.similar_to = Some(other.clone());
+                            record.similar_distance = Some(*distance);
+                        }
                     }
+
+                    if task.library {
+                        let original = SaveOptions {
+                            format: OutputFormat::Original,
+                            ..Default::default()
+                        };
+                        let stored = archived.or_else(|| {
+                            output::encode(bytes.clone(), &original, &meta)
+                                .ok()
+                                .map(|enc| (enc.bytes, enc.ext))
+                        });
+                        if let Some((data, ext)) = stored {
+                            let item = LibraryImage {
+                                prompt: prompt.to_string(),
+                                seed: Some(seed),
+                                aspect_ratio: Some(aspect_ratio.to_string()),
+                                model: Some(api_model.to_string()),
+                                account_id: task.account_id.clone(),
+                                account_email: task.account_email.clone(),
+                                workflow_id: Some(workflow_id.clone()),
+                                batch_id: task.batch_id.clone(),
+                                task_index: task.task_index,
+                                source_path: saved_path.clone(),
+                                created_at: now,
+                                phash: hashes.map(|h| phash::to_hex(h.phash)),
+                                dhash: hashes.map(|h| phash::to_hex(h.dhash)),
+                                ..Default::default()
+                            };
+                            if let Err(e) = library::add_image(&bytes, &data, ext, item) {
+                                diag.push_str(&format!("[Library error #{}: {}] ", idx + 1, e));
+                            }
+                        }
+                    }
+
+                    // Unsaved images stay in the backend and are served over the whisk-img scheme.
+                    let image_id = match saved_path {
+                        Some(_) => None,
+                        None => Some(image_cache::insert(bytes)),
+                    };
+                    let image_url = image_id.as_deref().map(image_cache::url_for);
+
+                    images.push(json!({
+                        "savedPath": saved_path,
+                        "videoPromptPath": record.video_prompt_path,
+                        "encodeError": record.encode_error,
+                        "imageId": image_id,
+                        "encodedImage": saved_path.as_deref().or(image_url.as_deref()),
+                        "similarTo": similar.as_ref().map(|(other, distance)| json!({
+                            "image": other,
+                            "distance": distance
+                        }))
+                    }));
+                }
+                Err(e) => {
+                    diag.push_str(&format!("[Decode error #{}: {}] ", idx + 1, e));
+                    record.error = Some(format!("Base64 decode: {}", e));
                 }
             }
-
-            images.push(json!({
-                "savedPath": saved_path,
-                "encodedImage": saved_path.as_deref().unwrap_or(&encoded_image)
-            }));
         }
+
+        entry.images.push(record);
     }
 
     diag.push_str("[API done] ");
 
+    entry.status = if images.is_empty() {
+        "failed"
+    } else if images.len() < count as usize {
+        "partial"
+    } else {
+        "success"
+    }
+    .to_string();
+    if images.is_empty() {
+        entry.error = Some("No images generated".to_string());
+    }
+    write_manifest(save_folder, &entry, &mut diag);
+
+    if !images.is_empty() && !workflow.fallback {
+        if let Err(e) = workflows::add_images(&workflow_id, &account_key, images.len() as u32) {
+            diag.push_str(&format!("[Workflow record error: {}] ", e));
+        }
+    }
+
     if images.is_empty() {
         return Ok(json!({
             "success": false,
@@ -563,89 +1238,200 @@ pub async fn generate_image_async(
     Ok(json!({
         "success": true,
         "images": images,
-        "projectLink": format!("https://labs.google/fx/tools/whisk/project/{}", workflow_id),
+        "prompt": prompt,
+        "stylePreset": task.style_preset,
+        "videoPrompt": task.video_prompt,
+        "workflowId": workflow_id,
+        "projectLink": project_link,
         "diagInfo": diag
     }))
 }
 
 pub async fn upload_ref_images_async(
     cookies: &str,
-    ref_images: Vec<String>,
+    ref_images: Vec<ReferenceInput>,
     existing_workflow_id: Option<String>,
+    account_id: Option<&str>,
+    workflow_name: Option<&str>,
+    max_edge: Option<u32>,
 ) -> Result<Value, String> {
     let client = build_client()?;
     let session_id = session_id_now();
+    let max_edge = max_edge
+        .or(settings::load_settings().reference_max_edge)
+        .unwrap_or(reference::DEFAULT_MAX_EDGE)
+        .clamp(reference::MIN_MAX_EDGE, reference::MAX_MAX_EDGE);
 
-    let mut workflow_id = existing_workflow_id.unwrap_or_default();
-    if workflow_id.is_empty() {
-        workflow_id = uuid::Uuid::new_v4().to_string();
-        if !cookies.is_empty() {
-            if let Some(wf_id) = create_workflow(&client, cookies, &session_id).await {
-                workflow_id = wf_id;
-            }
-        }
-    }
+    let account_key = account_id.unwrap_or_default();
+    let mut diag = String::new();
+    let workflow_id = resolve_workflow(
+        &client,
+        cookies,
+        &session_id,
+        existing_workflow_id,
+        account_id,
+        workflow_name,
+        &mut diag,
+    )
+    .await
+    .id;
 
     let mut uploaded: Vec<String> = Vec::new();
+    let mut references: Vec<Value> = Vec::new();
     let mut failed = 0u32;
+    let mut skipped = 0u32;
+    let mut cached = 0u32;
 
-    for ref_url in &ref_images {
-        if ref_url.starts_with("data:") {
-            if let Some(pos) = ref_url.find(",") {
-                let header = &ref_url[..pos];
-                let b64_data = &ref_url[pos + 1..];
-                let mime = header.replace("data:", "").replace(";base64", "");
-                let engine = base64::engine::general_purpose::STANDARD;
-                if let Ok(bytes) = engine.decode(b64_data) {
-                    match upload_reference_image(
-                        &client,
-                        cookies,
-                        &bytes,
-                        &mime,
-                        &workflow_id,
-                        &session_id,
-                    )
-                    .await
-                    {
-                        Ok(Some(name)) => uploaded.push(name),
-                        _ => failed += 1,
-                    }
-                }
+    for (index, ref_input) in ref_images.iter().enumerate() {
+        let ref_url = ref_input.input();
+        let mut result = json!({
+            "index": index,
+            "input": reference::input_label(ref_url),
+            "category": ref_input.category().name(),
+            "caption": ref_input.caption(),
+            "status": "failed",
+            "mediaName": null,
+            "errorCode": null,
+            "error": null,
+            "httpStatus": null
+        });
+
+        let bytes = match reference::read_input(ref_url) {
+            Ok(b) => b,
+            Err(e) => {
+                skipped += 1;
+                result["status"] = json!("skipped");
+                result["errorCode"] = json!(e.code);
+                result["error"] = json!(e.message);
+                references.push(result);
+                continue;
             }
-        } else if Path::new(ref_url).exists() {
-            if let Ok(bytes) = std::fs::read(ref_url) {
-                let ext = Path::new(ref_url)
-                    .extension()
-                    .and_then(|e| e.to_str())
-                    .unwrap_or("png")
-                    .to_lowercase();
-                let mime = match ext.as_str() {
-                    "jpg" | "jpeg" => "image/jpeg",
-                    "webp" => "image/webp",
-                    _ => "image/png",
-                };
-                match upload_reference_image(
-                    &client,
-                    cookies,
-                    &bytes,
-                    mime,
-                    &workflow_id,
-                    &session_id,
-                )
-                .await
-                {
-                    Ok(Some(name)) => uploaded.push(name),
-                    _ => failed += 1,
+        };
+
+        let sha256 = manifest::sha256_hex(&bytes);
+        result["sha256"] = json!(sha256);
+        let cache_key = upload_cache::CacheKey {
+            sha256: &sha256,
+            account_id: account_key,
+            workflow_id: &workflow_id,
+            category: ref_input.category().api_name(),
+            caption: ref_input.caption(),
+            max_edge,
+        };
+        if let Some(name) = upload_cache::lookup(&cache_key) {
+            cached += 1;
+            result["status"] = json!("uploaded");
+            result["cached"] = json!(true);
+            result["mediaName"] = json!(name);
+            uploaded.push(name);
+            references.push(result);
+            continue;
+        }
+
+        // Decoding and Lanczos resizing a camera photo takes long enough to stall the runtime.
+        let prepared = match tokio::task::spawn_blocking(move || {
+            reference::prepare_reference(&bytes, max_edge)
+        })
+        .await
+        .unwrap_or_else(|e| {
+            Err(reference::ReferenceError::new(
+                "prepare_failed",
+                e.to_string(),
+            ))
+        }) {
+            Ok(p) => p,
+            Err(e) => {
+                failed += 1;
+                result["errorCode"] = json!(e.code);
+                result["error"] = json!(e.message);
+                references.push(result);
+                continue;
+            }
+        };
+
+        if let (Value::Object(map), Ok(Value::Object(info))) =
+            (&mut result, serde_json::to_value(&prepared))
+        {
+            map.extend(info);
+        }
+        match upload_reference_image(
+            &client,
+            cookies,
+            &prepared.bytes,
+            prepared.mime,
+            ref_input,
+            &workflow_id,
+            &session_id,
+        )
+        .await
+        {
+            Ok(name) => {
+                if let Err(e) = upload_cache::store(&cache_key, &name) {
+                    diag.push_str(&format!("[Upload cache error #{}: {}] ", index + 1, e));
                 }
+                result["status"] = json!("uploaded");
+                result["mediaName"] = json!(name);
+                uploaded.push(name);
+            }
+            Err(e) => {
+                failed += 1;
+                result["errorCode"] = json!(e.code);
+                result["error"] = json!(e.message);
+                result["httpStatus"] = json!(e.http_status);
             }
         }
+        references.push(result);
     }
 
     Ok(json!({
         "success": true,
         "uploadedCount": uploaded.len(),
         "failedCount": failed,
+        "skippedCount": skipped,
+        "cachedCount": cached,
         "mediaNames": uploaded,
-        "workflowId": workflow_id
+        "references": references,
+        "workflowId": workflow_id,
+        "projectLink": workflows::project_link(&workflow_id),
+        "diagInfo": diag
     }))
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn explicit_model_conflicts_with_references() {
+        assert_eq!(resolve_model(None, false), Ok("IMAGEN_3_5"));
+        assert_eq!(resolve_model(Some(" imagen_4 "), false), Ok("IMAGEN_4"));
+        assert!(resolve_model(Some("nope"), false).is_err());
+        assert_eq!(resolve_model(None, true), Ok(RECIPE_MODEL));
+        assert_eq!(resolve_model(Some(""), true), Ok(RECIPE_MODEL));
+        assert_eq!(resolve_model(Some("r2i"), true), Ok(RECIPE_MODEL));
+        assert!(resolve_model(Some("IMAGEN_4"), true).is_err());
+    }
+
+    #[test]
+    fn video_prompt_pairs_with_the_saved_image() {
+        let dir = std::env::temp_dir().join(format!("autowhisk_vp_{}", uuid::Uuid::new_v4()));
+        std::fs::create_dir_all(&dir).unwrap();
+        std::fs::write(dir.join("img.png"), b"taken").unwrap();
+        std::fs::write(dir.join("img.txt"), "notes").unwrap();
+
+        let image = filename::reserve_unique_path(&dir, "img.png").unwrap();
+        let prompt = write_video_prompt(&image, "pan left").unwrap();
+        assert_eq!(prompt.file_stem(), image.file_stem());
+        assert_eq!(prompt, dir.join("img_1.txt"));
+        assert_eq!(std::fs::read_to_string(&prompt).unwrap(), "pan left");
+        assert_eq!(
+            std::fs::read_to_string(dir.join("img.txt")).unwrap(),
+            "notes"
+        );
+
+        let err = write_video_prompt(&image, "zoom in").unwrap_err();
+        assert!(err.contains("already exists"), "{}", err);
+        assert_eq!(std::fs::read_to_string(&prompt).unwrap(), "pan left");
+        let _ = std::fs::remove_dir_all(&dir);
+    }
+}
@@ -0,0 +1,261 @@
+use crate::settings::app_data_dir;
+use serde::{Deserialize, Serialize};
+use serde_json::{json, Value};
+use std::collections::HashMap;
+use std::fs;
+use std::path::PathBuf;
+use std::sync::Mutex;
+
+const PROJECT_URL: &str = "https://labs.google/fx/tools/whisk/project/";
+const MAX_NAME_LEN: usize = 100;
+
+static LOCK: Mutex<()> = Mutex::new(());
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+pub struct WorkflowRecord {
+    pub id: String,
+    #[serde(default)]
+    pub account_id: String,
+    #[serde(default)]
+    pub name: String,
+    #[serde(default)]
+    pub created_at: u64,
+    #[serde(default)]
+    pub last_used_at: u64,
+    #[serde(default)]
+    pub image_count: u32,
+}
+
+#[derive(Debug, Serialize, Deserialize, Clone, Default)]
+#[serde(rename_all = "camelCase")]
+struct WorkflowStore {
+    #[serde(default)]
+    workflows: Vec<WorkflowRecord>,
+    #[serde(default)]
+    defaults: HashMap<String, String>,
+}
+
+fn get_store_path() -> PathBuf {
+    app_data_dir().join("workflows.json")
+}
+
+fn now() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
+
+fn load_store() -> WorkflowStore {
+    match fs::read_to_string(get_store_path()) {
+        Ok(content) => serde_json::from_str(&content).unwrap_or_default(),
+        Err(_) => WorkflowStore::default(),
+    }
+}
+
+fn save_store(store: &WorkflowStore) -> Result<(), String> {
+    let json = serde_json::to_string_pretty(store).map_err(|e| e.to_string())?;
+    fs::write(get_store_path(), json).map_err(|e| format!("Failed to save: {}", e))
+}
+
+fn update_store<T>(f: impl FnOnce(&mut WorkflowStore) -> Result<T, String>) -> Result<T, String> {
+    let _guard = LOCK.lock().unwrap_or_else(|e| e.into_inner());
+    let mut store = load_store();
+    let out = f(&mut store)?;
+    save_store(&store)?;
+    Ok(out)
+}
+
+pub fn project_link(workflow_id: &str) -> String {
+    format!("{}{}", PROJECT_URL, workflow_id)
+}
+
+pub fn clean_name(name: &str) -> Option<String> {
+    let name: String = name
+        .split_whitespace()
+        .collect::<Vec<_>>()
+        .join(" ")
+        .chars()
+        .take(MAX_NAME_LEN)
+        .collect();
+    if name.is_empty() {
+        None
+    } else {
+        Some(name)
+    }
+}
+
+fn to_value(record: &WorkflowRecord, default_id: Option<&String>) -> Value {
+    json!({
+        "id": record.id,
+        "accountId": record.account_id,
+        "name": record.name,
+        "createdAt": record.created_at,
+        "lastUsedAt": record.last_used_at,
+        "imageCount": record.image_count,
+        "projectLink": project_link(&record.id),
+        "isDefault": default_id == Some(&record.id)
+    })
+}
+
+pub fn default_for(account_id: &str) -> Option<String> {
+    load_store().defaults.get(account_id).cloned()
+}
+
+pub fn record_workflow(id: &str, account_id: &str, name: &str) -> Result<(), String> {
+    update_store(|store| {
+        let ts = now();
+        match store.workflows.iter_mut().find(|w| w.id == id) {
+            Some(existing) => {
+                existing.name = name.to_string();
+                existing.last_used_at = ts;
+            }
+            None => store.workflows.push(WorkflowRecord {
+                id: id.to_string(),
+                account_id: account_id.to_string(),
+                name: name.to_string(),
+                created_at: ts,
+                last_used_at: ts,
+                image_count: 0,
+            }),
+        }
+        Ok(())
+    })
+}
+
+// Workflows reused from outside the app are recorded the first time images land in them.
+pub fn add_images(id: &str, account_id: &str, count: u32) -> Result<(), String> {
+    update_store(|store| {
+        let ts = now();
+        match store.workflows.iter_mut().find(|w| w.id == id) {
+            Some(existing) => {
+                existing.image_count += count;
+                existing.last_used_at = ts;
+            }
+            None => store.workflows.push(WorkflowRecord {
+                id: id.to_string(),
+                account_id: account_id.to_string(),
+                created_at: ts,
+                last_used_at: ts,
+                image_count: count,
+                ..Default::default()
+            }),
+        }
+        Ok(())
+    })
+}
+
+pub fn list_workflows(account_id: Option<&str>) -> Result<Value, String> {
+    let store = load_store();
+    let mut items: Vec<&WorkflowRecord> = store
+        .workflows
+        .iter()
+        .filter(|w| account_id.is_none() || Some(w.account_id.as_str()) == account_id)
+        .collect();
+    items.sort_by_key(|w| std::cmp::Reverse(w.last_used_at));
+    let list: Vec<Value> = items
+        .into_iter()
+        .map(|w| to_value(w, store.defaults.get(&w.account_id)))
+        .collect();
+    Ok(json!(list))
+}
+
+pub fn get_workflow(id: &str) -> Option<WorkflowRecord> {
+    load_store().workflows.into_iter().find(|w| w.id == id)
+}
+
+pub fn rename_workflow(id: &str, name: &str) -> Result<Value, String> {
+    let name = clean_name(name).ok_or("Workflow name is empty")?;
+    update_store(|store| {
+        let record = store
+            .workflows
+            .iter_mut()
+            .find(|w| w.id == id)
+            .ok_or_else(|| format!("Unknown workflow: {}", id))?;
+        record.name = name;
+        let record = record.clone();
+        Ok(to_value(&record, store.defaults.get(&record.account_id)))
+    })
+}
+
+pub fn set_default_workflow(account_id: &str, id: Option<&str>) -> Result<Value, String> {
+    if account_id.is_empty() {
+        return Err("Account id is required".to_string());
+    }
+    update_store(|store| {
+        match id {
+            Some(id) => {
+                let record = store
+                    .workflows
+                    .iter()
+                    .find(|w| w.id == id)
+                    .ok_or_else(|| format!("Unknown workflow: {}", id))?;
+                if !record.account_id.is_empty() && record.account_id != account_id {
+                    return Err("Workflow belongs to another account".to_string());
+                }
+                store
+                    .defaults
+                    .insert(account_id.to_string(), id.to_string());
+            }
+            None => {
+                store.defaults.remove(account_id);
+            }
+        }
+        Ok(json!({ "accountId": account_id, "defaultWorkflowId": id }))
+    })
+}
+
+pub fn forget_workflow(id: &str) -> Result<bool, String> {
+    update_store(|store| {
+        let before = store.workflows.len();
+        store.workflows.retain(|w| w.id != id);
+        store.defaults.retain(|_, v| v != id);
+        Ok(store.workflows.len() != before)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn unique(prefix: &str) -> String {
+        format!("{}-{}", prefix, uuid::Uuid::new_v4())
+    }
+
+    #[test]
+    fn record_rename_default_and_forget() {
+        let (account, other) = (unique("acc"), unique("acc"));
+        let (wf, foreign) = (unique("wf"), unique("wf"));
+        record_workflow(&wf, &account, "Batch one").unwrap();
+        record_workflow(&foreign, &other, "Not mine").unwrap();
+        add_images(&wf, &account, 3).unwrap();
+
+        let record = get_workflow(&wf).unwrap();
+        assert_eq!(record.account_id, account);
+        assert_eq!(record.name, "Batch one");
+        assert_eq!(record.image_count, 3);
+
+        let renamed = rename_workflow(&wf, "  Batch   two ").unwrap();
+        assert_eq!(renamed["name"], "Batch two");
+        assert!(rename_workflow(&wf, "   ").is_err());
+        assert!(rename_workflow(&unique("wf"), "x").is_err());
+
+        assert_eq!(
+            set_default_workflow(&account, Some(&foreign)).unwrap_err(),
+            "Workflow belongs to another account"
+        );
+        assert!(set_default_workflow("", Some(&wf)).is_err());
+        set_default_workflow(&account, Some(&wf)).unwrap();
+        assert_eq!(default_for(&account), Some(wf.clone()));
+        let listed = list_workflows(Some(&account)).unwrap();
+        assert_eq!(listed.as_array().unwrap().len(), 1);
+        assert_eq!(listed[0]["isDefault"], true);
+
+        assert!(forget_workflow(&wf).unwrap());
+        assert!(!forget_workflow(&wf).unwrap());
+        assert!(get_workflow(&wf).is_none());
+        assert_eq!(default_for(&account), None);
+        forget_workflow(&foreign).unwrap();
+    }
+}